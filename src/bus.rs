@@ -1,26 +1,347 @@
-
-//will add stuff for signals later
 //this is what carries signals from the cpu to DRAM and other devices
+//Each device owns a range of the physical address space and the bus
+//hands accesses to whoever owns the address. Anything nobody owns is an error
+//
+//dram, the clint and the plic are always there since the harts are wired straight to them.
+//Everything else (the uart, or whatever peripherals a board wants) is plugged in with
+//add_device, which wont let two things claim the same addresses
+//
+//lr/sc reservations live here too, since every store from every hart comes through the bus.
+//A reservation covers the aligned doubleword LR read from, and any store that touches it
+//breaks it. That includes the hart's own stores, which the spec leaves up to us
+//
+//when harts run on their own threads each one gets a Bus of its own (see attach). Those share
+//dram, and send everything else to the one home Bus behind a lock. The clint and plic are
+//copied back every so often so interrupts can be checked without taking the lock.
+//Stores from other threads dont come through our bus, so there sc goes by the version dram
+//keeps for each doubleword instead, which every store bumps
 #![allow(non_snake_case)]
+use std::any::Any;
+use std::sync::{Arc, Mutex, PoisonError};
+
 use crate::dram::Dram;
+#[cfg(test)]
+use crate::dram::{DRAM_BASE, DRAM_SIZE};
+use crate::clint::{Clint, CLINT_BASE, CLINT_SIZE};
+use crate::plic::{Plic, PLIC_BASE, PLIC_SIZE, PLIC_SOURCES};
+use crate::uart::{Uart, UART_BASE, UART_SIZE};
+use crate::device::Device;
+
+//what can go wrong talking to the bus
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum BusError {
+	Unmapped(u64), //no device lives at this address
+	Overlap(u64), //tried to map a device on top of something already there
+}
+
+//whether [addr, addr + size) sits entirely inside a device
+fn in_range(addr: u64, size: u64, base: u64, len: u64) -> bool {
+	addr >= base && addr.checked_add(size).is_some_and(|end| end <= base + len)
+}
+
+//whether [a, a + aLen) and [b, b + bLen) share any addresses
+fn overlaps(a: u64, aLen: u64, b: u64, bLen: u64) -> bool {
+	a < b.saturating_add(bLen) && b < a.saturating_add(aLen)
+}
+
+//a device plugged in at some range of addresses
+struct Mapping {
+	base: u64,
+	size: u64,
+	device: Box<dyn Device>,
+}
 
 pub struct Bus {
 	pub dram: Dram,
+	pub clint: Clint,
+	pub plic: Plic,
+	devices: Vec<Mapping>,
+	reservations: Vec<Option<Reservation>>, //for each hart, by hart id
+	shared: Option<Shared>, //set when this is one thread's view of the home bus
 }
 
+#[derive(Copy, Clone)]
+struct Reservation {
+	addr: u64, //exactly what lr read
+	size: u8,
+	version: u64, //of the doubleword in dram, when lr read it
+}
+
+struct Shared {
+	home: Arc<Mutex<Bus>>,
+	ticks: u64, //ticks the home devices havent been given yet
+	interval: u64, //how many instructions go by between catching up
+}
+
+//size of a reservation set
+const RESERVATION_SIZE: u64 = 8;
+
 impl Bus {
+	//default memory map, program loaded at the start of dram
+	#[cfg(test)]
 	pub fn New(code: Vec<u8>) -> Bus {
+		Bus::with_dram(Dram::New(code, DRAM_BASE, DRAM_SIZE)).expect("default dram overlaps a device")
+	}
+
+	//dram can go anywhere that doesnt land on the clint, plic or uart
+	pub fn with_dram(dram: Dram) -> Result<Bus, BusError> {
+		let (base, size) = (dram.base, dram.size());
+		if overlaps(base, size, CLINT_BASE, CLINT_SIZE) || overlaps(base, size, PLIC_BASE, PLIC_SIZE) {
+			return Err(BusError::Overlap(base));
+		}
+		let mut bus = Self {
+			dram,
+			clint: Clint::New(1),
+			plic: Plic::New(1),
+			devices: Vec::new(),
+			reservations: Vec::new(),
+			shared: None,
+		};
+		bus.add_device(UART_BASE, UART_SIZE, Box::new(Uart::New())).map_err(|_| BusError::Overlap(base))?;
+		Ok(bus)
+	}
+
+	//what a hart holds on to while another hart has the real bus. Nothing is mapped
+	pub fn empty() -> Bus {
 		Self {
-			dram: Dram::New(code),
+			dram: Dram::New(Vec::new(), 0, 0),
+			clint: Clint::New(0),
+			plic: Plic::New(0),
+			devices: Vec::new(),
+			reservations: Vec::new(),
+			shared: None,
 		}
 	}
 
-	pub fn load(&self, addr: u64, size: u8) -> u64 {
-		self.dram.load(addr, size)
+	//a bus for a hart on another thread. It shares dram with home, and the rest goes through
+	//home. Every interval instructions the devices catch up and our clint and plic get refreshed
+	pub fn attach(home: &Arc<Mutex<Bus>>, interval: u64) -> Bus {
+		let mut bus = Bus::empty();
+		bus.dram = home.lock().unwrap_or_else(PoisonError::into_inner).dram.share();
+		bus.shared = Some(Shared {
+			home: home.clone(),
+			ticks: 0,
+			interval: interval.max(1),
+		});
+		bus.sync();
+		bus
 	}
 
-	pub fn store(&mut self, addr: u64, data: u64, size: u8) {
-		self.dram.store(addr, data,size);
+	//gives the home devices the ticks they're owed, and copies the clint and plic back
+	pub fn sync(&mut self) {
+		self.with_home(|_| ());
+	}
+
+	//runs f on the home bus. Everything f did is visible in our clint and plic afterwards
+	fn with_home<T>(&mut self, f: impl FnOnce(&mut Bus) -> T) -> T {
+		let shared = self.shared.as_mut().expect("bus isnt attached to anything");
+		let mut home = shared.home.lock().unwrap_or_else(PoisonError::into_inner);
+		for _ in 0..shared.ticks {
+			home.tick();
+		}
+		shared.ticks = 0;
+		let result = f(&mut home);
+		self.clint = home.clint.clone();
+		self.plic = home.plic.clone();
+		result
+	}
+
+	//rewires the clint and plic for this many harts. Their state starts over
+	pub fn set_harts(&mut self, harts: usize) {
+		self.clint = Clint::New(harts);
+		self.plic = Plic::New(harts);
+		self.reservations = vec![None; harts];
+	}
+
+	//maps a device at [base, base + size). The device is reset on the way in
+	pub fn add_device(&mut self, base: u64, size: u64, mut device: Box<dyn Device>) -> Result<(), BusError> {
+		if size == 0 || base.checked_add(size).is_none() {
+			return Err(BusError::Overlap(base));
+		}
+		let taken = overlaps(base, size, self.dram.base, self.dram.size())
+			|| overlaps(base, size, CLINT_BASE, CLINT_SIZE)
+			|| overlaps(base, size, PLIC_BASE, PLIC_SIZE)
+			|| self.devices.iter().any(|m| overlaps(base, size, m.base, m.size));
+		if taken {
+			return Err(BusError::Overlap(base));
+		}
+		device.reset();
+		self.devices.push(Mapping { base, size, device });
+		Ok(())
+	}
+
+	//the first plugged in device of type T, for poking at it from outside the guest
+	pub fn device<T: Device>(&mut self) -> Option<&mut T> {
+		self.devices.iter_mut().find_map(|m| (m.device.as_mut() as &mut dyn Any).downcast_mut::<T>())
+	}
+
+	//lets devices move on by one instruction, and passes their interrupt lines on to the plic
+	//devices sharing a source are or'd together
+	pub fn tick(&mut self) {
+		if let Some(shared) = &mut self.shared {
+			shared.ticks += 1;
+			if shared.ticks >= shared.interval {
+				self.sync();
+			}
+			return;
+		}
+		self.clint.tick();
+		let mut wired = 0u64;
+		let mut high = 0u64;
+		for m in self.devices.iter_mut() {
+			m.device.tick();
+			if let Some(irq) = m.device.irq().filter(|&irq| irq < PLIC_SOURCES) {
+				wired |= 1 << irq;
+				if m.device.irq_pending() {
+					high |= 1 << irq;
+				}
+			}
+		}
+		for source in 1..PLIC_SOURCES {
+			if wired & (1 << source) != 0 {
+				self.plic.set_irq(source, high & (1 << source) != 0);
+			}
+		}
+	}
+
+	//LR. Loads size bytes at addr and reserves them, replacing whatever the hart had reserved before
+	pub fn load_reserved(&mut self, hart: usize, addr: u64, size: u8) -> Result<u64, BusError> {
+		let (data, version) = if self.dram.contains(addr, size as u64) {
+			self.dram.load_reserved(addr - self.dram.base, size)
+		}
+		else {
+			(self.load(addr, size)?, 0)
+		};
+		self.reserve(hart, addr, size, version);
+		Ok(data)
+	}
+
+	//reserves size bytes at addr, as if an lr had read them under version
+	pub fn reserve(&mut self, hart: usize, addr: u64, size: u8, version: u64) {
+		if self.reservations.len() <= hart {
+			self.reservations.resize(hart + 1, None);
+		}
+		self.reservations[hart] = Some(Reservation { addr, size, version });
+	}
+
+	//whether the hart still holds a reservation on addr
+	pub fn reserved(&self, hart: usize, addr: u64) -> bool {
+		self.reservations.get(hart).copied().flatten()
+			.is_some_and(|r| r.addr & !(RESERVATION_SIZE - 1) == addr & !(RESERVATION_SIZE - 1))
+	}
+
+	//SC. Stores data if the hart still has a reservation on addr, and says whether it did.
+	//The reservation is gone afterwards either way
+	pub fn store_conditional(&mut self, hart: usize, addr: u64, data: u64, size: u8) -> Result<bool, BusError> {
+		let held = self.reservations.get(hart).copied().flatten().filter(|_| self.reserved(hart, addr));
+		self.cancel_reservation(hart);
+		let Some(r) = held else {
+			return Ok(false);
+		};
+		if self.shared.is_some() && self.dram.contains(addr, size as u64) {
+			if r.addr != addr || r.size != size {
+				return Ok(false);
+			}
+			return Ok(self.dram.store_conditional(addr - self.dram.base, size, r.version, data));
+		}
+		self.store(addr, data, size)?;
+		Ok(true)
+	}
+
+	pub fn cancel_reservation(&mut self, hart: usize) {
+		if let Some(r) = self.reservations.get_mut(hart) {
+			*r = None;
+		}
+	}
+
+	//a store to [addr, addr + size) happened
+	fn break_reservations(&mut self, addr: u64, size: u64) {
+		for r in self.reservations.iter_mut() {
+			if r.is_some_and(|r| overlaps(addr, size, r.addr & !(RESERVATION_SIZE - 1), RESERVATION_SIZE)) {
+				*r = None;
+			}
+		}
 	}
-}
 
+	//&mut because some device registers (like the plic claim) change when read
+	pub fn load(&mut self, addr: u64, size: u8) -> Result<u64, BusError> {
+		if self.dram.contains(addr, size as u64) {
+			return Ok(self.dram.load(addr - self.dram.base, size));
+		}
+		if self.shared.is_some() {
+			return self.with_home(|home| home.load(addr, size));
+		}
+		if in_range(addr, size as u64, CLINT_BASE, CLINT_SIZE) {
+			return Ok(self.clint.load(addr - CLINT_BASE, size));
+		}
+		if in_range(addr, size as u64, PLIC_BASE, PLIC_SIZE) {
+			return Ok(self.plic.load(addr - PLIC_BASE, size));
+		}
+		for m in self.devices.iter_mut() {
+			if in_range(addr, size as u64, m.base, m.size) {
+				return Ok(m.device.load(addr - m.base, size));
+			}
+		}
+		Err(BusError::Unmapped(addr))
+	}
+
+	pub fn store(&mut self, addr: u64, data: u64, size: u8) -> Result<(), BusError> {
+		self.break_reservations(addr, size as u64);
+		if self.dram.contains(addr, size as u64) {
+			self.dram.store(addr - self.dram.base, data, size);
+			return Ok(());
+		}
+		if self.shared.is_some() {
+			return self.with_home(|home| home.store(addr, data, size));
+		}
+		if in_range(addr, size as u64, CLINT_BASE, CLINT_SIZE) {
+			self.clint.store(addr - CLINT_BASE, data, size);
+			return Ok(());
+		}
+		if in_range(addr, size as u64, PLIC_BASE, PLIC_SIZE) {
+			self.plic.store(addr - PLIC_BASE, data, size);
+			return Ok(());
+		}
+		for m in self.devices.iter_mut() {
+			if in_range(addr, size as u64, m.base, m.size) {
+				m.device.store(addr - m.base, data, size);
+				return Ok(());
+			}
+		}
+		Err(BusError::Unmapped(addr))
+	}
+
+	//an AMO. Replaces size bytes at addr with op(old) without anyone getting in between,
+	//and hands back old
+	pub fn amo(&mut self, addr: u64, size: u8, op: impl Fn(u64) -> u64) -> Result<u64, BusError> {
+		self.break_reservations(addr, size as u64);
+		if self.dram.contains(addr, size as u64) {
+			return Ok(self.dram.fetch_update(addr - self.dram.base, size, op));
+		}
+		if self.shared.is_some() {
+			return self.with_home(|home| home.amo(addr, size, op));
+		}
+		let old = self.load(addr, size)?;
+		self.store(addr, op(old), size)?;
+		Ok(old)
+	}
+
+	//copies a chunk of bytes out of memory. Only dram, devices dont get read in bulk
+	pub fn read_bytes(&mut self, addr: u64, len: u64) -> Result<Vec<u8>, BusError> {
+		if self.dram.contains(addr, len) {
+			return Ok(self.dram.read_bytes(addr - self.dram.base, len));
+		}
+		Err(BusError::Unmapped(addr))
+	}
+
+	//copies a chunk of bytes into memory. Used for loading program images
+	pub fn write_bytes(&mut self, addr: u64, bytes: &[u8]) -> Result<(), BusError> {
+		self.break_reservations(addr, bytes.len() as u64);
+		if self.dram.contains(addr, bytes.len() as u64) {
+			self.dram.write_bytes(addr - self.dram.base, bytes);
+			return Ok(());
+		}
+		Err(BusError::Unmapped(addr))
+	}
+}
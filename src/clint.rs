@@ -0,0 +1,113 @@
+//core local interruptor
+//gives each hart a software interrupt (msip) and a timer interrupt (mtimecmp),
+//and holds the one mtime everyone shares
+//register layout is the sifive one that everything expects:
+//msip at +0 (4 bytes a hart), mtimecmp at +0x4000 (8 bytes a hart), mtime at +0xbff8
+//
+//mtime goes up by one every instruction rather than following wall clock time,
+//so runs are repeatable
+
+use crate::device::Device;
+
+pub const CLINT_BASE: u64 = 0x0200_0000;
+pub const CLINT_SIZE: u64 = 0x10000;
+
+const MSIP_OFFSET: u64 = 0x0;
+const MTIMECMP_OFFSET: u64 = 0x4000;
+const MTIME_OFFSET: u64 = 0xbff8;
+
+#[derive(Clone)]
+pub struct Clint {
+	pub msip: Vec<u32>,
+	pub mtimecmp: Vec<u64>,
+	pub mtime: u64,
+}
+
+impl Clint {
+	pub fn New(harts: usize) -> Clint {
+		Clint {
+			msip: vec![0; harts],
+			//timer shouldnt go off until someone sets it up
+			mtimecmp: vec![u64::MAX; harts],
+			mtime: 0,
+		}
+	}
+
+	pub fn timer_pending(&self, hart: usize) -> bool {
+		self.mtimecmp.get(hart).is_some_and(|&cmp| self.mtime >= cmp)
+	}
+
+	pub fn software_pending(&self, hart: usize) -> bool {
+		self.msip.get(hart).is_some_and(|&msip| msip != 0)
+	}
+}
+
+impl Device for Clint {
+	//offset is relative to the start of the clint
+	//anything that isnt a register reads as 0
+	fn load(&mut self, offset: u64, size: u8) -> u64 {
+		let (reg, regOffset) = match offset {
+			MSIP_OFFSET..=0x3fff => {
+				let hart = (offset / 4) as usize;
+				match self.msip.get(hart) {
+					Some(&msip) => (msip as u64, offset % 4),
+					None => return 0,
+				}
+			},
+			MTIMECMP_OFFSET..=0xbff7 => {
+				let hart = ((offset - MTIMECMP_OFFSET) / 8) as usize;
+				match self.mtimecmp.get(hart) {
+					Some(&cmp) => (cmp, (offset - MTIMECMP_OFFSET) % 8),
+					None => return 0,
+				}
+			},
+			MTIME_OFFSET..=0xbfff => (self.mtime, offset - MTIME_OFFSET),
+			_ => return 0,
+		};
+		let val = reg >> (regOffset * 8);
+		match size {
+			8 => val,
+			_ => val & ((1u64 << (size as u64 * 8)) - 1),
+		}
+	}
+
+	fn store(&mut self, offset: u64, data: u64, size: u8) {
+		match offset {
+			MSIP_OFFSET..=0x3fff => {
+				//only bit 0 does anything
+				if let Some(msip) = self.msip.get_mut((offset / 4) as usize) {
+					if offset.is_multiple_of(4) {
+						*msip = (data & 1) as u32;
+					}
+				}
+			},
+			MTIMECMP_OFFSET..=0xbff7 => {
+				let regOffset = (offset - MTIMECMP_OFFSET) % 8;
+				if let Some(cmp) = self.mtimecmp.get_mut(((offset - MTIMECMP_OFFSET) / 8) as usize) {
+					*cmp = write_part(*cmp, regOffset, data, size);
+				}
+			},
+			MTIME_OFFSET..=0xbfff => {
+				self.mtime = write_part(self.mtime, offset - MTIME_OFFSET, data, size);
+			},
+			_ => {},
+		}
+	}
+
+	fn reset(&mut self) {
+		*self = Clint::New(self.msip.len());
+	}
+
+	fn tick(&mut self) {
+		self.mtime = self.mtime.wrapping_add(1);
+	}
+}
+
+//64 bit registers can be written 4 bytes at a time (rv32 software does this to mtimecmp)
+fn write_part(old: u64, offset: u64, data: u64, size: u8) -> u64 {
+	if size == 8 {
+		return data;
+	}
+	let mask = ((1u64 << (size as u64 * 8)) - 1) << (offset * 8);
+	(old & !mask) | ((data << (offset * 8)) & mask)
+}
@@ -0,0 +1,82 @@
+//the host side of the uart
+//puts the terminal into raw mode (no line buffering or echo, so the guest sees every key
+//as it is typed) and has a thread sitting on stdin so the emulator never blocks waiting for input.
+//Output processing stays on though, otherwise every \n the guest prints stops turning into \r\n
+//
+//raw mode means ctrl-c goes to the guest rather than killing us, so like qemu,
+//ctrl-a then x quits. The terminal gets put back however we go: that exit, or the
+//console being dropped (main drops the machine before exiting with a status)
+
+use std::io::{self, Read};
+use std::process::{self, Command, Stdio};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+
+const CTRL_A: u8 = 0x01;
+
+pub struct Console {
+	input: Receiver<u8>,
+	saved: Option<String>, //terminal settings to go back to, if stdin was a terminal
+}
+
+//runs stty on our stdin. Fails if stdin isnt a terminal
+fn stty(args: &[&str]) -> Option<String> {
+	let out = Command::new("stty")
+		.args(args)
+		.stdin(Stdio::inherit())
+		.stderr(Stdio::null())
+		.output()
+		.ok()?;
+	if !out.status.success() {
+		return None;
+	}
+	Some(String::from_utf8_lossy(&out.stdout).trim().to_string())
+}
+
+fn restore(saved: &Option<String>) {
+	if let Some(settings) = saved {
+		stty(&[settings.as_str()]);
+	}
+}
+
+impl Console {
+	pub fn New() -> Console {
+		let saved = stty(&["-g"]);
+		if saved.is_some() {
+			stty(&["raw", "opost", "-echo"]);
+		}
+		let (tx, rx) = mpsc::channel();
+		let threadSaved = saved.clone();
+		thread::spawn(move || {
+			let mut stdin = io::stdin();
+			let mut byte = [0u8; 1];
+			let mut escape = false;
+			//stops at eof, or when the emulator goes away
+			while let Ok(1) = stdin.read(&mut byte) {
+				if escape && byte[0] == b'x' {
+					restore(&threadSaved);
+					process::exit(0);
+				}
+				escape = byte[0] == CTRL_A;
+				if tx.send(byte[0]).is_err() {
+					break;
+				}
+			}
+		});
+		Console {
+			input: rx,
+			saved,
+		}
+	}
+
+	//next byte typed, if there is one
+	pub fn read(&mut self) -> Option<u8> {
+		self.input.try_recv().ok()
+	}
+}
+
+impl Drop for Console {
+	fn drop(&mut self) {
+		restore(&self.saved);
+	}
+}
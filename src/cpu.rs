@@ -2,7 +2,11 @@
 
 use crate::regs::*;
 use crate::bus::Bus;
+use crate::trap::{Exception, Interrupt, INTERRUPT_PRIORITY};
 use std::cmp;
+use std::num::FpCategory;
+use std::sync::atomic::{fence, Ordering};
+use fpu::{SINGLE, DOUBLE};
 
 //Struct for Cpu
 pub struct Cpu {
@@ -13,55 +17,347 @@ pub struct Cpu {
 	pub pc: u64, //program counter
 	pub bus: Bus,
 	pub prv: privilege_level, //privilege
+	pub wfi: bool, //parked on a wfi until an interrupt is pending
+	pub tlb: Tlb, //cached translations
+	pub pmp: Pmp, //decoded pmp regions
+	pub instLen: u64, //size of the instruction being run, 2 if it was compressed
+	pub instBits: u32, //the instruction being run (expanded if it was compressed), for mtval
+	pub hostCalls: bool, //ecall is a syscall for the host (newlib mode) rather than a trap
+	pub ecalled: bool, //set by an ecall in hostCalls mode, until the host services it
+	pub semihosting: bool, //an ebreak in the semihosting sequence is a request for the host
+	pub semihostCalled: bool, //same as ecalled, for semihosting requests
 }
 
-//rounding mode for float instructoins
-enum rounding_mode {
-	RNE = 0,
-	RTZ = 1,
-	RDN = 2,
-	RUP = 3,
-	RMM = 4,
-	DYN = 7,
-}
+//the instructions either side of the ebreak that make it a semihosting call rather than
+//a breakpoint: slli x0, x0, 0x1f and srai x0, x0, 7. Both are nops
+const SEMIHOST_ENTRY: u64 = 0x01f01013;
+const SEMIHOST_EXIT: u64 = 0x40705013;
+
+//what any float op that produces a NaN gives back
+const CANONICAL_NAN_S: u32 = 0x7fc0_0000;
 
 //privilege elvel of the CPU
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
 pub enum privilege_level {
 	U = 0, //user
 	S = 1, //supervisor
 	M = 3, //machine
 }
 
+impl privilege_level {
+	//for turning the mpp/spp fields of mstatus back into a privilege level
+	//2 is reserved, so just treat it as user
+	pub fn from_bits(bits: u64) -> privilege_level {
+		match bits & 0x3 {
+			3 => privilege_level::M,
+			1 => privilege_level::S,
+			_ => privilege_level::U,
+		}
+	}
+}
+
 impl Cpu {
 	//Initialising stuff for a Cpu
+	//with the default memory map. Only the tests want that now, main goes through with_bus
+	#[cfg(test)]
 	pub fn new(code: Vec<u8>) -> Cpu  {
+		Cpu::with_bus(Bus::New(code))
+	}
+
+	//for when the memory map isnt the default one
+	pub fn with_bus(bus: Bus) -> Cpu {
 		let mut cpu = Cpu {
 			regs: [0;32], //set all regs to 0. Doesnt really matter except
 						//that r0 is a special register - the zero register. Must always be = 0
 			fregs: [0.0;32],
 			fcsr: 0,
 			csrs: [0;4096],
-			pc: 0,
-			bus: Bus::New(code),
+			pc: bus.dram.base, //start executing from the start of dram
+			bus,
 			prv: privilege_level::M,
+			wfi: false,
+			tlb: Tlb::New(),
+			pmp: Pmp::New(),
+			instLen: 4,
+			instBits: 0,
+			hostCalls: false,
+			ecalled: false,
+			semihosting: false,
+			semihostCalled: false,
 		};
-		cpu.regs[2] = 1024*1024; //r2 is stack register. Stack grows downwards,
-								//so r2 must be a non zero value. THis val was chosen at random
+		cpu.regs[2] = cpu.bus.dram.base + cpu.bus.dram.size(); //r2 is stack register. Stack grows downwards,
+								//so start it at the top of dram
+		//fills in the hardwired fields. The float unit starts on so programs that use it
+		//dont need anything to turn it on for them
+		cpu.store_csr(MSTATUS, MSTATUS_FS_INITIAL);
 		return cpu;
 	}
 	
 	//emulating fetch aspect of execution cycles
-	pub fn fetch(&self) -> u32 {
-		let pc = self.pc as usize; //has to be usize for indexing
-		let instruction = self.bus.load(self.pc, 4) as u32;
-					  
-		return instruction;
+	//compressed instructions come back already expanded to their 32 bit version
+	//only the first half is read to start with, so a compressed instruction right at the
+	//end of a page doesnt fault on the next one
+	pub fn fetch(&mut self) -> Result<u32, Exception> {
+		if self.pc & 0x1 != 0 {
+			return Err(Exception::InstructionAddressMisaligned(self.pc));
+		}
+		let low = self.read_virt(self.pc, 2, AccessType::Instruction)? as u16;
+		if low & 0x3 != 0x3 {
+			self.instLen = 2;
+			return rvc::expand(low);
+		}
+		self.instLen = 4;
+		Ok(self.read_virt(self.pc, 4, AccessType::Instruction)? as u32)
+	}
+
+	//whether the ebreak at pc is the middle of the semihosting sequence. All three have
+	//to be full size instructions, so a c.ebreak never counts
+	fn semihost_sequence(&mut self) -> bool {
+		self.instLen == 4
+			&& self.read_virt(self.pc.wrapping_sub(4), 4, AccessType::Instruction).ok() == Some(SEMIHOST_ENTRY)
+			&& self.read_virt(self.pc.wrapping_add(4), 4, AccessType::Instruction).ok() == Some(SEMIHOST_EXIT)
+	}
+
+	//memory accesses from instructions go through these rather than straight to the bus
+	//so that they get translated, and bus errors turn into the right kind of exception
+	pub fn load(&mut self, addr: u64, size: u8) -> Result<u64, Exception> {
+		self.read_virt(addr, size, AccessType::Load)
+	}
+
+	pub fn store(&mut self, addr: u64, data: u64, size: u8) -> Result<(), Exception> {
+		self.write_virt(addr, data, size)
+	}
+
+	//AMOs have to be naturally aligned, and any fault they cause is reported as a store fault
+	//op gets the size bytes that were there and gives back what to replace them with.
+	//The whole thing happens in one go on the bus, and the old value comes back
+	pub fn amo(&mut self, addr: u64, size: u8, op: impl Fn(u64) -> u64) -> Result<u64, Exception> {
+		if !addr.is_multiple_of(size as u64) {
+			return Err(Exception::StoreAddressMisaligned(addr));
+		}
+		let paddr = self.translate_store(addr, size)?;
+		self.bus.amo(paddr, size, op).map_err(|_| Exception::StoreAccessFault(addr))
+	}
+
+	//jumps and taken branches have to land on an instruction boundary
+	//(2 bytes, since compressed instructions can be anywhere)
+	fn jump(&mut self, target: u64) -> Result<(), Exception> {
+		if target & 0x1 != 0 {
+			return Err(Exception::InstructionAddressMisaligned(target));
+		}
+		//the sub is because we have an unconditional add to pc each step
+		self.pc = target.wrapping_sub(self.instLen);
+		Ok(())
+	}
+
+	//for an instruction that got past decode but turned out not to be allowed
+	fn illegal(&self) -> Exception {
+		Exception::IllegalInstruction(self.instBits as u64)
+	}
+
+	//float instructions are illegal while mstatus.FS is off. Anything that gets through
+	//marks the float state dirty (even if it didnt actually change anything, which is allowed)
+	fn check_fs(&mut self, inst: u32) -> Result<u32, Exception> {
+		if let 7 | 39 | 67 | 71 | 75 | 79 | 83 = inst & 0x7f {
+			let status = self.load_csr(MSTATUS);
+			if status & MSTATUS_FS == 0 {
+				return Err(Exception::IllegalInstruction(inst as u64));
+			}
+			self.store_csr(MSTATUS, status | MSTATUS_FS);
+		}
+		Ok(inst)
+	}
+
+	//runs a single instruction, taking a trap if anything goes wrong
+	//pending interrupts get taken before the instruction
+	pub fn step(&mut self) {
+		self.bus.tick();
+		self.update_mip();
+		//a hart sitting on a wfi doesnt do anything until some interrupt is pending
+		//(whether or not its actually enabled)
+		if self.wfi {
+			if self.load_csr(MIP) & self.load_csr(MIE) == 0 {
+				return;
+			}
+			self.wfi = false;
+		}
+		self.csrs[MCYCLE] = self.csrs[MCYCLE].wrapping_add(1);
+		if let Some(irq) = self.pending_interrupt() {
+			self.take_interrupt(irq);
+			return;
+		}
+		let pc = self.pc;
+		let result = self.fetch()
+			.inspect(|&inst| self.instBits = inst)
+			.and_then(|inst| self.check_fs(inst))
+			.and_then(Cpu::decode)
+			.and_then(|inst| self.execute(inst));
+		match result {
+			Ok(()) => {
+				self.pc = self.pc.wrapping_add(self.instLen);
+				self.csrs[MINSTRET] = self.csrs[MINSTRET].wrapping_add(1);
+			},
+			Err(exc) => {
+				self.pc = pc;
+				self.take_trap(exc);
+			},
+		}
+		self.regs[0] = 0;
+	}
+
+	//copies the interrupt lines from the devices into mip
+	//seip is driven by the plic here too, so software writes to it dont stick
+	pub fn update_mip(&mut self) {
+		let hart = self.load_csr(MHARTID) as usize;
+		let mut mip = self.load_csr(MIP) & !(MIP_MTIP | MIP_MSIP | MIP_MEIP | MIP_SEIP);
+		if self.bus.clint.timer_pending(hart) {
+			mip |= MIP_MTIP;
+		}
+		if self.bus.clint.software_pending(hart) {
+			mip |= MIP_MSIP;
+		}
+		if self.bus.plic.interrupt(2 * hart) {
+			mip |= MIP_MEIP;
+		}
+		if self.bus.plic.interrupt(2 * hart + 1) {
+			mip |= MIP_SEIP;
+		}
+		self.store_csr(MIP, mip);
+	}
+
+	//highest priority interrupt thats pending, enabled, and allowed at this privilege level
+	//machine interrupts are always on below M, supervisor ones (delegated through mideleg)
+	//are always on in U, and never taken in M
+	pub fn pending_interrupt(&self) -> Option<Interrupt> {
+		let pending = self.load_csr(MIP) & self.load_csr(MIE);
+		if pending == 0 {
+			return None;
+		}
+		let status = self.load_csr(MSTATUS);
+		let mideleg = self.load_csr(MIDELEG);
+		let mEnabled = self.prv != privilege_level::M || (status & MSTATUS_MIE) != 0;
+		let sEnabled = self.prv == privilege_level::U || (self.prv == privilege_level::S && (status & MSTATUS_SIE) != 0);
+		INTERRUPT_PRIORITY.iter().copied().find(|irq| {
+			if pending & irq.bit() == 0 {
+				return false;
+			}
+			if mideleg & irq.bit() != 0 { sEnabled } else { mEnabled }
+		})
+	}
+
+	pub fn take_trap(&mut self, exc: Exception) {
+		let delegated = self.load_csr(MEDELEG) & (1 << exc.code()) != 0;
+		self.trap(exc.code(), exc.tval(), delegated);
+	}
+
+	//interrupts set the top bit of the cause, and can use vectored mode
+	pub fn take_interrupt(&mut self, irq: Interrupt) {
+		self.wfi = false;
+		let delegated = self.load_csr(MIDELEG) & irq.bit() != 0;
+		self.trap((1 << 63) | irq.code(), 0, delegated);
+	}
+
+	//traps go to machine mode, unless they happened below M and medeleg/mideleg
+	//says to hand them to supervisor mode
+	//a trap also loses any lr reservation, so an sc cant succeed across a context switch
+	fn trap(&mut self, cause: u64, tval: u64, delegated: bool) {
+		self.bus.cancel_reservation(self.load_csr(MHARTID) as usize);
+		let mut status = self.load_csr(MSTATUS);
+		let interrupt = (cause >> 63) != 0;
+		if self.prv != privilege_level::M && delegated {
+			self.store_csr(SEPC, self.pc);
+			self.store_csr(SCAUSE, cause);
+			self.store_csr(STVAL, tval);
+			let sie = (status & MSTATUS_SIE) != 0;
+			status &= !(MSTATUS_SPIE | MSTATUS_SIE | MSTATUS_SPP);
+			if sie {
+				status |= MSTATUS_SPIE;
+			}
+			if self.prv == privilege_level::S {
+				status |= MSTATUS_SPP;
+			}
+			self.store_csr(MSTATUS, status);
+			self.prv = privilege_level::S;
+			self.pc = Cpu::trap_vector(self.load_csr(STVEC), cause, interrupt);
+			return;
+		}
+		self.store_csr(MEPC, self.pc);
+		self.store_csr(MCAUSE, cause);
+		self.store_csr(MTVAL, tval);
+		//stash the old interrupt enable and privilege, and disable interrupts
+		let mie = (status & MSTATUS_MIE) != 0;
+		status &= !(MSTATUS_MPIE | MSTATUS_MIE | MSTATUS_MPP);
+		if mie {
+			status |= MSTATUS_MPIE;
+		}
+		status |= (self.prv as u64) << MSTATUS_MPP_SHIFT;
+		self.store_csr(MSTATUS, status);
+		self.prv = privilege_level::M;
+		self.pc = Cpu::trap_vector(self.load_csr(MTVEC), cause, interrupt);
+	}
+
+	//bottom two bits of xtvec are the mode
+	//exceptions always go to the base, interrupts go to base + 4*cause in vectored mode
+	fn trap_vector(tvec: u64, cause: u64, interrupt: bool) -> u64 {
+		let base = tvec & !0x3;
+		if interrupt && tvec & 0x3 == 1 {
+			return base + 4 * (cause & !(1 << 63));
+		}
+		base
+	}
+
+	//mret - go back to whatever privilege level mpp says, restoring the interrupt enable
+	fn mret(&mut self) -> Result<(), Exception> {
+		if self.prv != privilege_level::M {
+			return Err(Exception::IllegalInstruction(0x30200073));
+		}
+		self.bus.cancel_reservation(self.load_csr(MHARTID) as usize);
+		let mut status = self.load_csr(MSTATUS);
+		let prv = privilege_level::from_bits((status & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT);
+		let mpie = (status & MSTATUS_MPIE) != 0;
+		status &= !(MSTATUS_MIE | MSTATUS_MPP);
+		status |= MSTATUS_MPIE;
+		if mpie {
+			status |= MSTATUS_MIE;
+		}
+		if prv != privilege_level::M {
+			status &= !MSTATUS_MPRV;
+		}
+		self.store_csr(MSTATUS, status);
+		self.prv = prv;
+		self.pc = self.load_csr(MEPC).wrapping_sub(self.instLen);
+		Ok(())
+	}
+
+	//sret - same deal for supervisor mode. tsr lets machine mode catch these
+	fn sret(&mut self) -> Result<(), Exception> {
+		let mut status = self.load_csr(MSTATUS);
+		if self.prv == privilege_level::U || (self.prv == privilege_level::S && (status & MSTATUS_TSR) != 0) {
+			return Err(Exception::IllegalInstruction(0x10200073));
+		}
+		self.bus.cancel_reservation(self.load_csr(MHARTID) as usize);
+		let prv = if (status & MSTATUS_SPP) != 0 { privilege_level::S } else { privilege_level::U };
+		let spie = (status & MSTATUS_SPIE) != 0;
+		status &= !(MSTATUS_SIE | MSTATUS_SPP | MSTATUS_MPRV);
+		status |= MSTATUS_SPIE;
+		if spie {
+			status |= MSTATUS_SIE;
+		}
+		self.store_csr(MSTATUS, status);
+		self.prv = prv;
+		self.pc = self.load_csr(SEPC).wrapping_sub(self.instLen);
+		Ok(())
 	}
 
 	//riscv NaN boxs f32s into the lower 32 bits of an f64
 	//have functions to box and unbox these f32s
+	//anything that isnt properly boxed (top 32 bits all ones) reads as the canonical NaN
 	pub fn unbox_float(num: f64) -> f32 {
 		let raw_bits = num.to_bits();
+		if raw_bits >> 32 != u32::MAX as u64 {
+			return f32::from_bits(CANONICAL_NAN_S);
+		}
 		let lower_32 : u32 = (raw_bits & (u32::MAX) as u64) as u32;
 		let unboxed_float = f32::from_bits(lower_32);
 		unboxed_float
@@ -74,110 +370,75 @@ impl Cpu {
 		NaNFloat
 	}
 
-	//these need some sort of wrapper so that supervisor cant freely access/change machine level csrs
-	pub fn load_csr(&self, addr: usize) -> u64 {
-		self.csrs[addr]
-	}
-
-	pub fn store_csr(&mut self, addr: usize, val: u64) {
-		self.csrs[addr] = val;
+	//fclass result: a single bit saying what kind of number it is
+	//0-7 go -inf, -normal, -subnormal, -0, +0, +subnormal, +normal, +inf. 8 is a signalling NaN, 9 quiet
+	pub fn fclass(category: FpCategory, negative: bool, quiet: bool) -> u64 {
+		let bit = match (category, negative) {
+			(FpCategory::Infinite, true) => 0,
+			(FpCategory::Normal, true) => 1,
+			(FpCategory::Subnormal, true) => 2,
+			(FpCategory::Zero, true) => 3,
+			(FpCategory::Zero, false) => 4,
+			(FpCategory::Subnormal, false) => 5,
+			(FpCategory::Normal, false) => 6,
+			(FpCategory::Infinite, false) => 7,
+			(FpCategory::Nan, _) => if quiet { 9 } else { 8 },
+		};
+		1 << bit
 	}
 
 
-	pub fn round_float(&self, num:f32) -> f32 {
-		let rounding_mode = (self.fcsr >> 5) & 0x7;
-		match rounding_mode {
-			RNE => { //round to nearest, ties to even
-				let mut temp = num.round();
-				if num.fract() == 0.5 {
-					if num % 2.0 == 1.0 {
-						if num < 0.0 {
-							temp = num.floor();
-						}
-						else {
-							temp = num.ceil();
-						}
-					}
-				}
-				temp
-			},
-			RTZ => {
-				num.trunc();
-				num
-			},
-			RDN => {
-				num.round();
-				num
-			},
-			RUP => {
-				num.ceil();
-				num
-			},
-			RMM => { //round to nearest, ties to larger max
-				let mut temp = num.round();
-				if temp.abs() < num.abs() {
-					if num.fract() == 0.5 {
-						if num < 0.0 {
-							temp = num.floor();
-						}
-						else {
-							temp = num.ceil();
-						}
-					}
-				}
-				temp
-			},
-			//panic here??
-			DYN => {panic!("dyn rounding mode unsupported");},
-			_ => {panic!("invalid rounding mode");},
-		}
-	}
-
-	pub fn decode(fetchVal: u32) -> InstructionFormat {
+	pub fn decode(fetchVal: u32) -> Result<InstructionFormat, Exception> {
 		//consider reformatting this
 		//such that rather than manually creating the structs
 		//implementing a ::New(u32) method for each struct
 		//and calling that here
 		let formatted_instruction = match (fetchVal & 0x7f) {
-			103 | 19 | 3 | 27 | 15  => {
+			103 | 19 | 3 | 27 | 15 | 115 | 7  => {
 				//I format instructions
-				let inst = RegImmInst::New(fetchVal);
+				let inst = RegImmInst::New(fetchVal)?;
 				InstructionFormat::I(inst)
 			},
-			51 | 59 => {
+			51 | 59 | 83 | 47 => {
 				//R format instructions
-				//Includes instructions from: RV64-I, RV64-M (all RV64-M inst are R format)
-				let inst = RegRegInst::New(fetchVal);  
+				//Includes instructions from: RV64-I, RV64-M (all RV64-M inst are R format), RV64-F, RV64-A
+				let inst = RegRegInst::New(fetchVal)?;  
 				InstructionFormat::R(inst)
 			},
-			35  => {
+			35 | 39 => {
 				//S format instructions
-				let inst = StoreInst::New(fetchVal);
+				let inst = StoreInst::New(fetchVal)?;
 				InstructionFormat::S(inst)
 			},
+			67 | 71 | 75 | 79 => {
+				//R4 format instructions, the fused multiply-adds
+				let inst = R4Inst::New(fetchVal)?;
+				InstructionFormat::R4(inst)
+			},
 			99 => {
 				//B format instructions
-				let inst = BranchInst::New(fetchVal);
+				let inst = BranchInst::New(fetchVal)?;
 				InstructionFormat::B(inst)
 			},
 			55 | 23 => {
-				let inst = UpperImmInst::New(fetchVal);
+				let inst = UpperImmInst::New(fetchVal)?;
 				InstructionFormat::U(inst)
 				//LUI / AUIPC instruction
 			},
 			111 => {
 				//JAL instruction
-				let inst = JumpInst::New(fetchVal); 
+				let inst = JumpInst::New(fetchVal)?; 
 				InstructionFormat::J(inst)
 			},
-			_ => { panic!("Instruction format {:b}  not yet supported for code {:b}", (fetchVal & 0x7f), fetchVal); }
+			_ => return Err(Exception::IllegalInstruction(fetchVal as u64)),
 		};
-		formatted_instruction
+		Ok(formatted_instruction)
 	}
 
 	//slightly weird name bc otherwise heaps of vars would be called 'inst'
 	//so instruction toExecute
-	pub fn execute(&mut self, toExecute: InstructionFormat) {
+	//anything that can go wrong comes back as an exception for step to trap on
+	pub fn execute(&mut self, toExecute: InstructionFormat) -> Result<(), Exception> {
 		//find what instruction format it is
 		match toExecute {
 			InstructionFormat::R(inst) => {
@@ -195,10 +456,10 @@ impl Cpu {
 						self.regs[inst.rd as usize] = self.regs[inst.rs1 as usize].wrapping_sub(self.regs[inst.rs2 as usize]) as i32 as i64 as u64;
 					},
 					Instruction::SLL => {
-						self.regs[inst.rd as usize] = self.regs[inst.rs1 as usize] << self.regs[inst.rs2 as usize];
+						self.regs[inst.rd as usize] = self.regs[inst.rs1 as usize] << (self.regs[inst.rs2 as usize] & 0x3f);
 					},
 					Instruction::SLLW => {
-						self.regs[inst.rd as usize] = (self.regs[inst.rs1 as usize] << self.regs[inst.rs2 as usize]) as i32 as i64 as u64;
+						self.regs[inst.rd as usize] = ((self.regs[inst.rs1 as usize] as u32) << (self.regs[inst.rs2 as usize] & 0x1f)) as i32 as i64 as u64;
 					},
 
 					//set destination register to 1 is inst.rs1 as usize < rs 2. SLT is signed comp, SLTU is unsigned comp
@@ -220,16 +481,16 @@ impl Cpu {
 					//when bitshifting, rust does logical shift for unsigned
 					//and does arithmetic shift for signed
 					Instruction::SRL => {
-						self.regs[inst.rd as usize] = self.regs[inst.rs1 as usize] >> self.regs[inst.rs2 as usize];
+						self.regs[inst.rd as usize] = self.regs[inst.rs1 as usize] >> (self.regs[inst.rs2 as usize] & 0x3f);
 					},
 					Instruction::SRLW => {
-						self.regs[inst.rd as usize] = self.regs[inst.rs1 as usize] >> self.regs[inst.rs2 as usize] as i32 as i64 as u64;
+						self.regs[inst.rd as usize] = ((self.regs[inst.rs1 as usize] as u32) >> (self.regs[inst.rs2 as usize] & 0x1f)) as i32 as i64 as u64;
 					},
 					Instruction::SRA => {
-						self.regs[inst.rd as usize] = ((self.regs[inst.rs1 as usize] as i64) >> self.regs[inst.rs2 as usize]) as u64;
+						self.regs[inst.rd as usize] = ((self.regs[inst.rs1 as usize] as i64) >> (self.regs[inst.rs2 as usize] & 0x3f)) as u64;
 					},
 					Instruction::SRAW => {
-						self.regs[inst.rd as usize] = ((self.regs[inst.rs1 as usize] as i32) >> self.regs[inst.rs2 as usize]) as i64 as u64;
+						self.regs[inst.rd as usize] = ((self.regs[inst.rs1 as usize] as i32) >> (self.regs[inst.rs2 as usize] & 0x1f)) as i64 as u64;
 					},
 
 					//RV64-M instructions
@@ -250,19 +511,29 @@ impl Cpu {
 						self.regs[inst.rd as usize] = (temp >> 64) as u64;
 					},
 					Instruction::DIV => {
-						let temp: u128 = ((self.regs[inst.rs1 as usize] as i64) / (self.regs[inst.rs2 as usize] as i64)) as u128;
+						//dividing by zero gives all ones, overflow gives the dividend. Never traps
+						let divisor = self.regs[inst.rs2 as usize] as i64;
+						let temp: u128 = match divisor {
+							0 => u64::MAX as u128,
+							_ => (self.regs[inst.rs1 as usize] as i64).wrapping_div(divisor) as u128,
+						};
 						self.regs[inst.rd as usize] = temp as u64;
 					},
 					Instruction::DIVU => {
-						let temp: u128 = (self.regs[inst.rs1 as usize] / self.regs[inst.rs2 as usize]) as u128;
+						let temp: u128 = self.regs[inst.rs1 as usize].checked_div(self.regs[inst.rs2 as usize]).unwrap_or(u64::MAX) as u128;
 						self.regs[inst.rd as usize] = temp as u64;
 					},
 					Instruction::REM => { //??
-						let temp: u128 = ((self.regs[inst.rs1 as usize] as i64) % (self.regs[inst.rs2 as usize] as i64)) as u128;
+						//remainder of dividing by zero is the dividend
+						let divisor = self.regs[inst.rs2 as usize] as i64;
+						let temp: u128 = match divisor {
+							0 => self.regs[inst.rs1 as usize] as u128,
+							_ => (self.regs[inst.rs1 as usize] as i64).wrapping_rem(divisor) as u128,
+						};
 						self.regs[inst.rd as usize] = temp as u64;
 					},
 					Instruction::REMU => {
-						let temp: u128 = (self.regs[inst.rs1 as usize] % self.regs[inst.rs2 as usize]) as u128;
+						let temp: u128 = self.regs[inst.rs1 as usize].checked_rem(self.regs[inst.rs2 as usize]).unwrap_or(self.regs[inst.rs1 as usize]) as u128;
 						self.regs[inst.rd as usize] = temp as u64;
 					},
 					Instruction::MULW => {
@@ -270,160 +541,86 @@ impl Cpu {
 						self.regs[inst.rd as usize] = temp as i32 as u64;
 					},
 					Instruction::DIVW => {
-						let temp: u128 = ((self.regs[inst.rs1 as usize] as i32) / (self.regs[inst.rs2 as usize] as i32)) as u128;
+						let divisor = self.regs[inst.rs2 as usize] as i32;
+						let temp: u128 = match divisor {
+							0 => u64::MAX as u128,
+							_ => (self.regs[inst.rs1 as usize] as i32).wrapping_div(divisor) as u128,
+						};
 						self.regs[inst.rd as usize] = temp as i32 as i64 as u64;
 					},
 					Instruction::DIVUW => {
-						let temp: u128 = ((self.regs[inst.rs1 as usize] as u32) / (self.regs[inst.rs2 as usize] as u32)) as u128;
+						let temp: u128 = (self.regs[inst.rs1 as usize] as u32).checked_div(self.regs[inst.rs2 as usize] as u32).unwrap_or(u32::MAX) as u128;
 						self.regs[inst.rd as usize] = temp as i32 as i64 as u64;
 					},
 					Instruction::REMW => {
-						let temp: u128 = ((self.regs[inst.rs1 as usize] as i32) % (self.regs[inst.rs2 as usize] as i32)) as u128;
+						let divisor = self.regs[inst.rs2 as usize] as i32;
+						let temp: u128 = match divisor {
+							0 => self.regs[inst.rs1 as usize] as i32 as u128,
+							_ => (self.regs[inst.rs1 as usize] as i32).wrapping_rem(divisor) as u128,
+						};
 						self.regs[inst.rd as usize] = temp as i32 as i64 as u64;
 					},
 					Instruction::REMUW => {
-						let temp: u128 = ((self.regs[inst.rs1 as usize] as u32) % (self.regs[inst.rs2 as usize] as u32)) as u128;
+						let temp: u128 = (self.regs[inst.rs1 as usize] as u32).checked_rem(self.regs[inst.rs2 as usize] as u32).unwrap_or(self.regs[inst.rs1 as usize] as u32) as u128;
 						self.regs[inst.rd as usize] = temp as i32 as i64 as u64;
 					},
 
 					//RV64-F instructions
 					Instruction::FADDS => {
-						let unboxed_1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let unboxed_2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-
-						self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_1 + unboxed_2);
+						self.float_arith(inst, SINGLE, fpu::add)?;
 					},
 					Instruction::FSUBS => {
-						let unboxed_1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let unboxed_2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						
-						self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_1 - unboxed_2);
+						self.float_arith(inst, SINGLE, fpu::sub)?;
 					},
 					Instruction::FMULS => {
-						let unboxed_1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let unboxed_2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						let temp  = unboxed_1 * unboxed_2;
-						self.fregs[inst.rd as usize] = Cpu::box_float(temp);
+						self.float_arith(inst, SINGLE, fpu::mul)?;
 					},
-					Instruction::FDIVS => { //implement
-						let unboxed_1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let unboxed_2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						let temp = unboxed_1 / unboxed_2;
-						self.fregs[inst.rd as usize] = Cpu::box_float(temp);
+					Instruction::FDIVS => {
+						self.float_arith(inst, SINGLE, fpu::div)?;
 					},
 					Instruction::FSQRTS => {
-						let unboxed_1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_1.sqrt());
+						self.float_sqrt(inst, SINGLE)?;
 					},
 					Instruction::FMINS => {
-						let unboxed_1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let unboxed_2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						if unboxed_1.is_nan() {
-							if unboxed_2.is_nan() {
-								self.fregs[inst.rd as usize] = Cpu::box_float(f32::NAN);
-							}
-							else {
-								self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_2);
-							}
-						}
-						else {
-							if unboxed_1 < unboxed_2 {
-								self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_1);
-							}
-							else {
-								self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_2);
-							}
-						}
+						self.float_pick(inst, SINGLE, fpu::min);
 					},
 					Instruction::FMAXS => {
-						let unboxed_1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let unboxed_2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						if unboxed_1.is_nan() {
-							if unboxed_2.is_nan() {
-								self.fregs[inst.rd as usize] = Cpu::box_float(f32::NAN);
-							}
-							else {
-								self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_2);
-							}
-						}
-						else {
-							if unboxed_1 > unboxed_2 {
-								self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_1);
-							}
-							else {
-								self.fregs[inst.rd as usize] = Cpu::box_float(unboxed_2);
-							}
-						}
+						self.float_pick(inst, SINGLE, fpu::max);
 					},
 					Instruction::FEQS  => {
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let f2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						if f1 == f2 {
-							self.regs[inst.rd as usize] = 1;
-						}
-						else {
-							self.regs[inst.rd as usize] = 0;
-						}
+						self.float_compare(inst, SINGLE, fpu::eq);
 					},
 					Instruction::FLTS  => {
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let f2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						if f1 < f2 {
-							self.regs[inst.rd as usize] = 1;
-						}
-						else {
-							self.regs[inst.rd as usize] = 0;
-						}
+						self.float_compare(inst, SINGLE, fpu::lt);
 					},
 					Instruction::FLES  => {
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let f2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						if f1 <= f2 {
-							self.regs[inst.rd as usize] = 1;
-						}
-						else {
-							self.regs[inst.rd as usize] = 0;
-						}
+						self.float_compare(inst, SINGLE, fpu::le);
 					},
+					//out of range values saturate, NaN counts as the biggest positive number
+					//32 bit results get sign extended, even the unsigned ones
 					Instruction::FCVTWS  => {
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let s1 = f1 as i32 as u32;
-						self.regs[inst.rd as usize] = s1 as u64;
+						self.float_to_int(inst, SINGLE, true, 32)?;
 					},
 					Instruction::FCVTWUS  => {
-						let f1 = Cpu::unbox_float( self.fregs[inst.rs1 as usize]);
-						let s1 = f1 as u32;
-						self.regs[inst.rd as usize] = f1 as u64;
-					},
-					Instruction::FCVTSW => { //this probs doesnt work. Need to conv from i32 to  => f32
-						let s1 = self.regs[inst.rs1 as usize] as i32;
-						let f1 = s1 as f32;
-						self.fregs[inst.rd as usize] = Cpu::box_float(f1);
-					},
-					Instruction::FCVTSWU  => {
-						let s1 = self.regs[inst.rs1 as usize] as u32;
-						let f1 = s1 as f32;
-						self.fregs[inst.rd as usize] = Cpu::box_float(f1);
+						self.float_to_int(inst, SINGLE, false, 32)?;
 					},
 					Instruction::FCVTLS  => {
-						let f1 =Cpu::unbox_float( self.fregs[inst.rs1 as usize]);
-						let s1 = f1 as i64 as u64;
-						self.regs[inst.rd as usize] = s1;
+						self.float_to_int(inst, SINGLE, true, 64)?;
 					},
 					Instruction::FCVTLUS  => {
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let s1 = f1 as u64;
-						self.regs[inst.rd as usize] = s1;
+						self.float_to_int(inst, SINGLE, false, 64)?;
+					},
+					Instruction::FCVTSW => {
+						self.int_to_float(inst, SINGLE, true, 32)?;
+					},
+					Instruction::FCVTSWU  => {
+						self.int_to_float(inst, SINGLE, false, 32)?;
 					},
 					Instruction::FCVTSL  => {
-						let s1 = self.regs[inst.rs1 as usize] as i64;
-						let f1 = s1 as f32;
-						self.fregs[inst.rd as usize] = Cpu::box_float(f1);
+						self.int_to_float(inst, SINGLE, true, 64)?;
 					},
 					Instruction::FCVTSLU  => {
-						let s1 = self.regs[inst.rs1 as usize] as u32;
-						let f1 = s1 as f32;
-						self.fregs[inst.rd as usize] = Cpu::box_float(f1);
+						self.int_to_float(inst, SINGLE, false, 64)?;
 					},
 					Instruction::FSGNJS => {
 						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
@@ -450,8 +647,8 @@ impl Cpu {
 						self.fregs[inst.rd as usize] = Cpu::box_float(f32::from_bits(u3));
 					},
 					Instruction::FMVXW => {
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let u1 = f1.to_bits();
+						//raw bits, no unboxing
+						let u1 = self.fregs[inst.rs1 as usize].to_bits() as u32;
 						self.regs[inst.rd as usize] = u1 as i32 as i64 as u64; //gotta sign extend
 					},
 					Instruction::FMVWX => {
@@ -459,139 +656,242 @@ impl Cpu {
 						let f1 = f32::from_bits(u1);
 						self.fregs[inst.rd as usize] = Cpu::box_float(f1);
 					},
-					Instruction::FCLASSS => {},
+					Instruction::FCLASSS => {
+						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
+						let quiet = f1.to_bits() & 0x0040_0000 != 0;
+						self.regs[inst.rd as usize] = Cpu::fclass(f1.classify(), f1.is_sign_negative(), quiet);
+					},
+
+					//RV64-D instructions
+					//doubles take up the whole register so theres no boxing to worry about
+					Instruction::FADDD => {
+						self.float_arith(inst, DOUBLE, fpu::add)?;
+					},
+					Instruction::FSUBD => {
+						self.float_arith(inst, DOUBLE, fpu::sub)?;
+					},
+					Instruction::FMULD => {
+						self.float_arith(inst, DOUBLE, fpu::mul)?;
+					},
+					Instruction::FDIVD => {
+						self.float_arith(inst, DOUBLE, fpu::div)?;
+					},
+					Instruction::FSQRTD => {
+						self.float_sqrt(inst, DOUBLE)?;
+					},
+					Instruction::FMIND => {
+						self.float_pick(inst, DOUBLE, fpu::min);
+					},
+					Instruction::FMAXD => {
+						self.float_pick(inst, DOUBLE, fpu::max);
+					},
+					Instruction::FEQD => {
+						self.float_compare(inst, DOUBLE, fpu::eq);
+					},
+					Instruction::FLTD => {
+						self.float_compare(inst, DOUBLE, fpu::lt);
+					},
+					Instruction::FLED => {
+						self.float_compare(inst, DOUBLE, fpu::le);
+					},
+					Instruction::FSGNJD | Instruction::FSGNJND | Instruction::FSGNJXD => {
+						let u1 = self.fregs[inst.rs1 as usize].to_bits();
+						let u2 = self.fregs[inst.rs2 as usize].to_bits();
+						let sign = match inst.instName {
+							Instruction::FSGNJD => u2,
+							Instruction::FSGNJND => !u2,
+							_ => u1 ^ u2,
+						} & (1 << 63);
+						self.fregs[inst.rd as usize] = f64::from_bits((u1 & !(1 << 63)) | sign);
+					},
+					Instruction::FCVTSD => {
+						self.float_convert(inst, DOUBLE, SINGLE)?;
+					},
+					Instruction::FCVTDS => {
+						self.float_convert(inst, SINGLE, DOUBLE)?;
+					},
+					Instruction::FCVTWD => {
+						self.float_to_int(inst, DOUBLE, true, 32)?;
+					},
+					Instruction::FCVTWUD => {
+						self.float_to_int(inst, DOUBLE, false, 32)?;
+					},
+					Instruction::FCVTLD => {
+						self.float_to_int(inst, DOUBLE, true, 64)?;
+					},
+					Instruction::FCVTLUD => {
+						self.float_to_int(inst, DOUBLE, false, 64)?;
+					},
+					Instruction::FCVTDW => {
+						self.int_to_float(inst, DOUBLE, true, 32)?;
+					},
+					Instruction::FCVTDWU => {
+						self.int_to_float(inst, DOUBLE, false, 32)?;
+					},
+					Instruction::FCVTDL => {
+						self.int_to_float(inst, DOUBLE, true, 64)?;
+					},
+					Instruction::FCVTDLU => {
+						self.int_to_float(inst, DOUBLE, false, 64)?;
+					},
+					Instruction::FMVXD => {
+						self.regs[inst.rd as usize] = self.fregs[inst.rs1 as usize].to_bits();
+					},
+					Instruction::FMVDX => {
+						self.fregs[inst.rd as usize] = f64::from_bits(self.regs[inst.rs1 as usize]);
+					},
+					Instruction::FCLASSD => {
+						let f1 = self.fregs[inst.rs1 as usize];
+						let quiet = f1.to_bits() & (1 << 51) != 0;
+						self.regs[inst.rd as usize] = Cpu::fclass(f1.classify(), f1.is_sign_negative(), quiet);
+					},
 					Instruction::AMOADDW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as i32;
-						self.bus.store(self.regs[inst.rs1 as usize], data.wrapping_add(self.regs[inst.rs2 as usize] as i32) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| (old as i32).wrapping_add(src as i32) as u64)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
 					},
 					Instruction::AMOADDD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as i64;
-						self.bus.store(self.regs[inst.rs1 as usize], data.wrapping_add(self.regs[inst.rs2 as usize] as i64) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| old.wrapping_add(src))?;
+						self.regs[inst.rd as usize] = data;
 					},
 					Instruction::AMOSWAPW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as i32;
-						self.bus.store(self.regs[inst.rs1 as usize], (self.regs[inst.rs2 as usize] as i32) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |_| src)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
 					},
 					Instruction::AMOSWAPD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as i64;
-						self.bus.store(self.regs[inst.rs1 as usize], (self.regs[inst.rs2 as usize] as i64) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |_| src)?;
+						self.regs[inst.rd as usize] = data;
 					},
 					Instruction::AMOORW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as i32;
-						self.bus.store(self.regs[inst.rs1 as usize], (data | (self.regs[inst.rs2 as usize] as i32)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| old | src)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
 					},
 					Instruction::AMOORD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as i64;
-						self.bus.store(self.regs[inst.rs1 as usize], (data | (self.regs[inst.rs2 as usize] as i64)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| old | src)?;
+						self.regs[inst.rd as usize] = data;
 					},
 					Instruction::AMOXORW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as i32;
-						self.bus.store(self.regs[inst.rs1 as usize], (data ^ (self.regs[inst.rs2 as usize] as i32)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| old ^ src)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
+					},
 					Instruction::AMOXORD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as i64;
-						self.bus.store(self.regs[inst.rs1 as usize], (data ^ (self.regs[inst.rs2 as usize] as i64)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| old ^ src)?;
+						self.regs[inst.rd as usize] = data;
+					},
 					Instruction::AMOANDW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as i32;
-						self.bus.store(self.regs[inst.rs1 as usize], (data & (self.regs[inst.rs2 as usize] as i32)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| old & src)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
+					},
 					Instruction::AMOANDD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as i64;
-						self.bus.store(self.regs[inst.rs1 as usize], (data & (self.regs[inst.rs2 as usize] as i64)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| old & src)?;
+						self.regs[inst.rd as usize] = data;
+					},
 					Instruction::AMOMAXW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as i32;
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::max(data,(self.regs[inst.rs2 as usize] as i32)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| cmp::max(old as i32, src as i32) as u64)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
+					},
 					Instruction::AMOMAXD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as i64;
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::max(data, (self.regs[inst.rs2 as usize] as i64)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| cmp::max(old as i64, src as i64) as u64)?;
+						self.regs[inst.rd as usize] = data;
+					},
 					Instruction::AMOMAXUW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as u32;
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::max(data, (self.regs[inst.rs2 as usize] as u32)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| cmp::max(old as u32, src as u32) as u64)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
+					},
 					Instruction::AMOMAXUD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8);
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::max(data, (self.regs[inst.rs2 as usize])) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| cmp::max(old, src))?;
+						self.regs[inst.rd as usize] = data;
+					},
 					Instruction::AMOMINW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as i32;
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::min(data, (self.regs[inst.rs2 as usize] as i32)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| cmp::min(old as i32, src as i32) as u64)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
+					},
 					Instruction::AMOMIND => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as i64;
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::min(data, (self.regs[inst.rs2 as usize] as i64)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| cmp::min(old as i64, src as i64) as u64)?;
+						self.regs[inst.rd as usize] = data;
+					},
 					Instruction::AMOMINUW => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],4) as u32;
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::min(data, (self.regs[inst.rs2 as usize] as u32)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 4, |old| cmp::min(old as u32, src as u32) as u64)?;
+						self.regs[inst.rd as usize] = data as i32 as u64;
 					},
 					Instruction::AMOMINUD => {
-						let data = self.bus.load(self.regs[inst.rs1 as usize],8) as u64;
-						self.bus.store(self.regs[inst.rs1 as usize], cmp::min(data, (self.regs[inst.rs2 as usize] as u64)) as u64, 8);
-						self.regs[inst.rd as usize] = data as u64;
-					}, 
-					Instruction::LRW => {
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1 as usize],4) as i32 as u64;
-					}, 
-					Instruction::LRD => {
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1 as usize],8) as i64 as u64;
-					}, 
-					Instruction::SCW => {
-						self.bus.store(self.regs[inst.rs1 as usize], self.regs[inst.rs2 as usize], 4);
-					}, 
-					Instruction::SCD => {
-						self.bus.store(self.regs[inst.rs1 as usize], self.regs[inst.rs2 as usize], 8);
-					}, 
-					_ => (),
+						let src = self.regs[inst.rs2 as usize];
+						let data = self.amo(self.regs[inst.rs1 as usize], 8, |old| cmp::min(old, src))?;
+						self.regs[inst.rd as usize] = data;
+					},
+					Instruction::LRW | Instruction::LRD => {
+						let size = if inst.instName == Instruction::LRW { 4 } else { 8 };
+						let addr = self.regs[inst.rs1 as usize];
+						if !addr.is_multiple_of(size as u64) {
+							return Err(Exception::LoadAddressMisaligned(addr));
+						}
+						//reservations are on physical addresses, so other harts (and other mappings) see them
+						let paddr = self.translate_load(addr, size)?;
+						let data = self.bus.load_reserved(self.load_csr(MHARTID) as usize, paddr, size)
+							.map_err(|_| Exception::LoadAccessFault(addr))?;
+						self.regs[inst.rd as usize] = if size == 4 { data as i32 as i64 as u64 } else { data };
+					},
+					//only stores if the reservation from the last lr is still there. rd gets 0 if it
+					//did, 1 if it didnt. Either way the reservation is used up
+					Instruction::SCW | Instruction::SCD => {
+						let size = if inst.instName == Instruction::SCW { 4 } else { 8 };
+						let addr = self.regs[inst.rs1 as usize];
+						if !addr.is_multiple_of(size as u64) {
+							return Err(Exception::StoreAddressMisaligned(addr));
+						}
+						let paddr = self.translate_store(addr, size)?;
+						let hart = self.load_csr(MHARTID) as usize;
+						let stored = self.bus.store_conditional(hart, paddr, self.regs[inst.rs2 as usize], size)
+							.map_err(|_| Exception::StoreAccessFault(addr))?;
+						self.regs[inst.rd as usize] = !stored as u64;
+					},
+					_ => return Err(self.illegal()),
 				}
 			},
 			InstructionFormat::R4(inst) => {
 				match inst.instName {
-					Instruction::FMADDS =>{
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let f2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						let f3 = Cpu::unbox_float(self.fregs[inst.rs3 as usize]);
-						self.fregs[inst.rd as usize] = Cpu::box_float(f1.mul_add(f2,f3));
+					//all rounded once. The negated ones flip the product and/or the addend going in
+					Instruction::FMADDS => {
+						self.float_fma(inst, SINGLE, false, false)?;
 					},
-					Instruction::FMSUBS =>{
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let f2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						let f3 = Cpu::unbox_float(self.fregs[inst.rs3 as usize]);
-						self.fregs[inst.rd as usize] = Cpu::box_float(f1.mul_add(f2,-f3));
+					Instruction::FMSUBS => {
+						self.float_fma(inst, SINGLE, false, true)?;
 					},
-					Instruction::FNMADDS =>{ 
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let f2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						let f3 = Cpu::unbox_float(self.fregs[inst.rs3 as usize]);
-						self.fregs[inst.rd as usize] = Cpu::box_float((-(f1 * f2) -f3));
+					Instruction::FNMADDS => {
+						self.float_fma(inst, SINGLE, true, true)?;
 					},
-					Instruction::FNMSUBS =>{
-						let f1 = Cpu::unbox_float(self.fregs[inst.rs1 as usize]);
-						let f2 = Cpu::unbox_float(self.fregs[inst.rs2 as usize]);
-						let f3 = Cpu::unbox_float(self.fregs[inst.rs3 as usize]);
-						self.fregs[inst.rd as usize] = Cpu::box_float((-(f1 * f2) + f3));
+					Instruction::FNMSUBS => {
+						self.float_fma(inst, SINGLE, true, false)?;
+					},
+					Instruction::FMADDD => {
+						self.float_fma(inst, DOUBLE, false, false)?;
+					},
+					Instruction::FMSUBD => {
+						self.float_fma(inst, DOUBLE, false, true)?;
+					},
+					Instruction::FNMADDD => {
+						self.float_fma(inst, DOUBLE, true, true)?;
 					},
-					_ => panic!("Invalid instruction for R4 format"),
+					Instruction::FNMSUBD => {
+						self.float_fma(inst, DOUBLE, true, false)?;
+					},
+					_ => return Err(self.illegal()),
 				}
 			},
 			InstructionFormat::I(inst) => {
@@ -641,133 +941,154 @@ impl Cpu {
 						self.regs[inst.rd as usize] = (self.regs[inst.rs1 as usize] << (inst.imm as u16)) as i32 as i64 as u64;
 					},
 					Instruction::LB => {
-						self.regs[inst.rd as usize]  = self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 1) as i8 as i64 as u64;
+						self.regs[inst.rd as usize]  = self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 1)? as i8 as i64 as u64;
 					},
 					Instruction::LH => {
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 2) as i16 as i64 as u64;
+						self.regs[inst.rd as usize] = self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 2)? as i16 as i64 as u64;
 					},
 					Instruction::LW => {
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 4) as i32 as i64 as u64;
+						self.regs[inst.rd as usize] = self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 4)? as i32 as i64 as u64;
 					},
 					Instruction::LBU => {
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 1);
+						self.regs[inst.rd as usize] = self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 1)?;
 					},
 					Instruction::LHU => {
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 2);
+						self.regs[inst.rd as usize] = self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 2)?;
 					},
 					Instruction::LWU => {
 
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 4);
+						self.regs[inst.rd as usize] = self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 4)?;
 					},
 					Instruction::LD => {
-						self.regs[inst.rd as usize] = self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 8) as i64 as u64;
+						self.regs[inst.rd as usize] = self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 8)? as i64 as u64;
 					},
 					Instruction::JALR => {
 						//gotta clear the last bit, which is the u64::MAX bit
-						self.regs[inst.rd as usize] = self.pc.wrapping_add(4);
-						self.pc = (self.regs[inst.rs1 as usize].wrapping_add(inst.imm as u64).wrapping_sub(4)) & (u64::MAX-1);
-					},
-					Instruction::FENCE => {},
+						//target worked out first in case rd and rs1 are the same register
+						let target = self.regs[inst.rs1 as usize].wrapping_add(inst.imm as u64) & (u64::MAX-1);
+						let ret = self.pc.wrapping_add(self.instLen);
+						self.jump(target)?;
+						self.regs[inst.rd as usize] = ret;
+					},
+					//other harts might be on other threads, so this has to be a real fence on the host
+					Instruction::FENCE => fence(Ordering::SeqCst),
 					Instruction::FLW => {
-						let floatVal = f32::from_bits(self.bus.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 4) as u32);
+						let floatVal = f32::from_bits(self.load(self.regs[inst.rs1  as usize].wrapping_add(inst.imm as u64), 4)? as u32);
 						self.fregs[inst.rd as usize] = Cpu::box_float(floatVal); 
 					},
-					Instruction::CSRRW => {
-						if(inst.rd != 0) {
-							self.regs[inst.rd as usize] = self.load_csr(inst.imm as usize);
-						}
-						self.store_csr(inst.imm as u16 as usize, self.regs[inst.rs1 as usize]);
+					Instruction::FLD => {
+						let bits = self.load(self.regs[inst.rs1 as usize].wrapping_add(inst.imm as u64), 8)?;
+						self.fregs[inst.rd as usize] = f64::from_bits(bits);
 					},
-					Instruction::CSRRS => {
-						self.regs[inst.rd as usize] = self.load_csr(inst.imm as usize);
-						if(inst.rs1 != 0) { //a bit mask. Any high bits in the rs1 register set the corresponding bit in csr as high
-							self.store_csr(inst.imm as u16 as usize, self.regs[inst.rd as usize] | self.regs[inst.rs1 as usize]);
-						}
+					Instruction::CSRRW | Instruction::CSRRS | Instruction::CSRRC
+					| Instruction::CSRRWI | Instruction::CSRRSI | Instruction::CSRRCI => {
+						self.execute_csr(inst)?;
 					},
-					Instruction::CSRRC => {
-						self.regs[inst.rd as usize] = self.load_csr(inst.imm as usize);
-						if(inst.rs1 != 0) { //a bit mask. Any high bits in the rs1 register set the corresponding bit in csr as low
-							self.store_csr(inst.imm as u16 as usize, self.regs[inst.rd as usize] & !(self.regs[inst.rs1 as usize]));
+					Instruction::ECALL => {
+						//the host picks it up after the step, with pc already past it
+						if self.hostCalls {
+							self.ecalled = true;
+							return Ok(());
 						}
-					},
-					Instruction::CSRRWI => {
-						if(inst.rd != 0) {
-							self.regs[inst.rd as usize] = self.load_csr(inst.imm as usize);
+						return Err(match self.prv {
+							privilege_level::U => Exception::EnvironmentCallFromU,
+							privilege_level::S => Exception::EnvironmentCallFromS,
+							privilege_level::M => Exception::EnvironmentCallFromM,
+						});
+					},
+					Instruction::EBREAK => {
+						//pc moves on to the srai, which does nothing
+						if self.semihosting && self.semihost_sequence() {
+							self.semihostCalled = true;
+							return Ok(());
 						}
-						self.store_csr(inst.imm as u16 as usize, inst.rs1 as u64);
+						return Err(Exception::Breakpoint(self.pc));
 					},
-					Instruction::CSRRSI => {
-						self.regs[inst.rd as usize] = self.load_csr(inst.imm as usize);
-						if(inst.rs1 != 0) { //a bit mask. Any high bits in the rs1 register set the corresponding bit in csr as high
-							self.store_csr(inst.imm as u16 as usize, self.regs[inst.rd as usize] | (inst.rs1 as u64));
+					Instruction::MRET => {
+						self.mret()?;
+					},
+					Instruction::SRET => {
+						self.sret()?;
+					},
+					Instruction::WFI => {
+						//tw makes wfi illegal anywhere below machine mode
+						let tw = (self.load_csr(MSTATUS) & MSTATUS_TW) != 0;
+						if self.prv == privilege_level::U || (self.prv == privilege_level::S && tw) {
+							return Err(Exception::IllegalInstruction(0x10500073));
 						}
+						self.wfi = true;
 					},
-					Instruction::CSRRCI => {
-						self.regs[inst.rd as usize] = self.load_csr(inst.imm as usize);
-						if(inst.rs1 != 0) { //a bit mask. Any high bits in the rs1 register set the corresponding bit in csr as high
-							self.store_csr(inst.imm as u16 as usize, self.regs[inst.rd as usize] & !(inst.rs1 as u64));
+					Instruction::SFENCEVMA => {
+						let rs2 = (inst.imm & 0x1f) as u8;
+						let tvm = (self.load_csr(MSTATUS) & MSTATUS_TVM) != 0;
+						if self.prv == privilege_level::U || (self.prv == privilege_level::S && tvm) {
+							return Err(Exception::IllegalInstruction(0x12000073 | ((rs2 as u64) << 20) | ((inst.rs1 as u64) << 15)));
 						}
+						//x0 for either register means all addresses/all address spaces
+						let vaddr = if inst.rs1 != 0 { Some(self.regs[inst.rs1 as usize]) } else { None };
+						let asid = if rs2 != 0 { Some(self.regs[rs2 as usize] as u16) } else { None };
+						self.tlb.flush(vaddr, asid);
 					},
 					
-					_ => (),
+					_ => return Err(self.illegal()),
 				}
 			},
 			InstructionFormat::B(inst) => {
 				match inst.instName {
 					Instruction::BEQ => {
 						if self.regs[inst.rs1 as usize] == self.regs[inst.rs2 as usize] {
-							self.pc = self.pc.wrapping_add((inst.imm << 1) as u64).wrapping_sub(4); //the syub is because we ahve an unconditional ass
-																							  //to pc each loop
+							self.jump(self.pc.wrapping_add((inst.imm << 1) as u64))?;
 						}
 					},
 					Instruction::BNE => {
 						if self.regs[inst.rs1 as usize] != self.regs[inst.rs2 as usize] {
-							self.pc = self.pc.wrapping_add((inst.imm << 1) as u64).wrapping_sub(4); //the syub is because we ahve an unconditional ass
+							self.jump(self.pc.wrapping_add((inst.imm << 1) as u64))?;
 						}
 					},
 					Instruction::BLT => {
 						if (self.regs[inst.rs1 as usize] as i64) < (self.regs[inst.rs2 as usize] as i64) {
-							self.pc = self.pc.wrapping_add((inst.imm << 1) as u64).wrapping_sub(4); //the syub is because we ahve an unconditional ass
+							self.jump(self.pc.wrapping_add((inst.imm << 1) as u64))?;
 						}
 					},
 					Instruction::BGE => {
 						if (self.regs[inst.rs1 as usize] as i64) >= ( self.regs[inst.rs2 as usize] as i64) {
-							self.pc = self.pc.wrapping_add((inst.imm << 1) as u64).wrapping_sub(4); //the syub is because we ahve an unconditional ass
+							self.jump(self.pc.wrapping_add((inst.imm << 1) as u64))?;
 						}
 					},
 					Instruction::BLTU => {
 						if self.regs[inst.rs1 as usize] <  self.regs[inst.rs2 as usize] {
-							self.pc = self.pc.wrapping_add((inst.imm << 1) as u64).wrapping_sub(4); //the syub is because we ahve an unconditional ass
+							self.jump(self.pc.wrapping_add((inst.imm << 1) as u64))?;
 						}
 					},
 					Instruction::BGEU => {
 						if self.regs[inst.rs1 as usize] >=  self.regs[inst.rs2 as usize] {
-							self.pc = self.pc.wrapping_add((inst.imm << 1) as u64).wrapping_sub(4); //the syub is because we ahve an unconditional ass
+							self.jump(self.pc.wrapping_add((inst.imm << 1) as u64))?;
 						}
 					},
-					_ => (),
+					_ => return Err(self.illegal()),
 				}
 			},
 			InstructionFormat::S(inst) => {
 				match inst.instName {
 					Instruction::SB => {
-						self.bus.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 1);
+						self.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 1)?;
 					},
 					Instruction::SH => {
-						self.bus.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 2);
+						self.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 2)?;
 					},
 					Instruction::SW => {
-						self.bus.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 4);
+						self.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 4)?;
 					},
 					Instruction::SD => {
-						self.bus.store(self.regs[inst.rs1 as usize].wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 8);
+						self.store(self.regs[inst.rs1 as usize].wrapping_add(inst.imm as u64), self.regs[inst.rs2 as usize], 8)?;
 					},
 					Instruction::FSW => {
-						self.bus.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.fregs[inst.rs2 as usize].to_bits() as u64, 4);
+						self.store((self.regs[inst.rs1 as usize]).wrapping_add(inst.imm as u64), self.fregs[inst.rs2 as usize].to_bits() as u64, 4)?;
+					},
+					Instruction::FSD => {
+						self.store(self.regs[inst.rs1 as usize].wrapping_add(inst.imm as u64), self.fregs[inst.rs2 as usize].to_bits(), 8)?;
 					},
-					_ => {
-						panic!("agony");
-					}
+					_ => return Err(self.illegal()),
 				}
 			},
 			InstructionFormat::U(inst) => {
@@ -780,25 +1101,36 @@ impl Cpu {
 						self.regs[inst.rd as usize] = (inst.imm << 12) as i64 as u64;
 	
 					},
-					_ => (),
+					_ => return Err(self.illegal()),
 				}
 			},
 			InstructionFormat::J(inst) => {
 				match inst.instName {
 					Instruction::JAL => {
-						self.regs[inst.rd as usize] = self.pc.wrapping_add(4);
-						println!("pc before {}", self.pc);
-						self.pc = self.pc.wrapping_add((inst.imm << 1) as u64).wrapping_sub(4) as i64 as u64;
-						println!("pc after {}", self.pc + 4);
+						let ret = self.pc.wrapping_add(self.instLen);
+						self.jump(self.pc.wrapping_add((inst.imm << 1) as u64))?;
+						self.regs[inst.rd as usize] = ret;
 					},
-					_ => (),
+					_ => return Err(self.illegal()),
 				}
 			},
-			_ => panic!("Yet to implement that instruction format"),
 		}
+		Ok(())
 	}
 }
 
 
+mod csr;
+mod mmu;
+mod tlb;
+mod pmp;
+mod rvc;
+mod fpu;
+
+pub use mmu::{AccessType, SATP_MODE_SHIFT, SATP_MODE_SV39, PAGE_SHIFT, PAGE_SIZE};
+pub use mmu::{PTE_V, PTE_R, PTE_W, PTE_X, PTE_U, PTE_A, PTE_D};
+pub use tlb::Tlb;
+pub use pmp::Pmp;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,207 @@
+//the csr file
+//backing storage is still the flat csrs array, but everything goes through here so that
+//the views (sstatus, sie, sip, fflags/frm) and the WARL fields behave like real hardware
+//
+//load_csr/store_csr are what the emulator itself uses (traps, xret, devices etc.) - no
+//privilege checks, but writes still get legalised
+//csr instructions go through execute_csr, which does the access checks as well
+
+use super::*;
+
+//misa bit for an extension letter
+const fn ext(letter: char) -> u64 {
+	1 << (letter as u64 - 'A' as u64)
+}
+
+//fixed isa string: rv64 (mxl = 2) + IMAFDCSU
+pub const MISA_VALUE: u64 = (2 << 62)
+	| ext('A') | ext('C') | ext('D') | ext('F') | ext('I') | ext('M') | ext('S') | ext('U');
+
+//bits of mstatus that can actually be written. Everything else is hardwired
+const MSTATUS_WRITE_MASK: u64 = MSTATUS_SIE | MSTATUS_MIE | MSTATUS_SPIE | MSTATUS_MPIE
+	| MSTATUS_SPP | MSTATUS_MPP | MSTATUS_FS | MSTATUS_MPRV | MSTATUS_SUM | MSTATUS_MXR
+	| MSTATUS_TVM | MSTATUS_TW | MSTATUS_TSR;
+//the part of mstatus supervisor mode sees through sstatus
+const SSTATUS_MASK: u64 = MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP | MSTATUS_FS
+	| MSTATUS_SUM | MSTATUS_MXR | MSTATUS_UXL | MSTATUS_SD;
+
+//every interrupt we have
+const INTERRUPT_MASK: u64 = MIP_SSIP | MIP_MSIP | MIP_STIP | MIP_MTIP | MIP_SEIP | MIP_MEIP;
+//only supervisor interrupts can be handed down
+const MIDELEG_MASK: u64 = MIP_SSIP | MIP_STIP | MIP_SEIP;
+//the only bits of mip software gets to write, the rest are driven by devices
+const MIP_WRITE_MASK: u64 = MIP_SSIP | MIP_STIP | MIP_SEIP;
+//exceptions that can be delegated. ecall from M (11) cant be, and 10/14 are reserved
+const MEDELEG_MASK: u64 = 0xb3ff;
+
+impl Cpu {
+	//reads a csr the way hardware sees it
+	pub fn load_csr(&self, addr: usize) -> u64 {
+		match addr {
+			FFLAGS => (self.fcsr & 0x1f) as u64,
+			FRM => ((self.fcsr >> 5) & 0x7) as u64,
+			FCSR => (self.fcsr & 0xff) as u64,
+			CYCLE => self.csrs[MCYCLE],
+			TIME => self.bus.clint.mtime,
+			INSTRET => self.csrs[MINSTRET],
+			SSTATUS => self.csrs[MSTATUS] & SSTATUS_MASK,
+			SIE => self.csrs[MIE] & self.csrs[MIDELEG],
+			SIP => self.csrs[MIP] & self.csrs[MIDELEG],
+			MISA => MISA_VALUE,
+			_ => self.csrs[addr],
+		}
+	}
+
+	//writes a csr, throwing away/fixing up anything that isnt a legal value
+	pub fn store_csr(&mut self, addr: usize, val: u64) {
+		match addr {
+			FFLAGS => self.fcsr = (self.fcsr & !0x1f) | (val as u32 & 0x1f),
+			FRM => self.fcsr = (self.fcsr & !0xe0) | ((val as u32 & 0x7) << 5),
+			FCSR => self.fcsr = val as u32 & 0xff,
+			MSTATUS => {
+				let mut status = (self.csrs[MSTATUS] & !MSTATUS_WRITE_MASK) | (val & MSTATUS_WRITE_MASK);
+				//mpp of 2 is reserved, keep the old one
+				if (status & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT == 2 {
+					status = (status & !MSTATUS_MPP) | (self.csrs[MSTATUS] & MSTATUS_MPP);
+				}
+				//user and supervisor are always 64 bit
+				status = (status & !(MSTATUS_UXL | MSTATUS_SXL)) | (2 << 32) | (2 << 34);
+				status &= !MSTATUS_SD;
+				if status & MSTATUS_FS == MSTATUS_FS {
+					status |= MSTATUS_SD;
+				}
+				self.csrs[MSTATUS] = status;
+			},
+			SSTATUS => {
+				let status = (self.csrs[MSTATUS] & !SSTATUS_MASK) | (val & SSTATUS_MASK);
+				self.store_csr(MSTATUS, status);
+			},
+			MISA => {}, //cant turn extensions on or off
+			MEDELEG => self.csrs[MEDELEG] = val & MEDELEG_MASK,
+			MIDELEG => self.csrs[MIDELEG] = val & MIDELEG_MASK,
+			MIE => self.csrs[MIE] = val & INTERRUPT_MASK,
+			MIP => self.csrs[MIP] = val & INTERRUPT_MASK,
+			//supervisor can only touch the bits that have been delegated to it
+			SIE => {
+				let mask = self.csrs[MIDELEG];
+				self.csrs[MIE] = (self.csrs[MIE] & !mask) | (val & mask);
+			},
+			SIP => {
+				let mask = self.csrs[MIDELEG] & MIP_SSIP;
+				self.csrs[MIP] = (self.csrs[MIP] & !mask) | (val & mask);
+			},
+			//modes 2 and 3 are reserved, fall back to direct
+			MTVEC | STVEC => {
+				self.csrs[addr] = if val & 0x3 >= 2 { val & !0x3 } else { val };
+			},
+			//only bit 0 is fixed, compressed instructions can be 2 byte aligned
+			MEPC | SEPC => self.csrs[addr] = val & !0x1,
+			MCOUNTEREN | SCOUNTEREN => self.csrs[addr] = val & 0x7,
+			//writing an unsupported mode leaves satp alone
+			//anything cached for the old page tables is thrown away
+			SATP => {
+				if val >> mmu::SATP_MODE_SHIFT == mmu::SATP_MODE_BARE || mmu::satp_levels(val).is_some() {
+					self.csrs[SATP] = val;
+					self.tlb.flush(None, None);
+				}
+			},
+			PMPCFG0..=PMPCFG15 => self.store_pmpcfg(addr, val),
+			PMPADDR0..=PMPADDR63 => self.store_pmpaddr(addr, val),
+			MVENDORID | MARCHID | MIMPID | MHARTID => {},
+			_ => self.csrs[addr] = val,
+		}
+	}
+
+	//is there actually a csr here
+	fn csr_exists(addr: usize) -> bool {
+		//odd pmpcfgs are rv32 only
+		if let PMPCFG0..=PMPCFG15 = addr {
+			return addr.is_multiple_of(2);
+		}
+		matches!(addr,
+			FFLAGS | FRM | FCSR | CYCLE | TIME | INSTRET
+			| SSTATUS | SIE | STVEC | SCOUNTEREN | SSCRATCH | SEPC | SCAUSE | STVAL | SIP | SATP
+			| MVENDORID | MARCHID | MIMPID | MHARTID
+			| MSTATUS | MISA | MEDELEG | MIDELEG | MIE | MTVEC | MCOUNTEREN
+			| MSCRATCH | MEPC | MCAUSE | MTVAL | MIP | MCYCLE | MINSTRET
+			| PMPADDR0..=PMPADDR63)
+	}
+
+	//checks whether the current privilege level can get at a csr
+	//bits 9:8 of the address are the lowest privilege allowed, bits 11:10 == 3 means read only
+	fn csr_accessible(&self, addr: usize, write: bool) -> bool {
+		if !Cpu::csr_exists(addr) {
+			return false;
+		}
+		if (self.prv as usize) < ((addr >> 8) & 0x3) {
+			return false;
+		}
+		if write && (addr >> 10) & 0x3 == 0x3 {
+			return false;
+		}
+		//the float csrs go away with the rest of the float unit
+		if let FFLAGS | FRM | FCSR = addr {
+			if self.load_csr(MSTATUS) & MSTATUS_FS == 0 {
+				return false;
+			}
+		}
+		//tvm traps supervisor satp accesses
+		if addr == SATP && self.prv == privilege_level::S && (self.load_csr(MSTATUS) & MSTATUS_TVM) != 0 {
+			return false;
+		}
+		//the user counters need to be enabled by each level above
+		if let CYCLE | TIME | INSTRET = addr {
+			let bit = 1 << (addr - CYCLE);
+			if self.prv != privilege_level::M && self.load_csr(MCOUNTEREN) & bit == 0 {
+				return false;
+			}
+			if self.prv == privilege_level::U && self.load_csr(SCOUNTEREN) & bit == 0 {
+				return false;
+			}
+		}
+		true
+	}
+
+	//all six csr instructions
+	//rs1 of x0 (or a uimm of 0) for the set/clear versions means dont write at all,
+	//and csrrw(i) with rd of x0 doesnt read - this matters for side effects and read only csrs
+	pub fn execute_csr(&mut self, inst: RegImmInst) -> Result<(), Exception> {
+		let addr = (inst.imm as usize) & 0xfff;
+		let (funct3, src) = match inst.instName {
+			Instruction::CSRRW => (1, self.regs[inst.rs1 as usize]),
+			Instruction::CSRRS => (2, self.regs[inst.rs1 as usize]),
+			Instruction::CSRRC => (3, self.regs[inst.rs1 as usize]),
+			Instruction::CSRRWI => (5, inst.rs1 as u64),
+			Instruction::CSRRSI => (6, inst.rs1 as u64),
+			Instruction::CSRRCI => (7, inst.rs1 as u64),
+			_ => return Err(self.illegal()),
+		};
+		let write = funct3 & 0x3 == 1 || inst.rs1 != 0;
+		let read = funct3 & 0x3 != 1 || inst.rd != 0;
+		if !self.csr_accessible(addr, write) {
+			//rebuild the instruction bits for mtval
+			let bits = ((addr as u64) << 20) | ((inst.rs1 as u64) << 15) | (funct3 << 12) | ((inst.rd as u64) << 7) | 0x73;
+			return Err(Exception::IllegalInstruction(bits));
+		}
+		let old = if read { self.load_csr(addr) } else { 0 };
+		if write {
+			let mut new = match funct3 & 0x3 {
+				1 => src,
+				2 => old | src,
+				_ => old & !src,
+			};
+			//devices drive the rest of mip
+			if addr == MIP {
+				new = (self.load_csr(MIP) & !MIP_WRITE_MASK) | (new & MIP_WRITE_MASK);
+			}
+			self.store_csr(addr, new);
+			//float state got touched
+			if let FFLAGS | FRM | FCSR = addr {
+				let status = self.load_csr(MSTATUS);
+				self.store_csr(MSTATUS, status | MSTATUS_FS);
+			}
+		}
+		self.regs[inst.rd as usize] = old;
+		Ok(())
+	}
+}
@@ -0,0 +1,606 @@
+//software floating point
+//the host's floats always round to nearest and never say when something went wrong, so
+//anything that rounds is done here with integers instead. That way every rounding mode
+//and the accrued exception flags come out bit for bit the way the spec says
+//
+//values are passed around as raw bits (singles in the bottom 32). A finite value gets
+//unpacked to sig * 2^exp, worked on exactly (or with whatever falls off the bottom squashed
+//into a sticky bit), and then rounded and packed back up in one place, round_pack
+//
+//tininess is detected after rounding, which is what riscv picked
+
+use super::*;
+
+//exception flags, laid out like fflags
+pub const NX: u32 = 1 << 0; //inexact
+pub const UF: u32 = 1 << 1; //underflow
+pub const OF: u32 = 1 << 2; //overflow
+pub const DZ: u32 = 1 << 3; //divide by zero
+pub const NV: u32 = 1 << 4; //invalid operation
+
+//rounding mode for float instructions
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum RoundingMode {
+	Rne = 0, //to nearest, ties to even
+	Rtz = 1, //towards zero
+	Rdn = 2, //down
+	Rup = 3, //up
+	Rmm = 4, //to nearest, ties away from zero
+}
+
+impl RoundingMode {
+	//5 and 6 are reserved. 7 (dynamic) means go look at frm, which is up to the caller
+	pub fn from_bits(bits: u32) -> Option<RoundingMode> {
+		match bits {
+			0 => Some(RoundingMode::Rne),
+			1 => Some(RoundingMode::Rtz),
+			2 => Some(RoundingMode::Rdn),
+			3 => Some(RoundingMode::Rup),
+			4 => Some(RoundingMode::Rmm),
+			_ => None,
+		}
+	}
+}
+
+//shape of an ieee binary format
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct Format {
+	expBits: u32,
+	fracBits: u32,
+}
+
+pub const SINGLE: Format = Format { expBits: 8, fracBits: 23 };
+pub const DOUBLE: Format = Format { expBits: 11, fracBits: 52 };
+
+impl Format {
+	fn sign_bit(self) -> u64 {
+		1 << (self.expBits + self.fracBits)
+	}
+
+	fn exp_max(self) -> u64 {
+		(1 << self.expBits) - 1
+	}
+
+	fn frac_mask(self) -> u64 {
+		(1 << self.fracBits) - 1
+	}
+
+	//exponent of the lowest bit of the smallest subnormal
+	fn min_exp(self) -> i32 {
+		2 - (1 << (self.expBits - 1)) - self.fracBits as i32
+	}
+
+	//what any op that makes a NaN gives back
+	pub fn canonical_nan(self) -> u64 {
+		(self.exp_max() << self.fracBits) | (1 << (self.fracBits - 1))
+	}
+
+	fn infinity(self, sign: bool) -> u64 {
+		self.zero(sign) | (self.exp_max() << self.fracBits)
+	}
+
+	fn zero(self, sign: bool) -> u64 {
+		if sign { self.sign_bit() } else { 0 }
+	}
+
+	fn largest(self, sign: bool) -> u64 {
+		self.zero(sign) | ((self.exp_max() - 1) << self.fracBits) | self.frac_mask()
+	}
+
+	fn sign(self, a: u64) -> bool {
+		a & self.sign_bit() != 0
+	}
+
+	pub fn is_nan(self, a: u64) -> bool {
+		a & !self.sign_bit() > self.exp_max() << self.fracBits
+	}
+
+	//signalling NaNs have the top fraction bit clear
+	pub fn is_snan(self, a: u64) -> bool {
+		self.is_nan(a) && a & (1 << (self.fracBits - 1)) == 0
+	}
+
+	fn is_inf(self, a: u64) -> bool {
+		a & !self.sign_bit() == self.exp_max() << self.fracBits
+	}
+
+	fn is_zero(self, a: u64) -> bool {
+		a & !self.sign_bit() == 0
+	}
+
+	//a finite nonzero value as sig * 2^exp, with the top bit of sig at fracBits
+	//subnormals get normalised too so everything has the same number of bits to work with
+	fn unpack(self, a: u64) -> (i32, u128) {
+		let biased = (a >> self.fracBits) & self.exp_max();
+		let frac = a & self.frac_mask();
+		if biased == 0 {
+			let shift = self.fracBits - (63 - frac.leading_zeros());
+			(self.min_exp() - shift as i32, (frac << shift) as u128)
+		}
+		else {
+			(biased as i32 + self.min_exp() - 1, (frac | (1 << self.fracBits)) as u128)
+		}
+	}
+
+	//for comparing. Converting a single up to a double is exact, so the host can do those
+	fn to_f64(self, a: u64) -> f64 {
+		if self == SINGLE { f32::from_bits(a as u32) as f64 } else { f64::from_bits(a) }
+	}
+}
+
+//shifts right, keeping whether anything nonzero fell off as the bottom bit
+fn shift_right_jam(sig: u128, shift: u32) -> u128 {
+	if shift == 0 {
+		sig
+	}
+	else if shift >= 128 {
+		(sig != 0) as u128
+	}
+	else {
+		(sig >> shift) | ((sig & ((1 << shift) - 1)) != 0) as u128
+	}
+}
+
+fn msb(sig: u128) -> i32 {
+	127 - sig.leading_zeros() as i32
+}
+
+//rounds sig * 2^exp to a multiple of 2^lsb. Gives back how many of them, and whether that was inexact
+//if sig has had something jammed into its bottom bit there have to be at least two bits below lsb
+fn round_at(sig: u128, exp: i32, lsb: i32, sign: bool, rm: RoundingMode) -> (u128, bool) {
+	let shift = lsb - exp;
+	if shift <= 0 {
+		return (sig << -shift, false);
+	}
+	//two bits below the result: one worth a half, and one for anything less than that
+	let r = if shift >= 2 { shift_right_jam(sig, shift as u32 - 2) } else { sig << 1 };
+	let (q, low) = (r >> 2, r & 0x3);
+	let up = match rm {
+		RoundingMode::Rne => low > 2 || (low == 2 && q & 1 == 1),
+		RoundingMode::Rmm => low >= 2,
+		RoundingMode::Rtz => false,
+		RoundingMode::Rdn => low != 0 && sign,
+		RoundingMode::Rup => low != 0 && !sign,
+	};
+	(q + up as u128, low != 0)
+}
+
+//rounds +/- sig * 2^exp into the format. sig cant be zero
+pub fn round_pack(fmt: Format, sign: bool, exp: i32, sig: u128, rm: RoundingMode) -> (u64, u32) {
+	let f = fmt.fracBits as i32;
+	let minExp = fmt.min_exp();
+	//where the bottom bit would be with a full width significand
+	let lsb = exp + msb(sig) - f;
+	//tiny if it would still be below the smallest normal after rounding to full width
+	let tiny = lsb < minExp && (lsb < minExp - 1 || round_at(sig, exp, lsb, sign, rm).0 >> (f + 1) == 0);
+	let mut lsb = cmp::max(lsb, minExp);
+	let (mut q, inexact) = round_at(sig, exp, lsb, sign, rm);
+	//rounding up carried into a new bit. Whats shifted out is a 0
+	if q >> (f + 1) != 0 {
+		q >>= 1;
+		lsb += 1;
+	}
+	let biased = if q >> f != 0 { (lsb - minExp + 1) as u64 } else { 0 };
+	if biased >= fmt.exp_max() {
+		let toInf = match rm {
+			RoundingMode::Rne | RoundingMode::Rmm => true,
+			RoundingMode::Rtz => false,
+			RoundingMode::Rdn => sign,
+			RoundingMode::Rup => !sign,
+		};
+		let res = if toInf { fmt.infinity(sign) } else { fmt.largest(sign) };
+		return (res, OF | NX);
+	}
+	let mut flags = 0;
+	if inexact {
+		flags |= NX;
+		if tiny {
+			flags |= UF;
+		}
+	}
+	(fmt.zero(sign) | (biased << f) | (q as u64 & fmt.frac_mask()), flags)
+}
+
+//NaN in, canonical NaN out. Only signalling ones are invalid
+fn propagate(fmt: Format, a: u64, b: u64) -> (u64, u32) {
+	let flags = if fmt.is_snan(a) || fmt.is_snan(b) { NV } else { 0 };
+	(fmt.canonical_nan(), flags)
+}
+
+//x + -x is +0, unless rounding down
+fn exact_zero(fmt: Format, signA: bool, signB: bool, rm: RoundingMode) -> u64 {
+	fmt.zero(if signA == signB { signA } else { rm == RoundingMode::Rdn })
+}
+
+//adds two finite nonzero values, each given as (sign, exp, sig), then rounds once
+fn add_finite(fmt: Format, (signA, expA, sigA): (bool, i32, u128), (signB, expB, sigB): (bool, i32, u128), rm: RoundingMode) -> (u64, u32) {
+	//both get moved up to bit 125, leaving room to carry, and plenty of bits under the
+	//result for whatever gets jammed when one is shifted down to line up with the other
+	let (expA, sigA) = (expA - (125 - msb(sigA)), sigA << (125 - msb(sigA)));
+	let (expB, sigB) = (expB - (125 - msb(sigB)), sigB << (125 - msb(sigB)));
+	let (exp, sigA, sigB) = if expA >= expB {
+		(expA, sigA, shift_right_jam(sigB, (expA - expB) as u32))
+	}
+	else {
+		(expB, shift_right_jam(sigA, (expB - expA) as u32), sigB)
+	};
+	if signA == signB {
+		return round_pack(fmt, signA, exp, sigA + sigB, rm);
+	}
+	if sigA == sigB {
+		return (exact_zero(fmt, signA, signB, rm), 0);
+	}
+	if sigA > sigB {
+		round_pack(fmt, signA, exp, sigA - sigB, rm)
+	}
+	else {
+		round_pack(fmt, signB, exp, sigB - sigA, rm)
+	}
+}
+
+pub fn add(fmt: Format, a: u64, b: u64, rm: RoundingMode) -> (u64, u32) {
+	if fmt.is_nan(a) || fmt.is_nan(b) {
+		return propagate(fmt, a, b);
+	}
+	let (signA, signB) = (fmt.sign(a), fmt.sign(b));
+	if fmt.is_inf(a) || fmt.is_inf(b) {
+		if fmt.is_inf(a) && fmt.is_inf(b) && signA != signB {
+			return (fmt.canonical_nan(), NV);
+		}
+		return (if fmt.is_inf(a) { a } else { b }, 0);
+	}
+	if fmt.is_zero(a) && fmt.is_zero(b) {
+		return (exact_zero(fmt, signA, signB, rm), 0);
+	}
+	if fmt.is_zero(b) {
+		return (a, 0);
+	}
+	if fmt.is_zero(a) {
+		return (b, 0);
+	}
+	let (expA, sigA) = fmt.unpack(a);
+	let (expB, sigB) = fmt.unpack(b);
+	add_finite(fmt, (signA, expA, sigA), (signB, expB, sigB), rm)
+}
+
+pub fn sub(fmt: Format, a: u64, b: u64, rm: RoundingMode) -> (u64, u32) {
+	add(fmt, a, b ^ fmt.sign_bit(), rm)
+}
+
+pub fn mul(fmt: Format, a: u64, b: u64, rm: RoundingMode) -> (u64, u32) {
+	if fmt.is_nan(a) || fmt.is_nan(b) {
+		return propagate(fmt, a, b);
+	}
+	let sign = fmt.sign(a) != fmt.sign(b);
+	if fmt.is_inf(a) || fmt.is_inf(b) {
+		if fmt.is_zero(a) || fmt.is_zero(b) {
+			return (fmt.canonical_nan(), NV);
+		}
+		return (fmt.infinity(sign), 0);
+	}
+	if fmt.is_zero(a) || fmt.is_zero(b) {
+		return (fmt.zero(sign), 0);
+	}
+	let (expA, sigA) = fmt.unpack(a);
+	let (expB, sigB) = fmt.unpack(b);
+	round_pack(fmt, sign, expA + expB, sigA * sigB, rm)
+}
+
+pub fn div(fmt: Format, a: u64, b: u64, rm: RoundingMode) -> (u64, u32) {
+	if fmt.is_nan(a) || fmt.is_nan(b) {
+		return propagate(fmt, a, b);
+	}
+	let sign = fmt.sign(a) != fmt.sign(b);
+	if fmt.is_inf(a) {
+		if fmt.is_inf(b) {
+			return (fmt.canonical_nan(), NV);
+		}
+		return (fmt.infinity(sign), 0);
+	}
+	if fmt.is_inf(b) {
+		return (fmt.zero(sign), 0);
+	}
+	if fmt.is_zero(b) {
+		if fmt.is_zero(a) {
+			return (fmt.canonical_nan(), NV);
+		}
+		return (fmt.infinity(sign), DZ);
+	}
+	if fmt.is_zero(a) {
+		return (fmt.zero(sign), 0);
+	}
+	let (expA, sigA) = fmt.unpack(a);
+	let (expB, sigB) = fmt.unpack(b);
+	//64 extra bits gives a quotient with plenty to round with. The remainder goes in the sticky bit
+	let num = sigA << 64;
+	let q = (num / sigB) | (num % sigB != 0) as u128;
+	round_pack(fmt, sign, expA - expB - 64, q, rm)
+}
+
+//integer square root, and whether there was anything left over
+fn isqrt(n: u128) -> (u128, bool) {
+	let mut rem = n;
+	let mut root = 0u128;
+	let mut bit = 1u128 << 126;
+	while bit > n {
+		bit >>= 2;
+	}
+	while bit != 0 {
+		if rem >= root + bit {
+			rem -= root + bit;
+			root = (root >> 1) + bit;
+		}
+		else {
+			root >>= 1;
+		}
+		bit >>= 2;
+	}
+	(root, rem != 0)
+}
+
+pub fn sqrt(fmt: Format, a: u64, rm: RoundingMode) -> (u64, u32) {
+	if fmt.is_nan(a) {
+		return propagate(fmt, a, a);
+	}
+	//sqrt(-0) is -0
+	if fmt.is_zero(a) {
+		return (a, 0);
+	}
+	if fmt.sign(a) {
+		return (fmt.canonical_nan(), NV);
+	}
+	if fmt.is_inf(a) {
+		return (a, 0);
+	}
+	let (exp, sig) = fmt.unpack(a);
+	//the exponent needs to be even to halve it. Then scale up for enough bits in the root
+	let (exp, sig) = if exp & 1 != 0 { (exp - 1, sig << 1) } else { (exp, sig) };
+	let (root, inexact) = isqrt(sig << 70);
+	round_pack(fmt, false, (exp - 70) / 2, root | inexact as u128, rm)
+}
+
+//a * b + c, rounded once. The negated versions just flip signs on the way in
+pub fn fma(fmt: Format, a: u64, b: u64, c: u64, rm: RoundingMode) -> (u64, u32) {
+	//inf * 0 is invalid, even when c is a quiet NaN
+	if (fmt.is_inf(a) && fmt.is_zero(b)) || (fmt.is_zero(a) && fmt.is_inf(b)) {
+		return (fmt.canonical_nan(), NV);
+	}
+	if fmt.is_nan(a) || fmt.is_nan(b) || fmt.is_nan(c) {
+		let flags = if fmt.is_snan(a) || fmt.is_snan(b) || fmt.is_snan(c) { NV } else { 0 };
+		return (fmt.canonical_nan(), flags);
+	}
+	let signP = fmt.sign(a) != fmt.sign(b);
+	let signC = fmt.sign(c);
+	if fmt.is_inf(a) || fmt.is_inf(b) {
+		if fmt.is_inf(c) && signC != signP {
+			return (fmt.canonical_nan(), NV);
+		}
+		return (fmt.infinity(signP), 0);
+	}
+	if fmt.is_inf(c) {
+		return (c, 0);
+	}
+	if fmt.is_zero(a) || fmt.is_zero(b) {
+		if fmt.is_zero(c) {
+			return (exact_zero(fmt, signP, signC, rm), 0);
+		}
+		return (c, 0);
+	}
+	let (expA, sigA) = fmt.unpack(a);
+	let (expB, sigB) = fmt.unpack(b);
+	if fmt.is_zero(c) {
+		return round_pack(fmt, signP, expA + expB, sigA * sigB, rm);
+	}
+	let (expC, sigC) = fmt.unpack(c);
+	add_finite(fmt, (signP, expA + expB, sigA * sigB), (signC, expC, sigC), rm)
+}
+
+//float to a width bit integer. Anything out of range (NaN counts as really big) saturates
+//and is invalid rather than inexact. 32 bit results come back sign extended
+pub fn to_int(fmt: Format, a: u64, signed: bool, width: u32, rm: RoundingMode) -> (u64, u32) {
+	let sign = fmt.sign(a);
+	let max: i128 = if signed { (1 << (width - 1)) - 1 } else { (1 << width) - 1 };
+	let min: i128 = if signed { -(1 << (width - 1)) } else { 0 };
+	let saturated = if sign && !fmt.is_nan(a) { min } else { max };
+	let (val, flags) = if fmt.is_nan(a) || fmt.is_inf(a) {
+		(saturated, NV)
+	}
+	else if fmt.is_zero(a) {
+		(0, 0)
+	}
+	else {
+		let (exp, sig) = fmt.unpack(a);
+		//far too big for any integer, and would overflow the shift
+		if exp > 64 {
+			(saturated, NV)
+		}
+		else {
+			let (mag, inexact) = round_at(sig, exp, 0, sign, rm);
+			let val = if sign { -(mag as i128) } else { mag as i128 };
+			if val > max || val < min {
+				(saturated, NV)
+			}
+			else {
+				(val, if inexact { NX } else { 0 })
+			}
+		}
+	};
+	let res = if width == 32 { val as i32 as i64 as u64 } else { val as u64 };
+	(res, flags)
+}
+
+//a width bit integer to float
+pub fn from_int(fmt: Format, val: u64, signed: bool, width: u32, rm: RoundingMode) -> (u64, u32) {
+	let val: i128 = match (signed, width) {
+		(true, 32) => val as i32 as i128,
+		(false, 32) => val as u32 as i128,
+		(true, _) => val as i64 as i128,
+		(false, _) => val as i128,
+	};
+	if val == 0 {
+		return (fmt.zero(false), 0);
+	}
+	round_pack(fmt, val < 0, 0, val.unsigned_abs(), rm)
+}
+
+//between precisions. Widening is always exact, narrowing rounds
+pub fn convert(from: Format, to: Format, a: u64, rm: RoundingMode) -> (u64, u32) {
+	if from.is_nan(a) {
+		let flags = if from.is_snan(a) { NV } else { 0 };
+		return (to.canonical_nan(), flags);
+	}
+	let sign = from.sign(a);
+	if from.is_inf(a) {
+		return (to.infinity(sign), 0);
+	}
+	if from.is_zero(a) {
+		return (to.zero(sign), 0);
+	}
+	let (exp, sig) = from.unpack(a);
+	round_pack(to, sign, exp, sig, rm)
+}
+
+//feq is quiet, so only a signalling NaN is invalid
+pub fn eq(fmt: Format, a: u64, b: u64) -> (u64, u32) {
+	if fmt.is_nan(a) || fmt.is_nan(b) {
+		return (0, propagate(fmt, a, b).1);
+	}
+	((fmt.to_f64(a) == fmt.to_f64(b)) as u64, 0)
+}
+
+//flt and fle signal on any NaN
+pub fn lt(fmt: Format, a: u64, b: u64) -> (u64, u32) {
+	if fmt.is_nan(a) || fmt.is_nan(b) {
+		return (0, NV);
+	}
+	((fmt.to_f64(a) < fmt.to_f64(b)) as u64, 0)
+}
+
+pub fn le(fmt: Format, a: u64, b: u64) -> (u64, u32) {
+	if fmt.is_nan(a) || fmt.is_nan(b) {
+		return (0, NV);
+	}
+	((fmt.to_f64(a) <= fmt.to_f64(b)) as u64, 0)
+}
+
+//a NaN loses to anything that isnt one, and -0 counts as smaller than +0
+fn min_max(fmt: Format, a: u64, b: u64, wantMin: bool) -> (u64, u32) {
+	let flags = propagate(fmt, a, b).1;
+	match (fmt.is_nan(a), fmt.is_nan(b)) {
+		(true, true) => (fmt.canonical_nan(), flags),
+		(true, false) => (b, flags),
+		(false, true) => (a, flags),
+		(false, false) => {
+			let (x, y) = (fmt.to_f64(a), fmt.to_f64(b));
+			let pickA = if x == y { fmt.sign(a) == wantMin } else { (x < y) == wantMin };
+			(if pickA { a } else { b }, 0)
+		},
+	}
+}
+
+pub fn min(fmt: Format, a: u64, b: u64) -> (u64, u32) {
+	min_max(fmt, a, b, true)
+}
+
+pub fn max(fmt: Format, a: u64, b: u64) -> (u64, u32) {
+	min_max(fmt, a, b, false)
+}
+
+impl Cpu {
+	//the rounding mode an instruction asked for. 7 means whatever frm says, and a reserved
+	//mode either way makes it an illegal instruction
+	pub fn rounding(&self, rm: u8) -> Result<RoundingMode, Exception> {
+		let bits = if rm == 7 { (self.fcsr >> 5) & 0x7 } else { rm as u32 };
+		RoundingMode::from_bits(bits).ok_or_else(|| self.illegal())
+	}
+
+	//flags accrue. Only software clears them
+	pub fn raise(&mut self, flags: u32) {
+		self.fcsr |= flags & 0x1f;
+	}
+
+	//raw bits of a float register. A single that isnt properly NaN boxed reads as the canonical NaN
+	pub fn freg(&self, fmt: Format, reg: u8) -> u64 {
+		let bits = self.fregs[reg as usize].to_bits();
+		if fmt == DOUBLE {
+			bits
+		}
+		else {
+			Cpu::unbox_float(f64::from_bits(bits)).to_bits() as u64
+		}
+	}
+
+	pub fn set_freg(&mut self, fmt: Format, reg: u8, bits: u64) {
+		let bits = if fmt == SINGLE { bits | (0xffff_ffff << 32) } else { bits };
+		self.fregs[reg as usize] = f64::from_bits(bits);
+	}
+
+	//the two source ops that round: add, sub, mul, div
+	pub fn float_arith(&mut self, inst: RegRegInst, fmt: Format, op: fn(Format, u64, u64, RoundingMode) -> (u64, u32)) -> Result<(), Exception> {
+		let rm = self.rounding(inst.rm)?;
+		let (res, flags) = op(fmt, self.freg(fmt, inst.rs1), self.freg(fmt, inst.rs2), rm);
+		self.raise(flags);
+		self.set_freg(fmt, inst.rd, res);
+		Ok(())
+	}
+
+	pub fn float_sqrt(&mut self, inst: RegRegInst, fmt: Format) -> Result<(), Exception> {
+		let rm = self.rounding(inst.rm)?;
+		let (res, flags) = sqrt(fmt, self.freg(fmt, inst.rs1), rm);
+		self.raise(flags);
+		self.set_freg(fmt, inst.rd, res);
+		Ok(())
+	}
+
+	//fmin and fmax
+	pub fn float_pick(&mut self, inst: RegRegInst, fmt: Format, op: fn(Format, u64, u64) -> (u64, u32)) {
+		let (res, flags) = op(fmt, self.freg(fmt, inst.rs1), self.freg(fmt, inst.rs2));
+		self.raise(flags);
+		self.set_freg(fmt, inst.rd, res);
+	}
+
+	//feq, flt and fle. The answer goes in an integer register
+	pub fn float_compare(&mut self, inst: RegRegInst, fmt: Format, op: fn(Format, u64, u64) -> (u64, u32)) {
+		let (res, flags) = op(fmt, self.freg(fmt, inst.rs1), self.freg(fmt, inst.rs2));
+		self.raise(flags);
+		self.regs[inst.rd as usize] = res;
+	}
+
+	pub fn float_to_int(&mut self, inst: RegRegInst, fmt: Format, signed: bool, width: u32) -> Result<(), Exception> {
+		let rm = self.rounding(inst.rm)?;
+		let (res, flags) = to_int(fmt, self.freg(fmt, inst.rs1), signed, width, rm);
+		self.raise(flags);
+		self.regs[inst.rd as usize] = res;
+		Ok(())
+	}
+
+	pub fn int_to_float(&mut self, inst: RegRegInst, fmt: Format, signed: bool, width: u32) -> Result<(), Exception> {
+		let rm = self.rounding(inst.rm)?;
+		let (res, flags) = from_int(fmt, self.regs[inst.rs1 as usize], signed, width, rm);
+		self.raise(flags);
+		self.set_freg(fmt, inst.rd, res);
+		Ok(())
+	}
+
+	pub fn float_convert(&mut self, inst: RegRegInst, from: Format, to: Format) -> Result<(), Exception> {
+		let rm = self.rounding(inst.rm)?;
+		let (res, flags) = convert(from, to, self.freg(from, inst.rs1), rm);
+		self.raise(flags);
+		self.set_freg(to, inst.rd, res);
+		Ok(())
+	}
+
+	//the fused multiply-adds. The negated forms flip the sign of the product and/or the addend
+	pub fn float_fma(&mut self, inst: R4Inst, fmt: Format, negProduct: bool, negAddend: bool) -> Result<(), Exception> {
+		let rm = self.rounding(inst.rm)?;
+		let flip = |neg: bool| if neg { fmt.sign_bit() } else { 0 };
+		let a = self.freg(fmt, inst.rs1) ^ flip(negProduct);
+		let c = self.freg(fmt, inst.rs3) ^ flip(negAddend);
+		let (res, flags) = fma(fmt, a, self.freg(fmt, inst.rs2), c, rm);
+		self.raise(flags);
+		self.set_freg(fmt, inst.rd, res);
+		Ok(())
+	}
+}
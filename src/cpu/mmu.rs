@@ -0,0 +1,268 @@
+//the mmu
+//turns virtual addresses into physical ones by walking the page tables satp points at
+//supports Sv39, Sv48 and Sv57 - they only differ in how many levels there are
+//
+//A/D bits get set by the walker (rather than faulting) when theyre not already set
+//successful walks get cached in the tlb
+//every physical access (including the walk itself) then goes through pmp
+//
+
+use super::*;
+use super::tlb::TlbEntry;
+
+//what the access is for. Decides which permission bit gets checked
+//and what kind of fault comes out of it
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum AccessType {
+	Instruction,
+	Load,
+	Store, //includes AMOs
+}
+
+impl AccessType {
+	pub fn access_fault(&self, addr: u64) -> Exception {
+		match self {
+			AccessType::Instruction => Exception::InstructionAccessFault(addr),
+			AccessType::Load => Exception::LoadAccessFault(addr),
+			AccessType::Store => Exception::StoreAccessFault(addr),
+		}
+	}
+
+	pub fn page_fault(&self, addr: u64) -> Exception {
+		match self {
+			AccessType::Instruction => Exception::InstructionPageFault(addr),
+			AccessType::Load => Exception::LoadPageFault(addr),
+			AccessType::Store => Exception::StorePageFault(addr),
+		}
+	}
+}
+
+//satp fields
+pub const SATP_MODE_SHIFT: u64 = 60;
+pub const SATP_MODE_BARE: u64 = 0;
+pub const SATP_MODE_SV39: u64 = 8;
+pub const SATP_MODE_SV48: u64 = 9;
+pub const SATP_MODE_SV57: u64 = 10;
+pub const SATP_ASID_SHIFT: u64 = 44;
+pub const SATP_ASID: u64 = 0xffff;
+pub const SATP_PPN: u64 = (1 << 44) - 1;
+
+//page table entry bits
+pub const PTE_V: u64 = 1 << 0;
+pub const PTE_R: u64 = 1 << 1;
+pub const PTE_W: u64 = 1 << 2;
+pub const PTE_X: u64 = 1 << 3;
+pub const PTE_U: u64 = 1 << 4;
+pub const PTE_G: u64 = 1 << 5;
+pub const PTE_A: u64 = 1 << 6;
+pub const PTE_D: u64 = 1 << 7;
+const PTE_PPN_SHIFT: u64 = 10;
+const PTE_PPN: u64 = (1 << 44) - 1;
+//N/PBMT and the reserved bits. None of those extensions are supported so these have to be 0
+const PTE_RESERVED: u64 = 0x3ff << 54;
+
+pub const PAGE_SHIFT: u64 = 12;
+pub const PAGE_SIZE: u64 = 1 << PAGE_SHIFT;
+
+//number of page table levels for each satp mode
+pub fn satp_levels(satp: u64) -> Option<u64> {
+	match satp >> SATP_MODE_SHIFT {
+		SATP_MODE_SV39 => Some(3),
+		SATP_MODE_SV48 => Some(4),
+		SATP_MODE_SV57 => Some(5),
+		_ => None,
+	}
+}
+
+impl Cpu {
+	//privilege loads and stores happen at. mprv lets machine mode borrow mpp's
+	pub fn data_privilege(&self) -> privilege_level {
+		let status = self.load_csr(MSTATUS);
+		if self.prv == privilege_level::M && (status & MSTATUS_MPRV) != 0 {
+			return privilege_level::from_bits((status & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT);
+		}
+		self.prv
+	}
+
+	//fetches always happen at the current privilege, mprv only affects loads and stores
+	pub fn access_privilege(&self, access: AccessType) -> privilege_level {
+		if access == AccessType::Instruction { self.prv } else { self.data_privilege() }
+	}
+
+	//virtual -> physical
+	//machine mode and bare mode dont translate at all
+	pub fn translate(&mut self, vaddr: u64, access: AccessType) -> Result<u64, Exception> {
+		let prv = self.access_privilege(access);
+		let satp = self.load_csr(SATP);
+		let levels = match satp_levels(satp) {
+			Some(levels) if prv != privilege_level::M => levels,
+			_ => return Ok(vaddr),
+		};
+		let asid = ((satp >> SATP_ASID_SHIFT) & SATP_ASID) as u16;
+		if let Some(entry) = self.tlb.lookup(asid, vaddr) {
+			//first store to a clean page has to go back to the page table to set D
+			if access != AccessType::Store || entry.pte & PTE_D != 0 {
+				self.tlb.hits += 1;
+				self.check_pte(vaddr, entry.pte, access, prv)?;
+				return Ok(entry.translate(vaddr));
+			}
+		}
+		self.tlb.misses += 1;
+		let entry = self.walk(vaddr, access, prv, satp, levels)?;
+		self.tlb.insert(asid, entry.pte & PTE_G != 0, vaddr, entry);
+		Ok(entry.translate(vaddr))
+	}
+
+	//walks the page table for vaddr, returning the leaf pte and where its page starts
+	//A/D bits only get set once the access is known to be allowed
+	pub fn walk(&mut self, vaddr: u64, access: AccessType, prv: privilege_level, satp: u64, levels: u64) -> Result<TlbEntry, Exception> {
+		//addresses have to be sign extended from the top bit of the virtual address
+		let vaBits = PAGE_SHIFT + 9 * levels;
+		let top = (vaddr as i64) >> (vaBits - 1);
+		if top != 0 && top != -1 {
+			return Err(access.page_fault(vaddr));
+		}
+
+		let mut table = (satp & SATP_PPN) << PAGE_SHIFT;
+		let mut level = levels - 1;
+		let mut global = false;
+		loop {
+			let vpn = (vaddr >> (PAGE_SHIFT + 9 * level)) & 0x1ff;
+			let pteAddr = table + vpn * 8;
+			//page table accesses are checked by pmp as supervisor accesses
+			if !self.pmp.check(pteAddr, 8, AccessType::Load, privilege_level::S) {
+				return Err(access.access_fault(vaddr));
+			}
+			let mut pte = self.bus.load(pteAddr, 8).map_err(|_| access.access_fault(vaddr))?;
+			if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) || pte & PTE_RESERVED != 0 {
+				return Err(access.page_fault(vaddr));
+			}
+			let ppn = (pte >> PTE_PPN_SHIFT) & PTE_PPN;
+			//not a leaf - go down a level
+			if pte & (PTE_R | PTE_X) == 0 {
+				//A, D and U are reserved in non-leaf entries
+				if level == 0 || pte & (PTE_A | PTE_D | PTE_U) != 0 {
+					return Err(access.page_fault(vaddr));
+				}
+				//a global bit anywhere up the tree makes everything under it global
+				global |= pte & PTE_G != 0;
+				table = ppn << PAGE_SHIFT;
+				level -= 1;
+				continue;
+			}
+			//superpages have to be aligned to their size
+			let pageMask = (1u64 << (9 * level)) - 1;
+			if ppn & pageMask != 0 {
+				return Err(access.page_fault(vaddr));
+			}
+			self.check_pte(vaddr, pte, access, prv)?;
+			//first access to the page, or first write - hardware sets the bits
+			let mut update = PTE_A;
+			if access == AccessType::Store {
+				update |= PTE_D;
+			}
+			if pte & update != update {
+				if !self.pmp.check(pteAddr, 8, AccessType::Store, privilege_level::S) {
+					return Err(access.access_fault(vaddr));
+				}
+				pte |= update;
+				self.bus.store(pteAddr, pte, 8).map_err(|_| access.access_fault(vaddr))?;
+			}
+			if global {
+				pte |= PTE_G;
+			}
+			return Ok(TlbEntry {
+				pte,
+				level,
+				paddr: ppn << PAGE_SHIFT,
+			});
+		}
+	}
+
+	//permission checks for a leaf pte
+	pub fn check_pte(&self, vaddr: u64, pte: u64, access: AccessType, prv: privilege_level) -> Result<(), Exception> {
+		let status = self.load_csr(MSTATUS);
+		let allowed = match access {
+			AccessType::Instruction => pte & PTE_X != 0,
+			//mxr makes execute only pages readable
+			AccessType::Load => pte & PTE_R != 0 || ((status & MSTATUS_MXR) != 0 && pte & PTE_X != 0),
+			AccessType::Store => pte & PTE_W != 0,
+		};
+		if !allowed {
+			return Err(access.page_fault(vaddr));
+		}
+		//user pages are off limits to supervisor unless sum is set, and supervisor can never run them
+		//supervisor pages are always off limits to user
+		let user = pte & PTE_U != 0;
+		let ok = match prv {
+			privilege_level::U => user,
+			privilege_level::S => !user || (access != AccessType::Instruction && (status & MSTATUS_SUM) != 0),
+			privilege_level::M => true,
+		};
+		if !ok {
+			return Err(access.page_fault(vaddr));
+		}
+		Ok(())
+	}
+
+	//physical address for a load that has to happen in one piece (lr)
+	//naturally aligned like the stores below, so it never straddles a page
+	pub fn translate_load(&mut self, vaddr: u64, size: u8) -> Result<u64, Exception> {
+		let paddr = self.translate(vaddr, AccessType::Load)?;
+		if !self.pmp.check(paddr, size as u64, AccessType::Load, self.data_privilege()) {
+			return Err(Exception::LoadAccessFault(vaddr));
+		}
+		Ok(paddr)
+	}
+
+	//physical address for a store that has to happen in one piece (amos and sc)
+	//these are always naturally aligned, so they never straddle a page
+	pub fn translate_store(&mut self, vaddr: u64, size: u8) -> Result<u64, Exception> {
+		let paddr = self.translate(vaddr, AccessType::Store)?;
+		if !self.pmp.check(paddr, size as u64, AccessType::Store, self.data_privilege()) {
+			return Err(Exception::StoreAccessFault(vaddr));
+		}
+		Ok(paddr)
+	}
+
+	//reads size bytes at a virtual address
+	//accesses that straddle a page get split up, since each page can map anywhere
+	pub fn read_virt(&mut self, vaddr: u64, size: u8, access: AccessType) -> Result<u64, Exception> {
+		if (vaddr & (PAGE_SIZE - 1)) + size as u64 > PAGE_SIZE {
+			let mut data = 0;
+			for i in 0..size as u64 {
+				data |= self.read_virt(vaddr.wrapping_add(i), 1, access)? << (8 * i);
+			}
+			return Ok(data);
+		}
+		let paddr = self.translate(vaddr, access)?;
+		if !self.pmp.check(paddr, size as u64, access, self.access_privilege(access)) {
+			return Err(access.access_fault(vaddr));
+		}
+		self.bus.load(paddr, size).map_err(|_| access.access_fault(vaddr))
+	}
+
+	pub fn write_virt(&mut self, vaddr: u64, data: u64, size: u8) -> Result<(), Exception> {
+		if (vaddr & (PAGE_SIZE - 1)) + size as u64 > PAGE_SIZE {
+			//translate everything first so a fault on the second page doesnt leave half a store behind
+			let mut paddrs = Vec::new();
+			for i in 0..size as u64 {
+				let paddr = self.translate(vaddr.wrapping_add(i), AccessType::Store)?;
+				if !self.pmp.check(paddr, 1, AccessType::Store, self.data_privilege()) {
+					return Err(Exception::StoreAccessFault(vaddr));
+				}
+				paddrs.push(paddr);
+			}
+			for (i, paddr) in paddrs.into_iter().enumerate() {
+				self.bus.store(paddr, (data >> (8 * i)) & 0xff, 1).map_err(|_| Exception::StoreAccessFault(vaddr))?;
+			}
+			return Ok(());
+		}
+		let paddr = self.translate(vaddr, AccessType::Store)?;
+		if !self.pmp.check(paddr, size as u64, AccessType::Store, self.data_privilege()) {
+			return Err(Exception::StoreAccessFault(vaddr));
+		}
+		self.bus.store(paddr, data, size).map_err(|_| Exception::StoreAccessFault(vaddr))
+	}
+}
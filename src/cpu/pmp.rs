@@ -0,0 +1,155 @@
+//physical memory protection
+//64 entries, each a pmpaddr + a config byte packed into the pmpcfg csrs (8 to a csr on rv64)
+//the csrs still live in the csrs array, but every write rebuilds a list of the active
+//regions so that checking an access doesnt have to decode everything again
+//
+//like qemu, if no entry is turned on then nothing is checked at all - otherwise
+//supervisor and user would have no access to anything until firmware set pmp up
+
+use super::*;
+
+pub const PMP_COUNT: usize = 64;
+
+//config byte fields
+pub const PMP_R: u8 = 1 << 0;
+pub const PMP_W: u8 = 1 << 1;
+pub const PMP_X: u8 = 1 << 2;
+pub const PMP_A_SHIFT: u8 = 3;
+pub const PMP_A: u8 = 0x3 << PMP_A_SHIFT;
+pub const PMP_L: u8 = 1 << 7;
+//address matching modes (0 is off)
+pub const PMP_TOR: u8 = 1;
+pub const PMP_NA4: u8 = 2;
+pub const PMP_NAPOT: u8 = 3;
+
+//pmpaddr holds bits 55:2 of the address
+const PMPADDR_MASK: u64 = (1 << 54) - 1;
+
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct PmpRegion {
+	pub start: u64,
+	pub end: u64, //exclusive
+	pub cfg: u8,
+}
+
+pub struct Pmp {
+	regions: Vec<PmpRegion>, //in priority order, lowest entry first
+}
+
+impl Pmp {
+	pub fn New() -> Pmp {
+		Pmp {
+			regions: Vec::new(),
+		}
+	}
+
+	//decodes the active entries out of the raw config bytes and addresses
+	pub fn rebuild(&mut self, cfgs: &[u8], addrs: &[u64]) {
+		self.regions.clear();
+		for i in 0..PMP_COUNT {
+			let cfg = cfgs[i];
+			let addr = addrs[i] & PMPADDR_MASK;
+			let (start, end) = match (cfg & PMP_A) >> PMP_A_SHIFT {
+				PMP_TOR => {
+					let start = if i == 0 { 0 } else { (addrs[i - 1] & PMPADDR_MASK) << 2 };
+					(start, addr << 2)
+				},
+				PMP_NA4 => (addr << 2, (addr << 2) + 4),
+				PMP_NAPOT => {
+					//trailing ones say how big the region is, 8 bytes at the smallest
+					let ones = addr.trailing_ones() as u64;
+					let size = 1u64 << (ones + 3);
+					let start = (addr & !((1u64 << ones) - 1)) << 2;
+					(start, start + size)
+				},
+				_ => continue,
+			};
+			//a TOR entry with its top below its bottom doesnt match anything
+			if end <= start {
+				continue;
+			}
+			self.regions.push(PmpRegion { start, end, cfg });
+		}
+	}
+
+	//whether an access of size bytes at paddr is allowed
+	pub fn check(&self, paddr: u64, size: u64, access: AccessType, prv: privilege_level) -> bool {
+		if self.regions.is_empty() {
+			return true;
+		}
+		let last = paddr.saturating_add(size - 1);
+		for region in &self.regions {
+			//any byte in the region is a match
+			if paddr >= region.end || last < region.start {
+				continue;
+			}
+			//but the first matching entry has to cover the whole access
+			if paddr < region.start || last >= region.end {
+				return false;
+			}
+			//machine mode only cares about locked entries
+			if prv == privilege_level::M && region.cfg & PMP_L == 0 {
+				return true;
+			}
+			let perm = match access {
+				AccessType::Instruction => PMP_X,
+				AccessType::Load => PMP_R,
+				AccessType::Store => PMP_W,
+			};
+			return region.cfg & perm != 0;
+		}
+		//nothing matched - only machine mode gets through
+		prv == privilege_level::M
+	}
+}
+
+impl Cpu {
+	//config byte of entry i
+	pub fn pmp_cfg(&self, i: usize) -> u8 {
+		(self.csrs[PMPCFG0 + (i / 8) * 2] >> ((i % 8) * 8)) as u8
+	}
+
+	//writes to a pmpcfg csr. Locked entries keep their old config
+	pub fn store_pmpcfg(&mut self, addr: usize, val: u64) {
+		let first = (addr - PMPCFG0) / 2 * 8;
+		let mut packed = 0;
+		for byte in 0..8 {
+			let old = self.pmp_cfg(first + byte);
+			let mut cfg = (val >> (byte * 8)) as u8;
+			if old & PMP_L != 0 {
+				cfg = old;
+			}
+			//bits 5 and 6 are reserved, and write without read is a reserved combination
+			cfg &= !0x60;
+			if cfg & PMP_W != 0 && cfg & PMP_R == 0 {
+				cfg &= !PMP_W;
+			}
+			packed |= (cfg as u64) << (byte * 8);
+		}
+		self.csrs[addr] = packed;
+		self.update_pmp();
+	}
+
+	//writes to a pmpaddr csr. locked entries (and the entry a locked TOR entry uses
+	//as its bottom) cant be changed
+	pub fn store_pmpaddr(&mut self, addr: usize, val: u64) {
+		let i = addr - PMPADDR0;
+		if self.pmp_cfg(i) & PMP_L != 0 {
+			return;
+		}
+		if i + 1 < PMP_COUNT {
+			let next = self.pmp_cfg(i + 1);
+			if next & PMP_L != 0 && (next & PMP_A) >> PMP_A_SHIFT == PMP_TOR {
+				return;
+			}
+		}
+		self.csrs[addr] = val & PMPADDR_MASK;
+		self.update_pmp();
+	}
+
+	fn update_pmp(&mut self) {
+		let cfgs: Vec<u8> = (0..PMP_COUNT).map(|i| self.pmp_cfg(i)).collect();
+		self.pmp.rebuild(&cfgs, &self.csrs[PMPADDR0..PMPADDR0 + PMP_COUNT]);
+	}
+}
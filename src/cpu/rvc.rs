@@ -0,0 +1,220 @@
+//compressed (C extension) instructions
+//every 16 bit instruction is just a shorter way of writing some 32 bit one, so rather
+//than teaching decode and execute about a whole new set of instructions, they get
+//expanded out to the full encoding here and go through decode like anything else
+//
+//anything with the bottom two bits not both set is compressed. The register fields
+//written rd'/rs1'/rs2' are 3 bits wide and only reach x8-x15 (or f8-f15)
+
+use super::*;
+
+//opcodes of the 32 bit instructions things expand to
+const OP_LOAD: u32 = 3;
+const OP_LOAD_FP: u32 = 7;
+const OP_IMM: u32 = 19;
+const OP_IMM_32: u32 = 27;
+const OP_STORE: u32 = 35;
+const OP_STORE_FP: u32 = 39;
+const OP: u32 = 51;
+const OP_LUI: u32 = 55;
+const OP_32: u32 = 59;
+const OP_BRANCH: u32 = 99;
+const OP_JALR: u32 = 103;
+const OP_JAL: u32 = 111;
+const OP_SYSTEM: u32 = 115;
+
+//pulls bits [hi:lo] of the parcel down to the bottom
+fn bits(parcel: u32, hi: u32, lo: u32) -> u32 {
+	(parcel >> lo) & ((1 << (hi - lo + 1)) - 1)
+}
+
+//sign extends the bottom width bits
+fn sext(val: u32, width: u32) -> i32 {
+	((val << (32 - width)) as i32) >> (32 - width)
+}
+
+//the 3 bit register fields
+fn creg(field: u32) -> u32 {
+	field + 8
+}
+
+fn itype(opcode: u32, funct3: u32, rd: u32, rs1: u32, imm: i32) -> u32 {
+	((imm as u32 & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn rtype(opcode: u32, funct3: u32, funct7: u32, rd: u32, rs1: u32, rs2: u32) -> u32 {
+	(funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+
+fn stype(opcode: u32, funct3: u32, rs1: u32, rs2: u32, imm: i32) -> u32 {
+	let imm = imm as u32;
+	(bits(imm, 11, 5) << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (bits(imm, 4, 0) << 7) | opcode
+}
+
+fn btype(funct3: u32, rs1: u32, rs2: u32, imm: i32) -> u32 {
+	let imm = imm as u32;
+	(bits(imm, 12, 12) << 31) | (bits(imm, 10, 5) << 25) | (rs2 << 20) | (rs1 << 15)
+		| (funct3 << 12) | (bits(imm, 4, 1) << 8) | (bits(imm, 11, 11) << 7) | OP_BRANCH
+}
+
+fn jtype(rd: u32, imm: i32) -> u32 {
+	let imm = imm as u32;
+	(bits(imm, 20, 20) << 31) | (bits(imm, 10, 1) << 21) | (bits(imm, 11, 11) << 20)
+		| (bits(imm, 19, 12) << 12) | (rd << 7) | OP_JAL
+}
+
+//offset field of c.j and c.jal
+fn cj_offset(p: u32) -> i32 {
+	let imm = (bits(p, 12, 12) << 11) | (bits(p, 11, 11) << 4) | (bits(p, 10, 9) << 8)
+		| (bits(p, 8, 8) << 10) | (bits(p, 7, 7) << 6) | (bits(p, 6, 6) << 7)
+		| (bits(p, 5, 3) << 1) | (bits(p, 2, 2) << 5);
+	sext(imm, 12)
+}
+
+//the 6 bit immediate most of quadrant 1 uses. bit 5 is at 12, 4:0 at 6:2
+fn ci_imm(p: u32) -> u32 {
+	(bits(p, 12, 12) << 5) | bits(p, 6, 2)
+}
+
+//offsets for word and doubleword loads/stores off a register
+fn cl_word(p: u32) -> i32 {
+	((bits(p, 12, 10) << 3) | (bits(p, 6, 6) << 2) | (bits(p, 5, 5) << 6)) as i32
+}
+
+fn cl_double(p: u32) -> i32 {
+	((bits(p, 12, 10) << 3) | (bits(p, 6, 5) << 6)) as i32
+}
+
+//expands a 16 bit instruction to the 32 bit one it stands for
+pub fn expand(parcel: u16) -> Result<u32, Exception> {
+	let p = parcel as u32;
+	let illegal = Err(Exception::IllegalInstruction(parcel as u64));
+	let funct3 = bits(p, 15, 13);
+	//full size register fields, used by quadrants 1 and 2
+	let rd = bits(p, 11, 7);
+	let rs2 = bits(p, 6, 2);
+	//compressed register fields
+	let rdc = creg(bits(p, 4, 2));
+	let rs1c = creg(bits(p, 9, 7));
+
+	let inst = match (p & 0x3, funct3) {
+		//c.addi4spn. All zeros is defined to be illegal, and lands here too
+		(0, 0) => {
+			let imm = (bits(p, 12, 11) << 4) | (bits(p, 10, 7) << 6) | (bits(p, 6, 6) << 2) | (bits(p, 5, 5) << 3);
+			if imm == 0 {
+				return illegal;
+			}
+			itype(OP_IMM, 0, rdc, 2, imm as i32)
+		},
+		//c.fld, c.lw, c.ld
+		(0, 1) => itype(OP_LOAD_FP, 3, rdc, rs1c, cl_double(p)),
+		(0, 2) => itype(OP_LOAD, 2, rdc, rs1c, cl_word(p)),
+		(0, 3) => itype(OP_LOAD, 3, rdc, rs1c, cl_double(p)),
+		//c.fsd, c.sw, c.sd. The source is in the rd' spot
+		(0, 5) => stype(OP_STORE_FP, 3, rs1c, rdc, cl_double(p)),
+		(0, 6) => stype(OP_STORE, 2, rs1c, rdc, cl_word(p)),
+		(0, 7) => stype(OP_STORE, 3, rs1c, rdc, cl_double(p)),
+
+		//c.addi (c.nop when rd is 0)
+		(1, 0) => itype(OP_IMM, 0, rd, rd, sext(ci_imm(p), 6)),
+		//c.addiw. Replaces c.jal on rv64
+		(1, 1) => {
+			if rd == 0 {
+				return illegal;
+			}
+			itype(OP_IMM_32, 0, rd, rd, sext(ci_imm(p), 6))
+		},
+		//c.li
+		(1, 2) => itype(OP_IMM, 0, rd, 0, sext(ci_imm(p), 6)),
+		//c.addi16sp
+		(1, 3) if rd == 2 => {
+			let imm = (bits(p, 12, 12) << 9) | (bits(p, 6, 6) << 4) | (bits(p, 5, 5) << 6)
+				| (bits(p, 4, 3) << 7) | (bits(p, 2, 2) << 5);
+			if imm == 0 {
+				return illegal;
+			}
+			itype(OP_IMM, 0, 2, 2, sext(imm, 10))
+		},
+		//c.lui
+		(1, 3) => {
+			let imm = sext(ci_imm(p), 6);
+			if imm == 0 {
+				return illegal;
+			}
+			((imm as u32) << 12) | (rd << 7) | OP_LUI
+		},
+		(1, 4) => {
+			match bits(p, 11, 10) {
+				//c.srli, c.srai
+				0 => itype(OP_IMM, 5, rs1c, rs1c, ci_imm(p) as i32),
+				1 => itype(OP_IMM, 5, rs1c, rs1c, (ci_imm(p) | 0x400) as i32),
+				//c.andi
+				2 => itype(OP_IMM, 7, rs1c, rs1c, sext(ci_imm(p), 6)),
+				//register-register ops on rd'/rs2'
+				_ => {
+					match (bits(p, 12, 12), bits(p, 6, 5)) {
+						(0, 0) => rtype(OP, 0, 0x20, rs1c, rs1c, rdc), //c.sub
+						(0, 1) => rtype(OP, 4, 0, rs1c, rs1c, rdc), //c.xor
+						(0, 2) => rtype(OP, 6, 0, rs1c, rs1c, rdc), //c.or
+						(0, 3) => rtype(OP, 7, 0, rs1c, rs1c, rdc), //c.and
+						(1, 0) => rtype(OP_32, 0, 0x20, rs1c, rs1c, rdc), //c.subw
+						(1, 1) => rtype(OP_32, 0, 0, rs1c, rs1c, rdc), //c.addw
+						_ => return illegal,
+					}
+				},
+			}
+		},
+		//c.j
+		(1, 5) => jtype(0, cj_offset(p)),
+		//c.beqz, c.bnez
+		(1, 6) | (1, 7) => {
+			let imm = (bits(p, 12, 12) << 8) | (bits(p, 11, 10) << 3) | (bits(p, 6, 5) << 6)
+				| (bits(p, 4, 3) << 1) | (bits(p, 2, 2) << 5);
+			btype(funct3 - 6, rs1c, 0, sext(imm, 9))
+		},
+
+		//c.slli
+		(2, 0) => itype(OP_IMM, 1, rd, rd, ci_imm(p) as i32),
+		//c.fldsp, c.lwsp, c.ldsp. Integer loads into x0 are reserved
+		(2, 1) => {
+			let imm = (bits(p, 12, 12) << 5) | (bits(p, 6, 5) << 3) | (bits(p, 4, 2) << 6);
+			itype(OP_LOAD_FP, 3, rd, 2, imm as i32)
+		},
+		(2, 2) => {
+			if rd == 0 {
+				return illegal;
+			}
+			let imm = (bits(p, 12, 12) << 5) | (bits(p, 6, 4) << 2) | (bits(p, 3, 2) << 6);
+			itype(OP_LOAD, 2, rd, 2, imm as i32)
+		},
+		(2, 3) => {
+			if rd == 0 {
+				return illegal;
+			}
+			let imm = (bits(p, 12, 12) << 5) | (bits(p, 6, 5) << 3) | (bits(p, 4, 2) << 6);
+			itype(OP_LOAD, 3, rd, 2, imm as i32)
+		},
+		(2, 4) => {
+			match (bits(p, 12, 12), rd, rs2) {
+				//c.jr
+				(0, 0, 0) => return illegal,
+				(0, _, 0) => itype(OP_JALR, 0, 0, rd, 0),
+				//c.mv
+				(0, _, _) => rtype(OP, 0, 0, rd, 0, rs2),
+				//c.ebreak
+				(1, 0, 0) => itype(OP_SYSTEM, 0, 0, 0, 1),
+				//c.jalr
+				(1, _, 0) => itype(OP_JALR, 0, 1, rd, 0),
+				//c.add
+				_ => rtype(OP, 0, 0, rd, rd, rs2),
+			}
+		},
+		//c.fsdsp, c.swsp, c.sdsp
+		(2, 5) => stype(OP_STORE_FP, 3, 2, rs2, ((bits(p, 12, 10) << 3) | (bits(p, 9, 7) << 6)) as i32),
+		(2, 6) => stype(OP_STORE, 2, 2, rs2, ((bits(p, 12, 9) << 2) | (bits(p, 8, 7) << 6)) as i32),
+		(2, 7) => stype(OP_STORE, 3, 2, rs2, ((bits(p, 12, 10) << 3) | (bits(p, 9, 7) << 6)) as i32),
+
+		_ => return illegal,
+	};
+	Ok(inst)
+}
@@ -1,4 +1,21 @@
 use super::*;
+use super::mmu::*;
+use super::pmp::*;
+use super::fpu::{NX, UF, OF, DZ, NV};
+use crate::bus::BusError;
+use crate::trap::Exception;
+use crate::dram::{Dram, DRAM_BASE, DRAM_SIZE};
+use crate::clint::CLINT_BASE;
+use crate::plic::PLIC_BASE;
+use crate::uart::{Uart, UART_BASE, UART_IRQ};
+use crate::device::Device;
+use crate::machine::Machine;
+use crate::linux::{Linux, Fault, USER_BASE};
+use crate::elf::{self, Elf, Segment, PF_R, PF_X};
+use crate::mm;
+use crate::newlib::Newlib;
+use crate::semihost::Semihost;
+use std::sync::{Arc, Mutex};
 
 #[test]
 fn ADDI_test() {
@@ -11,19 +28,19 @@ fn ADDI_test() {
 		imm: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	inst.imm = -1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0);
 
 	
 	inst.imm = -1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1] as i64, -1);
 }
 
@@ -38,26 +55,26 @@ fn ADDIW_test() {
 		imm: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	inst.imm = -1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0);
 
 	
 	inst.imm = -1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1] as i64, -1);
 
 	cpu.regs[1] = 0xffffffff;
 	inst.imm = 1;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1] as i64, 0);
 }
 
@@ -66,6 +83,7 @@ fn ADD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 1,
@@ -74,19 +92,19 @@ fn ADD_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 0;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[2] = u64::MAX - 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], u64::MAX);
 
 	
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0);
 }
 
@@ -96,6 +114,7 @@ fn ADDW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::ADDW,
@@ -103,13 +122,13 @@ fn ADDW_test() {
 	};
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0);
 
 
@@ -117,7 +136,7 @@ fn ADDW_test() {
 	cpu.regs[2] = 1;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0);
 }
 
@@ -126,6 +145,7 @@ fn SUB_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 1,
@@ -134,13 +154,13 @@ fn SUB_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 0;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],u64::MAX);
 
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], u64::MAX - 1);
 }
 
@@ -150,6 +170,7 @@ fn SUBW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SUBW,
@@ -157,13 +178,13 @@ fn SUBW_test() {
 	};
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],u64::MAX);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0);
 
 
@@ -171,7 +192,7 @@ fn SUBW_test() {
 	cpu.regs[2] = 1;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], u64::MAX -1);
 }
 
@@ -187,13 +208,13 @@ fn SLTI_test() {
 		imm: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	inst.imm = -1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0);
 }
 
@@ -202,6 +223,7 @@ fn SLT_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SLT,
@@ -210,14 +232,14 @@ fn SLT_test() {
 	cpu.regs[1] = 0;
 	cpu.regs[2] = u64::MAX;
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],0);
 
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 2;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 1);
 }
 
@@ -232,13 +254,13 @@ fn SLTIU_test() {
 		imm: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	inst.imm = -1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 1);
 }
 
@@ -247,6 +269,7 @@ fn SLTU_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SLTU,
@@ -255,14 +278,14 @@ fn SLTU_test() {
 	cpu.regs[1] = 0;
 	cpu.regs[2] = u64::MAX;
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 2;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 1);
 }
 
@@ -278,7 +301,7 @@ fn ANDI_test() {
 		imm: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],0);
 
@@ -286,7 +309,7 @@ fn ANDI_test() {
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0b1111101010101010);
 }
 
@@ -295,6 +318,7 @@ fn AND_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::AND,
@@ -303,7 +327,7 @@ fn AND_test() {
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 0;
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],0);
 
@@ -311,7 +335,7 @@ fn AND_test() {
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0b1111101010101010);
 }
 
@@ -326,7 +350,7 @@ fn ORI_test() {
 		imm: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
@@ -334,7 +358,7 @@ fn ORI_test() {
 	cpu.regs[1] = 0b0000111111111111;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0b0000111111111111);
 }
 
@@ -344,6 +368,7 @@ fn OR_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::OR,
@@ -352,7 +377,7 @@ fn OR_test() {
 	cpu.regs[1] = 0;
 	cpu.regs[2] = 1;
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
@@ -360,7 +385,7 @@ fn OR_test() {
 	cpu.regs[1] = 0b0000111111111111;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0b0000111111111111);
 }
 
@@ -375,7 +400,7 @@ fn XORI_test() {
 		imm: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
@@ -383,7 +408,7 @@ fn XORI_test() {
 	cpu.regs[1] = 0b0000111111110000;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0b0000000011111111);
 }
 
@@ -393,6 +418,7 @@ fn XOR_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::XOR,
@@ -401,7 +427,7 @@ fn XOR_test() {
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 0;
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
@@ -409,7 +435,7 @@ fn XOR_test() {
 	cpu.regs[1] = 0b0000111111110000;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0b0000000011111111);
 }
 
@@ -418,6 +444,7 @@ fn SLL_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SLL,
@@ -426,19 +453,19 @@ fn SLL_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 2;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],4);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 14);
 
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x1fffe);
 }
 
@@ -454,19 +481,19 @@ fn SLLI_test() {
 	};
 	let mut instFmt = InstructionFormat::I(inst);
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],4);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 14);
 
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x1fffe);
 }
 
@@ -482,19 +509,19 @@ fn SLLIW_test() {
 	};
 	let mut instFmt = InstructionFormat::I(inst);
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],4);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 14);
 
 	cpu.regs[1] = 0xffffffffffff;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xfffffffffffffffe);
 }
 
@@ -503,6 +530,7 @@ fn SLLW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SLLW,
@@ -511,19 +539,19 @@ fn SLLW_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 2;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],4);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 14);
 
 	cpu.regs[1] = 0xffffffff;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xfffffffffffffffe);
 }
 
@@ -534,6 +562,7 @@ fn SRL_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SRL,
@@ -542,19 +571,19 @@ fn SRL_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 2;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x7fff);
 }
 
@@ -570,19 +599,19 @@ fn SRLI_test() {
 	};
 	let mut instFmt = InstructionFormat::I(inst);
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x7fff);
 }
 
@@ -598,19 +627,19 @@ fn SRLIW_test() {
 	};
 	let mut instFmt = InstructionFormat::I(inst);
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x7fff);
 }
 
@@ -619,6 +648,7 @@ fn SRLW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SRLW,
@@ -627,19 +657,19 @@ fn SRLW_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 2;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xffff;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x7fff);
 }
 
@@ -649,6 +679,7 @@ fn SRA_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SRA,
@@ -657,19 +688,19 @@ fn SRA_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 2;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xffffffffffffffff;
 
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xffffffffffffffff);
 }
 
@@ -686,19 +717,19 @@ fn SRAI_test() {
 	};
 	let mut instFmt = InstructionFormat::I(inst);
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xffffffffffffffff;
 
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xffffffffffffffff);
 }
 
@@ -716,19 +747,19 @@ fn SRAIW_test() {
 	};
 	let mut instFmt = InstructionFormat::I(inst);
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xfffffffe;
 	
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xffffffffffffffff);
 }
 
@@ -739,6 +770,7 @@ fn SRAW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rd: 1,
 		instName: Instruction::SRAW,
@@ -747,19 +779,19 @@ fn SRAW_test() {
 	let mut instFmt = InstructionFormat::R(inst);
 	cpu.regs[1] = 2;
 	cpu.regs[2] =1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1],1);
 
 	cpu.regs[1] = 7;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 3);
 
 	cpu.regs[1] = 0xfffffffe;
 	
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xffffffffffffffff);
 }
 
@@ -773,7 +805,7 @@ fn BEQ_test() {
 		rs2: 2,
 		
 		instName: Instruction::BEQ,
-		imm: 1,
+		imm: 4,
 	};
 	let mut instFmt = InstructionFormat::B(inst);
 
@@ -781,20 +813,20 @@ fn BEQ_test() {
 	let oldPc = cpu.pc;
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 	cpu.pc = 0x1000;
@@ -802,7 +834,7 @@ fn BEQ_test() {
 	cpu.regs[1] = 1;
 	inst.imm = -0x100;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -811,7 +843,7 @@ fn BEQ_test() {
 
 	inst.imm = -0xe00;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -829,7 +861,7 @@ fn BNE_test() {
 		rs2: 2,
 		
 		instName: Instruction::BNE,
-		imm: 1,
+		imm: 4,
 	};
 	let mut instFmt = InstructionFormat::B(inst);
 
@@ -837,20 +869,20 @@ fn BNE_test() {
 	let oldPc = cpu.pc;
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 
@@ -859,7 +891,7 @@ fn BNE_test() {
 	cpu.regs[1] = 1;
 	inst.imm = -0x100;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -868,7 +900,7 @@ fn BNE_test() {
 
 	inst.imm = -0xe00;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -886,7 +918,7 @@ fn BLTU_test() {
 		rs2: 2,
 		
 		instName: Instruction::BLTU,
-		imm: 1,
+		imm: 4,
 	};
 	let mut instFmt = InstructionFormat::B(inst);
 
@@ -894,29 +926,29 @@ fn BLTU_test() {
 	let oldPc = cpu.pc;
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 
 	cpu.regs[1] = 3;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 	cpu.pc = 0x1000;
@@ -924,7 +956,7 @@ fn BLTU_test() {
 	cpu.regs[1] = 1;
 	inst.imm = -0x100;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -933,7 +965,7 @@ fn BLTU_test() {
 
 	inst.imm = -0xe00;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -951,7 +983,7 @@ fn BLT_test() {
 		rs2: 2,
 		
 		instName: Instruction::BLT,
-		imm: 1,
+		imm: 4,
 	};
 	let mut instFmt = InstructionFormat::B(inst);
 
@@ -959,37 +991,37 @@ fn BLT_test() {
 	let oldPc = cpu.pc;
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 
 	cpu.regs[1] = 3;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 4);
+	assert_eq!(cpu.pc, 16);
 	let oldPc = cpu.pc;
 
 	cpu.pc = 0x1000;
@@ -997,7 +1029,7 @@ fn BLT_test() {
 	cpu.regs[1] = 1;
 	inst.imm = -0x100;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -1006,7 +1038,7 @@ fn BLT_test() {
 
 	inst.imm = -0xe00;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -1023,7 +1055,7 @@ fn BGEU_test() {
 		rs2: 2,
 		
 		instName: Instruction::BGEU,
-		imm: 1,
+		imm: 4,
 	};
 	let mut instFmt = InstructionFormat::B(inst);
 
@@ -1031,7 +1063,7 @@ fn BGEU_test() {
 	let oldPc = cpu.pc;
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
@@ -1040,28 +1072,28 @@ fn BGEU_test() {
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 
 	cpu.regs[1] = 3;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 4);
+	assert_eq!(cpu.pc, 16);
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 6);
+	assert_eq!(cpu.pc, 24);
 	let oldPc = cpu.pc;
 
 	cpu.pc = 0x1000;
@@ -1069,7 +1101,7 @@ fn BGEU_test() {
 	cpu.regs[1] = 3;
 	inst.imm = -0x100;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -1078,7 +1110,7 @@ fn BGEU_test() {
 
 	inst.imm = -0xe00;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -1095,7 +1127,7 @@ fn BGE_test() {
 		rs1: 1,
 		rs2: 2,
 		instName: Instruction::BGE,
-		imm: 1,
+		imm: 4,
 	};
 	let mut instFmt = InstructionFormat::B(inst);
 
@@ -1103,7 +1135,7 @@ fn BGE_test() {
 	let oldPc = cpu.pc;
 	cpu.regs[1] = 1;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
@@ -1112,28 +1144,28 @@ fn BGE_test() {
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 2);
+	assert_eq!(cpu.pc, 8);
 	let oldPc = cpu.pc;
 
 
 	cpu.regs[1] = 3;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 4);
+	assert_eq!(cpu.pc, 16);
 	let oldPc = cpu.pc;
 
 	cpu.regs[1] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
-	assert_eq!(cpu.pc, 4);
+	assert_eq!(cpu.pc, 16);
 	let oldPc = cpu.pc;
 
 	cpu.pc = 0x1000;
@@ -1141,7 +1173,7 @@ fn BGE_test() {
 	cpu.regs[1] = 3;
 	inst.imm = -0x100;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -1150,7 +1182,7 @@ fn BGE_test() {
 
 	inst.imm = -0xe00;
 	instFmt = InstructionFormat::B(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	if cpu.pc != oldPc {
 		cpu.pc = cpu.pc.wrapping_add(4);
 	}
@@ -1171,7 +1203,7 @@ fn JAL_test() {
 
 	cpu.pc = 10;
 	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.pc.wrapping_add(4), 12);
 	assert_eq!(cpu.regs[1], 14);
 
@@ -1179,7 +1211,7 @@ fn JAL_test() {
 	cpu.regs[1] = 0;
 	inst.imm = -1;
 	instFmt = InstructionFormat::J(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.pc.wrapping_add(4), 8);
 	assert_eq!(cpu.regs[1], 14);
@@ -1187,7 +1219,7 @@ fn JAL_test() {
 	cpu.pc = 0;
 	inst.imm = -100;
 	instFmt = InstructionFormat::J(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.pc.wrapping_add(4), u64::MAX-199);
 	assert_eq!(cpu.regs[1], 4);
@@ -1198,6 +1230,7 @@ fn JAL_test() {
 fn JALR_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
+	cpu.pc = 0;
 	let mut inst = RegImmInst {
 		instName: Instruction::JALR,
 		imm: 2,
@@ -1208,7 +1241,7 @@ fn JALR_test() {
 
 	cpu.regs[2] = 10;
 	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.pc.wrapping_add(4), 12);
 	assert_eq!(cpu.regs[1], 4);
 
@@ -1216,7 +1249,7 @@ fn JALR_test() {
 	cpu.regs[1] = 0;
 	inst.imm = -1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.pc.wrapping_add(4), 8);
 	assert_eq!(cpu.regs[1], 12);
@@ -1224,7 +1257,7 @@ fn JALR_test() {
 	cpu.regs[2] = 0;
 	inst.imm = -100;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.pc.wrapping_add(4), u64::MAX-99);
 	assert_eq!(cpu.regs[1], 8);
@@ -1247,22 +1280,22 @@ fn LUI_test() {
 	let mut instFmt = InstructionFormat::U(inst);
 
 	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 4096);
 
 	inst.imm = 0x10;
 	instFmt = InstructionFormat::U(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 65536);
 
 	cpu.regs[1] = 0xff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 65536);
 
 
 	inst.imm = 0x80000;
 	instFmt = InstructionFormat::U(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xffffffff80000000);
 }
 
@@ -1271,6 +1304,7 @@ fn LUI_test() {
 fn AUIPC_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
+	cpu.pc = 0;
 	let mut inst = UpperImmInst {
 		instName: Instruction::AUIPC,
 		imm: 1,
@@ -1279,40 +1313,40 @@ fn AUIPC_test() {
 	let mut instFmt = InstructionFormat::U(inst);
 
 	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 4096);
 
 	inst.imm = 0x10;
 	instFmt = InstructionFormat::U(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 65536);
 
 	cpu.regs[1] = 0xff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 65536);
 
 
 	inst.imm = 0x80000;
 	instFmt = InstructionFormat::U(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0xffffffff80000000);
 
 	cpu.pc = 0x4;
 	inst.imm = 1;
 	instFmt = InstructionFormat::U(inst);
 	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 4100);
 
 	cpu.pc = 100;
 	inst.imm = 0x10;
 	instFmt = InstructionFormat::U(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 65636);
 
 	cpu.pc= 200;
 	cpu.regs[1] = 0xff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 65736);
 
 
@@ -1320,7 +1354,7 @@ fn AUIPC_test() {
 	inst.imm = 0x80000;
 	instFmt = InstructionFormat::U(inst);
 	cpu.pc = 0x100000000;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x80000000);
 	
 }
@@ -1337,38 +1371,38 @@ fn LBU_test() {
 		rd: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[2] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[2] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[2] = 3;
+	cpu.bus.store(DRAM_BASE + 3, 0x4142434445464748, 8).unwrap();
+	cpu.regs[2] = DRAM_BASE + 3;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x48);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x47);
 
 	inst.imm = -1;
-	cpu.bus.store(2,0x40,1);
+	cpu.bus.store(DRAM_BASE + 2,0x40,1).unwrap();
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x40);
 	
-	cpu.bus.store(0,0xff, 1);
+	cpu.bus.store(DRAM_BASE,0xff, 1).unwrap();
 	inst.imm = 0;
 	instFmt = InstructionFormat::I(inst);
-	cpu.regs[2] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[2] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1], 0xff);
 }
@@ -1385,38 +1419,38 @@ fn LB_test() {
 		rd: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[2] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[2] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[2] = 3;
+	cpu.bus.store(DRAM_BASE + 3, 0x4142434445464748, 8).unwrap();
+	cpu.regs[2] = DRAM_BASE + 3;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x48);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x47);
 
 	inst.imm = -1;
-	cpu.bus.store(2,0x40,1);
+	cpu.bus.store(DRAM_BASE + 2,0x40,1).unwrap();
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x40);
 	
-	cpu.bus.store(0,0xff, 1);
+	cpu.bus.store(DRAM_BASE,0xff, 1).unwrap();
 	inst.imm = 0;
 	instFmt = InstructionFormat::I(inst);
-	cpu.regs[2] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[2] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1] as i64, -1);
 }
@@ -1433,38 +1467,38 @@ fn LHU_test() {
 		rd: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[2] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[2] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[2] = 3;
+	cpu.bus.store(DRAM_BASE + 3, 0x4142434445464748, 8).unwrap();
+	cpu.regs[2] = DRAM_BASE + 3;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4748);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4647);
 
 	inst.imm = -1;
-	cpu.bus.store(2,0x40,1);
+	cpu.bus.store(DRAM_BASE + 2,0x40,1).unwrap();
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4840);
 
-	cpu.bus.store(0,0xffff,2);
+	cpu.bus.store(DRAM_BASE,0xffff,2).unwrap();
 	inst.imm = 0;
 	instFmt = InstructionFormat::I(inst);
-	cpu.regs[2] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[2] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1], 0xffff);
 }
@@ -1481,38 +1515,38 @@ fn LH_test() {
 		rd: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[2] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[2] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[2] = 3;
+	cpu.bus.store(DRAM_BASE + 3, 0x4142434445464748, 8).unwrap();
+	cpu.regs[2] = DRAM_BASE + 3;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4748);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4647);
 
 	inst.imm = -1;
-	cpu.bus.store(2,0x40,1);
+	cpu.bus.store(DRAM_BASE + 2,0x40,1).unwrap();
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4840);
 
-	cpu.bus.store(0,0xffff,2);
+	cpu.bus.store(DRAM_BASE,0xffff,2).unwrap();
 	inst.imm = 0;
 	instFmt = InstructionFormat::I(inst);
-	cpu.regs[2] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[2] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1] as i64, -1);
 }
@@ -1528,39 +1562,39 @@ fn LWU_test() {
 		rd: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[2] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[2] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[2] = 3;
+	cpu.bus.store(DRAM_BASE + 3, 0x4142434445464748, 8).unwrap();
+	cpu.regs[2] = DRAM_BASE + 3;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x45464748);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x44454647);
 
 	inst.imm = -1;
-	cpu.bus.store(2,0x40,1);
+	cpu.bus.store(DRAM_BASE + 2,0x40,1).unwrap();
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x46474840);
 	
 
-	cpu.bus.store(0,0xffffffff,4);
+	cpu.bus.store(DRAM_BASE,0xffffffff,4).unwrap();
 	inst.imm = 0;
 	instFmt = InstructionFormat::I(inst);
-	cpu.regs[2] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[2] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1], 0xffffffff);
 }
@@ -1577,39 +1611,39 @@ fn LW_test() {
 		rd: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[2] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[2] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[2] = 3;
+	cpu.bus.store(DRAM_BASE + 3, 0x4142434445464748, 8).unwrap();
+	cpu.regs[2] = DRAM_BASE + 3;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x45464748);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x44454647);
 
 	inst.imm = -1;
-	cpu.bus.store(2,0x40,1);
+	cpu.bus.store(DRAM_BASE + 2,0x40,1).unwrap();
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x46474840);
 	
 
-	cpu.bus.store(0,0xffffffff,4);
+	cpu.bus.store(DRAM_BASE,0xffffffff,4).unwrap();
 	inst.imm = 0;
 	instFmt = InstructionFormat::I(inst);
-	cpu.regs[2] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[2] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1] as i64, -1);
 }
@@ -1626,39 +1660,39 @@ fn LD_test() {
 		rd: 1,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[2] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[2] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[2] = 3;
+	cpu.bus.store(DRAM_BASE + 3, 0x4142434445464748, 8).unwrap();
+	cpu.regs[2] = DRAM_BASE + 3;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4142434445464748);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x0041424344454647);
 
 	inst.imm = -1;
-	cpu.bus.store(2,0x40,1);
+	cpu.bus.store(DRAM_BASE + 2,0x40,1).unwrap();
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[1], 0x4243444546474840);
 	
 
-	cpu.bus.store(0,0xffffffffffffffff,8);
+	cpu.bus.store(DRAM_BASE,0xffffffffffffffff,8).unwrap();
 	inst.imm = 0;
 	instFmt = InstructionFormat::I(inst);
-	cpu.regs[2] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[2] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[1] as i64, -1);
 }
@@ -1677,28 +1711,28 @@ fn SB_test() {
 	};
 	let mut instFmt  = InstructionFormat::S(inst);
 	cpu.regs[2] = 0x10;
-	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[1] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 	
-	assert_eq!(cpu.bus.load(0,1),0x10);
+	assert_eq!(cpu.bus.load(DRAM_BASE,1).unwrap(),0x10);
 
 	cpu.regs[2] = 0x2010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,1), 0x10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,1).unwrap(), 0x10);
 
-	cpu.regs[1] = 0x4;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(4,1), 0x10);
+	cpu.regs[1] = DRAM_BASE + 0x4;
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 4,1).unwrap(), 0x10);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::S(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(5,1), 0x10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 5,1).unwrap(), 0x10);
 
 	inst.imm = -1;
 	instFmt = InstructionFormat::S(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(3,1), 0x10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 3,1).unwrap(), 0x10);
 }
 
 
@@ -1714,28 +1748,28 @@ fn SH_test() {
 	};
 	let mut instFmt  = InstructionFormat::S(inst);
 	cpu.regs[2] = 0x10;
-	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[1] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 	
-	assert_eq!(cpu.bus.load(0,2),0x10);
+	assert_eq!(cpu.bus.load(DRAM_BASE,2).unwrap(),0x10);
 
 	cpu.regs[2] = 0x2010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,2), 0x2010);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,2).unwrap(), 0x2010);
 
-	cpu.regs[1] = 0x4;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(4,2), 0x2010);
+	cpu.regs[1] = DRAM_BASE + 0x4;
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 4,2).unwrap(), 0x2010);
 
 	inst.imm = 1;
 	instFmt = InstructionFormat::S(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(5,2), 0x2010);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 5,2).unwrap(), 0x2010);
 
 	inst.imm = -1;
 	instFmt = InstructionFormat::S(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(3,2), 0x2010);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 3,2).unwrap(), 0x2010);
 }
 
 #[test]
@@ -1750,30 +1784,30 @@ fn SW_test() {
 	};
 	let mut instFmt  = InstructionFormat::S(inst);
 	cpu.regs[2] = 0x10;
-	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.regs[1] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 	
-	assert_eq!(cpu.bus.load(0,4),0x10);
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(),0x10);
 
 	cpu.regs[2] = 0x2010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,4), 0x2010);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(), 0x2010);
 
-	cpu.regs[1] = 0x4;
+	cpu.regs[1] = DRAM_BASE + 0x4;
 	cpu.regs[2] = 0x302010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(4,4), 0x302010);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 4,4).unwrap(), 0x302010);
 
 	inst.imm = 1;
 	cpu.regs[2] = 0x40302010;
 	instFmt = InstructionFormat::S(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(5,4), 0x40302010);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 5,4).unwrap(), 0x40302010);
 
 	inst.imm = -1;
 	instFmt = InstructionFormat::S(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(3,4), 0x40302010);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 3,4).unwrap(), 0x40302010);
 }
 
 #[test]
@@ -1781,6 +1815,7 @@ fn MUL_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::MUL,
 		rd: 0,
 		rs1: 1,
@@ -1790,25 +1825,25 @@ fn MUL_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 156);
 
 	//-1 * -1 == 1
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -0x10);
 
 
 	cpu.regs[1] = 0x7fffffffffffffff;
 	cpu.regs[2] = 0x7fffffffffffffff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 }
 
@@ -1817,6 +1852,7 @@ fn MULW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::MULW,
 		rd: 0,
 		rs1: 1,
@@ -1826,31 +1862,31 @@ fn MULW_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 156);
 
 	//-1 * -1 == 1
 	cpu.regs[1] = u32::MAX as u64;
 	cpu.regs[2] = u32::MAX as u64;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
 //	panic!("ayy");
 	cpu.regs[1] = u32::MAX as u64;
 	cpu.regs[2] = 10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -10);
 
 
 	cpu.regs[1] = 0x7fffffff;
 	cpu.regs[2] = 0x7fffffff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 	cpu.regs[1] = 0xaaaaaaaa7fffffff;
 	cpu.regs[2] = 0xaaaaaaaa7fffffff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 }
 
@@ -1860,6 +1896,7 @@ fn MULH_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::MULH,
 		rd: 0,
 		rs1: 1,
@@ -1869,25 +1906,25 @@ fn MULH_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	//-1 * -1 == 1
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -0x1_i64);
 
 
 	cpu.regs[1] = 0x7fffffffffffffff;
 	cpu.regs[2] = 0x7fffffffffffffff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0x3fffffffffffffff);
 }
 
@@ -1897,6 +1934,7 @@ fn MULHU_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::MULHU,
 		rd: 0,
 		rs1: 1,
@@ -1906,25 +1944,25 @@ fn MULHU_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], u64::MAX-1);
 
 
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0xf);
 
 
 	cpu.regs[1] = 0x7fffffffffffffff;
 	cpu.regs[2] = 0x7fffffffffffffff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0x3fffffffffffffff);
 }
 
@@ -1934,6 +1972,7 @@ fn MULHSU_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::MULHSU,
 		rd: 0,
 		rs1: 1,
@@ -1943,24 +1982,24 @@ fn MULHSU_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -1);
 
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = 10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -1);
 
 
 	cpu.regs[1] = 0x7fffffffffffffff;
 	cpu.regs[2] = 0x7fffffffffffffff;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, 0x3fffffffffffffff);
 }
 
@@ -1970,6 +2009,7 @@ fn DIV_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::DIV,
 		rd: 0,
 		rs1: 1,
@@ -1979,24 +2019,24 @@ fn DIV_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 2);
 
 }
@@ -2008,6 +2048,7 @@ fn DIVU_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::DIVU,
 		rd: 0,
 		rs1: 1,
@@ -2017,24 +2058,24 @@ fn DIVU_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0xfffffffffffffff);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 2);
 
 
@@ -2047,6 +2088,7 @@ fn DIVW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::DIVW,
 		rd: 0,
 		rs1: 1,
@@ -2056,29 +2098,29 @@ fn DIVW_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	
 	cpu.regs[1] = 0x100000000;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 2);
 
 	cpu.regs[1] = 1;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -1);
 }
 
@@ -2087,6 +2129,7 @@ fn DIVUW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::DIVUW,
 		rd: 0,
 		rs1: 1,
@@ -2096,30 +2139,30 @@ fn DIVUW_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
 	cpu.regs[1] = 0x100000000;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 2);
 
 
 	cpu.regs[1] = 1;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 }
 
@@ -2130,6 +2173,7 @@ fn REM_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::REM,
 		rd: 0,
 		rs1: 1,
@@ -2139,36 +2183,36 @@ fn REM_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = 0x100000000;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = u64::MAX -1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -1);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -1);
 }
 
@@ -2179,6 +2223,7 @@ fn REMU_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::REMU,
 		rd: 0,
 		rs1: 1,
@@ -2188,36 +2233,36 @@ fn REMU_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = 0x100000000;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = u64::MAX -1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], u64::MAX -6);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 }
 
@@ -2228,6 +2273,7 @@ fn REMW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::REMW,
 		rd: 0,
 		rs1: 1,
@@ -2237,36 +2283,36 @@ fn REMW_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = 0x100000001;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = u64::MAX -1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -1);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as i64, -1);
 }
 
@@ -2276,6 +2322,7 @@ fn REMUW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::REMUW,
 		rd: 0,
 		rs1: 1,
@@ -2285,36 +2332,36 @@ fn REMUW_test() {
 
 	cpu.regs[1] = 12;
 	cpu.regs[2] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	
 	cpu.regs[1] = u64::MAX;
 	cpu.regs[2] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = 0x100000001;
 	cpu.regs[2] = 0x10;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 	cpu.regs[1] = 24;
 	cpu.regs[2] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = u64::MAX -1;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0] as u32, u32::MAX -6);
 
 
 	cpu.regs[1] = u64::MAX -6;
 	cpu.regs[2] = 2;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 }
 
@@ -2329,22 +2376,22 @@ fn FLW_test() {
 		imm: 0,
 	};
 	let instFmt = InstructionFormat::I(inst);
-	cpu.bus.store(0,(1.0f32).to_bits() as u64,4);
-	cpu.regs[1] = 0;
+	cpu.bus.store(DRAM_BASE,(1.0f32).to_bits() as u64,4).unwrap();
+	cpu.regs[1] = DRAM_BASE;
 
-	cpu.execute(instFmt);
-	assert_eq!(cpu.fregs[0], 1.0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.0);
 	
-	cpu.bus.store(4,(1.23456f32).to_bits() as u64,4);
-	cpu.regs[1] = 4;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.fregs[0], 1.23456);
+	cpu.bus.store(DRAM_BASE + 4,(1.23456f32).to_bits() as u64,4).unwrap();
+	cpu.regs[1] = DRAM_BASE + 4;
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.23456);
 
-	cpu.bus.store(8, (-1.234 * 10f32).to_bits() as u64,4);
+	cpu.bus.store(DRAM_BASE + 8, (-1.234 * 10f32).to_bits() as u64,4).unwrap();
 	inst.imm = 4;
 	let instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.fregs[0], -1.234*10f64);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -1.234*10f32);
 }
 
 
@@ -2355,26 +2402,26 @@ fn FSW_test() {
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = StoreInst {
 		instName: Instruction::FSW,
-		rs1: 0,
+		rs1: 2,
 		rs2: 1,
 		imm: 0,
 	};
 	let instFmt = InstructionFormat::S(inst);
-	cpu.fregs[1] = 1.0;
+	cpu.regs[2] = DRAM_BASE;
+	cpu.fregs[1] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
-	println!("{:b}", cpu.bus.load(0,4));
-	assert_eq!(f32::from_bits(cpu.bus.load(0,4) as u32), 1.0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(f32::from_bits(cpu.bus.load(DRAM_BASE,4).unwrap() as u32), 1.0);
 	
-	cpu.fregs[1] = 1.23456;
-	cpu.execute(instFmt);
-	assert_eq!(f32::from_bits(cpu.bus.load(0,4) as u32), 1.23456);
+	cpu.fregs[1] = Cpu::box_float(1.23456);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(f32::from_bits(cpu.bus.load(DRAM_BASE,4).unwrap() as u32), 1.23456);
 
 	inst.imm = 4;
 	let instFmt = InstructionFormat::S(inst);
-	cpu.fregs[1] = -1.234*10f64;
-	cpu.execute(instFmt);
-	assert_eq!(f32::from_bits(cpu.bus.load(4,4) as u32), -1.234*10f32);
+	cpu.fregs[1] = Cpu::box_float(-1.234*10f32);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(f32::from_bits(cpu.bus.load(DRAM_BASE + 4,4).unwrap() as u32), -1.234*10f32);
 }
 
 #[test]
@@ -2382,6 +2429,7 @@ fn FADDS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::FADDS,
 		rd: 0,
 		rs1: 1,
@@ -2391,20 +2439,20 @@ fn FADDS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 2.0);
 	
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 0.0);
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	let a  = 1.2465*(f32::powi(10.0,18));
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), a);
 
@@ -2416,6 +2464,7 @@ fn FSUBS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::FSUBS,
 		rd: 0,
 		rs1: 1,
@@ -2425,22 +2474,22 @@ fn FSUBS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 0.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 2.0);
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
-	let a  = 1.2225*(f32::powi(10.0,18)); //f32 are so inaccurate, must assign to a variable before asserting
-											//to avoid a failed asertion because the literal doesnt round but the var does
+	cpu.execute(instFmt).unwrap();
+	//f32 are so inaccurate, the expected value has to go through the same rounding
+	let a  = 1.2345*(f32::powi(10.0,18)) - 1.2*(f32::powi(10.0,16));
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), a);
 
 }
@@ -2451,6 +2500,7 @@ fn FMULS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::FMULS,
 		rd: 0,
 		rs1: 1,
@@ -2460,29 +2510,29 @@ fn FMULS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -1.0);
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
-	let a =  1.4814*(f32::powi(10.0,34));
+	cpu.execute(instFmt).unwrap();
+	let a =  1.2345*(f32::powi(10.0,18)) * (1.2*(f32::powi(10.0,16)));
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), a);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.0*(f32::powi(10.0,-2)));
 
-	cpu.execute(instFmt);
-	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.2345*(f32::powi(10.0,16)));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.2345*(f32::powi(10.0,18)) * f32::powi(10.0,-2));
 }
 
 #[test]
@@ -2490,6 +2540,7 @@ fn FDIVS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::FDIVS,
 		rd: 0,
 		rs1: 1,
@@ -2499,28 +2550,28 @@ fn FDIVS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -1.0);
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
-	let a : f32 = 102.875;
+	cpu.execute(instFmt).unwrap();
+	let a : f32 = 1.2345*(f32::powi(10.0,18)) / (1.2*(f32::powi(10.0,16)));
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), a);
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.0*(f32::powi(10.0,-2)));
 
-	cpu.execute(instFmt);
-	let a : f32 = 1.2345*(f32::powi(10.0,20));
+	cpu.execute(instFmt).unwrap();
+	let a : f32 = 1.2345*(f32::powi(10.0,18)) / f32::powi(10.0,-2);
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), a);
 }
 
@@ -2529,6 +2580,7 @@ fn FMAXS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::FMAXS,
 		rd: 0,
 		rs1: 1,
@@ -2538,26 +2590,26 @@ fn FMAXS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.0);
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.2345*(f32::powi(10.0,18)));
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,-18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.2*(f32::powi(10.0,16)));
 }
 
@@ -2566,6 +2618,7 @@ fn FMINS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::FMINS,
 		rd: 0,
 		rs1: 1,
@@ -2575,26 +2628,26 @@ fn FMINS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -1.0);
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), Cpu::unbox_float(cpu.fregs[2]));
 
 	cpu.fregs[1] = Cpu::box_float(1.2345*(f32::powi(10.0,-18)));
 	cpu.fregs[2] = Cpu::box_float(1.2*(f32::powi(10.0,16)));
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), Cpu::unbox_float(cpu.fregs[1]));
 }
 
@@ -2603,6 +2656,7 @@ fn FSQRTS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::FSQRTS,
 		rd: 0,
 		rs1: 1,
@@ -2611,17 +2665,17 @@ fn FSQRTS_test() {
 	let instFmt = InstructionFormat::R(inst);
 	cpu.fregs[1] = Cpu::box_float(1.0);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(2.25);
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 1.5);
 
 	cpu.fregs[1] = Cpu::box_float(1522756000000000000000000.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	let a  = Cpu::box_float(1234.0*(f32::powi(10.0, 9)));
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), Cpu::unbox_float(a));
 }
@@ -2633,6 +2687,7 @@ fn FCVTSLU_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2641,15 +2696,15 @@ fn FCVTSLU_test() {
 	let instFmt = InstructionFormat::R(inst);
 
 	cpu.regs[1] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 12.0);
 
 	cpu.regs[1] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 13.0);
 
 	cpu.regs[1] = 14;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 14.0);
 
 }
@@ -2659,6 +2714,7 @@ fn FCVTSL_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2667,15 +2723,15 @@ fn FCVTSL_test() {
 	let instFmt = InstructionFormat::R(inst);
 
 	cpu.regs[1] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 12.0);
 
 	cpu.regs[1] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 13.0);
 
 	cpu.regs[1] = u64::MAX;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -1.0);
 
 }
@@ -2685,6 +2741,7 @@ fn FCVTSWU_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2693,15 +2750,15 @@ fn FCVTSWU_test() {
 	let instFmt = InstructionFormat::R(inst);
 
 	cpu.regs[1] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 12.0);
 
 	cpu.regs[1] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 13.0);
 
 	cpu.regs[1] = u32::MAX as u64;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	let a = Cpu::box_float(4294967295.0);
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), Cpu::unbox_float(a));
 
@@ -2712,6 +2769,7 @@ fn FCVTSW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2720,15 +2778,15 @@ fn FCVTSW_test() {
 	let instFmt = InstructionFormat::R(inst);
 
 	cpu.regs[1] = 12;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 12.0);
 
 	cpu.regs[1] = 13;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 13.0);
 
 	cpu.regs[1] = u32::MAX as u64;
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -1.0);
 }
 
@@ -2737,6 +2795,7 @@ fn FCVTLUS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 1,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2745,32 +2804,32 @@ fn FCVTLUS_test() {
 	let instFmt = InstructionFormat::R(inst);
 
 	cpu.fregs[1] = 	Cpu::box_float(12.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	cpu.fregs[1] = 	Cpu::box_float(13.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 
 	cpu.fregs[1] = 	Cpu::box_float(12.5);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 	
 	//technically incorrect accorind to riscv specification,
 	//rust seems to consistenyl round down upon even
 	//fix this test when rounding-mode function  is implemented
 	cpu.fregs[1] = 	Cpu::box_float(13.5);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 
 
 	cpu.fregs[1] = 	Cpu::box_float(13.4);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 	
 	
 	cpu.fregs[1] =	Cpu::box_float(18446744073709551616.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0],u64::MAX);
 }
 
@@ -2779,6 +2838,7 @@ fn FCVTLS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2788,29 +2848,40 @@ fn FCVTLS_test() {
 
 
 	cpu.fregs[1] = 	Cpu::box_float(12.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	cpu.fregs[1] = 	Cpu::box_float(13.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 
+	//ties go to the even one
 	cpu.fregs[1] = 	Cpu::box_float(13.5);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.regs[0], 13);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], 14);
 
 	//agony
 	cpu.fregs[1] = 	Cpu::box_float(13.9);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 14);
+
+	//rtz just chops the fraction off
+	inst.rm = 1;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[0], 13);
+	cpu.fregs[1] = 	Cpu::box_float(13.5);
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[0], 13);
 	
+	//closest single to this is 2^32
 	cpu.fregs[1] = 	Cpu::box_float(4294967295.0);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.regs[0], 4294967295);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], 4294967296);
 
+	//too big, saturates
 	cpu.fregs[1] = 	Cpu::box_float(18446744073709551615.0);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.regs[0] as i64, -1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], i64::MAX as u64);
 
 }
 
@@ -2819,6 +2890,7 @@ fn FCVTWUS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 1,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2828,25 +2900,35 @@ fn FCVTWUS_test() {
 
 	
 	cpu.fregs[1] = Cpu::box_float(12.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	cpu.fregs[1] =Cpu::box_float(13.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 
 	cpu.fregs[1] =Cpu::box_float(13.4);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 
 
 	cpu.fregs[1] =Cpu::box_float(13.6);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 
+	//4294967295 rounds up to 2^32 as a float, which is too big, so it saturates
+	//(and gets sign extended like every 32 bit result)
 	cpu.fregs[1] = Cpu::box_float(4294967295.0);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.regs[0], 4294967296);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], u64::MAX);
+
+	cpu.fregs[1] = Cpu::box_float(-1.0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], 0);
+
+	cpu.fregs[1] = Cpu::box_float(f32::NAN);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], u64::MAX);
 }
 
 #[test]
@@ -2854,6 +2936,7 @@ fn FCVTWS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 1,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2862,20 +2945,28 @@ fn FCVTWS_test() {
 	let instFmt = InstructionFormat::R(inst);
 
 	cpu.fregs[1] = Cpu::box_float(12.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 12);
 
 	cpu.fregs[1] = Cpu::box_float(13.5);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 13);
 
 	cpu.fregs[1] = Cpu::box_float(2147483647.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 2147483647);
 
 	cpu.fregs[1] = Cpu::box_float(4294967295.0);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.regs[0] as i32, -1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], i32::MAX as u64);
+
+	cpu.fregs[1] = Cpu::box_float(-4294967295.0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], i32::MIN as i64 as u64);
+
+	cpu.fregs[1] = Cpu::box_float(f32::NAN);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[0], i32::MAX as u64);
 
 }
 
@@ -2884,6 +2975,7 @@ fn FEQS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2891,15 +2983,15 @@ fn FEQS_test() {
 	};
 	let instFmt = InstructionFormat::R(inst);
 	
-	cpu.fregs[1] = 1.0;
-	cpu.fregs[2] = 1.0;
-	cpu.execute(instFmt);
+	cpu.fregs[1] = Cpu::box_float(1.0);
+	cpu.fregs[2] = Cpu::box_float(1.0);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
-	cpu.fregs[1] = 1.0;
-	cpu.fregs[2] = 1.2;
-	cpu.execute(instFmt);
+	cpu.fregs[1] = Cpu::box_float(1.0);
+	cpu.fregs[2] = Cpu::box_float(1.2);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 	
 }
@@ -2909,6 +3001,7 @@ fn FLTS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2918,18 +3011,18 @@ fn FLTS_test() {
 	
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.2);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 	
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.2);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 }
 
@@ -2938,6 +3031,7 @@ fn FLES_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2948,19 +3042,19 @@ fn FLES_test() {
 	
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.0);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(1.2);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 1);
 
 
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-1.2);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[0], 0);
 	
 }
@@ -2970,6 +3064,7 @@ fn FSGNJS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -2979,11 +3074,11 @@ fn FSGNJS_test() {
 
 	cpu.fregs[1] = Cpu::box_float(f32::from_bits(0b1));
 	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x80000000));
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b10000000000000000000000000000001));
 
 	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x80000000-1));
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b00000000000000000000000000000001));
 
 	
@@ -2993,6 +3088,7 @@ fn FSGNJNS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -3002,11 +3098,11 @@ fn FSGNJNS_test() {
 
 	cpu.fregs[1] = Cpu::box_float(f32::from_bits(0b1));
 	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x80000000));
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b00000000000000000000000000000001));
 
 	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x80000000 -1));
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b10000000000000000000000000000001));
 
 	
@@ -3017,6 +3113,7 @@ fn FSGNJXS_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -3026,17 +3123,17 @@ fn FSGNJXS_test() {
 
 	cpu.fregs[1] = Cpu::box_float(f32::from_bits(0b1));
 	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x80000000));
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b10000000000000000000000000000001));
 
 	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x80000000 -1));
-	cpu.execute(instFmt);
-	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b10000000000000000000000000000001));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b00000000000000000000000000000001));
 
 
 	cpu.fregs[1] = Cpu::box_float(f32::from_bits(0x80000000 +1));
 	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x80000000));
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::from_bits(0b00000000000000000000000000000001));
 	
 }
@@ -3047,6 +3144,7 @@ fn FMADDS_test() {
 	let fakeData : Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = R4Inst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -3058,27 +3156,27 @@ fn FMADDS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-2.0);
 	cpu.fregs[3] = Cpu::box_float(0.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -2.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(0.0);
 	cpu.fregs[2] = Cpu::box_float(0.0);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 3.0);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5);
 	cpu.fregs[2] = Cpu::box_float(-1.5);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 0.75);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5*f32::powi(10.0, 16));
 	cpu.fregs[2] = Cpu::box_float(-2.0*f32::powi(10.0, 16));
 	cpu.fregs[3] = Cpu::box_float(-0.5*f32::powi(10.0,16)); 
-	cpu.execute(instFmt);
-	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -3.5*f32::powi(10.0,16));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), (1.5*f32::powi(10.0, 16)).mul_add(-2.0*f32::powi(10.0, 16), -0.5*f32::powi(10.0,16)));
 }
 
 
@@ -3087,6 +3185,7 @@ fn FMSUBS_test() {
 	let fakeData : Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = R4Inst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -3098,27 +3197,27 @@ fn FMSUBS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-2.0);
 	cpu.fregs[3] = Cpu::box_float(0.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -2.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(0.0);
 	cpu.fregs[2] = Cpu::box_float(0.0);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -3.0);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5);
 	cpu.fregs[2] = Cpu::box_float(-1.5);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -5.25);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5*f32::powi(10.0, 16));
 	cpu.fregs[2] = Cpu::box_float(-2.0*f32::powi(10.0, 16));
 	cpu.fregs[3] = Cpu::box_float(-0.5*f32::powi(10.0,16)); 
-	cpu.execute(instFmt);
-	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -2.5*f32::powi(10.0,16));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), (1.5*f32::powi(10.0, 16)).mul_add(-2.0*f32::powi(10.0, 16), 0.5*f32::powi(10.0,16)));
 }
 
 #[test]
@@ -3126,6 +3225,7 @@ fn FNMADDS_test() {
 	let fakeData : Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = R4Inst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -3137,33 +3237,34 @@ fn FNMADDS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-2.0);
 	cpu.fregs[3] = Cpu::box_float(0.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 2.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(0.0);
 	cpu.fregs[2] = Cpu::box_float(0.0);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -3.0);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5);
 	cpu.fregs[2] = Cpu::box_float(-1.5);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -0.75);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5*f32::powi(10.0, 16));
 	cpu.fregs[2] = Cpu::box_float(-2.0*f32::powi(10.0, 16));
 	cpu.fregs[3] = Cpu::box_float(-0.5*f32::powi(10.0,16)); 
-	cpu.execute(instFmt);
-	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 3.5*f32::powi(10.0,16));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -(1.5*f32::powi(10.0, 16)).mul_add(-2.0*f32::powi(10.0, 16), -0.5*f32::powi(10.0,16)));
 }
 #[test]
 fn FNMSUBS_test() {
 	let fakeData : Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = R4Inst {
+		rm: 0,
 		rd: 0,
 		rs1: 1,
 		rs2: 2,
@@ -3175,27 +3276,712 @@ fn FNMSUBS_test() {
 	cpu.fregs[1] = Cpu::box_float(1.0);
 	cpu.fregs[2] = Cpu::box_float(-2.0);
 	cpu.fregs[3] = Cpu::box_float(0.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 2.0);
 
 
 	cpu.fregs[1] = Cpu::box_float(0.0);
 	cpu.fregs[2] = Cpu::box_float(0.0);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 3.0);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5);
 	cpu.fregs[2] = Cpu::box_float(-1.5);
 	cpu.fregs[3] = Cpu::box_float(3.0); 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 5.25);
 	
 	cpu.fregs[1] = Cpu::box_float(1.5*f32::powi(10.0, 16));
 	cpu.fregs[2] = Cpu::box_float(-2.0*f32::powi(10.0, 16));
 	cpu.fregs[3] = Cpu::box_float(-0.5*f32::powi(10.0,16)); 
-	cpu.execute(instFmt);
-	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 2.5*f32::powi(10.0,16));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), -(1.5*f32::powi(10.0, 16)).mul_add(-2.0*f32::powi(10.0, 16), 0.5*f32::powi(10.0,16)));
+}
+
+#[test]
+fn FCLASSS_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let inst = RegRegInst {
+		rm: 0,
+		rd: 1,
+		rs1: 1,
+		rs2: 0,
+		instName: Instruction::FCLASSS,
+	};
+	let instFmt = InstructionFormat::R(inst);
+	let cases = [
+		(f32::NEG_INFINITY, 1 << 0),
+		(-1.0, 1 << 1),
+		(-f32::from_bits(1), 1 << 2),
+		(-0.0, 1 << 3),
+		(0.0, 1 << 4),
+		(f32::from_bits(1), 1 << 5),
+		(1.0, 1 << 6),
+		(f32::INFINITY, 1 << 7),
+		(f32::from_bits(0x7f80_0001), 1 << 8),
+		(f32::NAN, 1 << 9),
+	];
+	for (val, class) in cases {
+		cpu.fregs[1] = Cpu::box_float(val);
+		cpu.execute(instFmt).unwrap();
+		assert_eq!(cpu.regs[1], class);
+	}
+	//not NaN boxed, so its treated as the canonical NaN
+	cpu.fregs[1] = 1.0;
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[1], 1 << 9);
+}
+
+#[test]
+fn float_decode_test() {
+	let fakeData: Vec<u8> = vec![
+		0x07, 0x20, 0x05, 0x00, //flw ft0, 0(a0)
+		0x87, 0x20, 0x45, 0x00, //flw ft1, 4(a0)
+		0x53, 0x71, 0x10, 0x00, //fadd.s ft2, ft0, ft1
+		0xc3, 0x71, 0x10, 0x10, //fmadd.s ft3, ft0, ft1, ft2
+		0x27, 0x24, 0x35, 0x00, //fsw ft3, 8(a0)
+		0xd3, 0x95, 0x01, 0xe0, //fclass.s a1, ft3
+		0x53, 0x86, 0x01, 0xe0, //fmv.x.w a2, ft3
+		0xd3, 0x96, 0x01, 0xc0, //fcvt.w.s a3, ft3, rtz
+		0x53, 0x27, 0x21, 0xa0, //feq.s a4, ft2, ft2
+		0x53, 0xf2, 0x00, 0x58, //fsqrt.s ft4, ft1
+		0xd3, 0x02, 0x06, 0xf0, //fmv.w.x ft5, a2
+		0x07, 0x20, 0x05, 0x00, //flw ft0, 0(a0)
+	];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x200);
+	cpu.bus.store(DRAM_BASE + 0x100, 1.5f32.to_bits() as u64, 4).unwrap();
+	cpu.bus.store(DRAM_BASE + 0x104, 4.0f32.to_bits() as u64, 4).unwrap();
+	cpu.regs[10] = DRAM_BASE + 0x100;
+	for _ in 0..11 {
+		cpu.step();
+	}
+	assert_eq!(cpu.pc, DRAM_BASE + 44);
+	assert_eq!(Cpu::unbox_float(cpu.fregs[2]), 5.5);
+	assert_eq!(Cpu::unbox_float(cpu.fregs[3]), 11.5);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x108, 4).unwrap(), 11.5f32.to_bits() as u64);
+	assert_eq!(cpu.regs[11], 1 << 6);
+	assert_eq!(cpu.regs[12], 11.5f32.to_bits() as u64);
+	assert_eq!(cpu.regs[13], 11);
+	assert_eq!(cpu.regs[14], 1);
+	assert_eq!(Cpu::unbox_float(cpu.fregs[4]), 2.0);
+	assert_eq!(cpu.fregs[5].to_bits(), cpu.fregs[3].to_bits());
+	//running float instructions dirties the float state
+	assert_eq!(cpu.load_csr(MSTATUS) & MSTATUS_FS, MSTATUS_FS);
+	assert_eq!(cpu.load_csr(MSTATUS) & MSTATUS_SD, MSTATUS_SD);
+
+	//with the float unit off they (and the float csrs) are illegal
+	cpu.store_csr(MSTATUS, 0);
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x200);
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+	assert_eq!(cpu.load_csr(MTVAL), 0x00052007);
+	let csrr = RegImmInst {
+		instName: Instruction::CSRRS,
+		rd: 1,
+		rs1: 0,
+		imm: FCSR as i16,
+	};
+	assert!(cpu.execute(InstructionFormat::I(csrr)).is_err());
+}
+
+#[test]
+fn FLD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let inst = RegImmInst {
+		instName: Instruction::FLD,
+		rd: 0,
+		rs1: 1,
+		imm: 8,
+	};
+	cpu.bus.store(DRAM_BASE + 8, (-1.234e100f64).to_bits(), 8).unwrap();
+	cpu.regs[1] = DRAM_BASE;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], -1.234e100);
+}
+
+#[test]
+fn FSD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let inst = StoreInst {
+		instName: Instruction::FSD,
+		rs1: 2,
+		rs2: 1,
+		imm: -8,
+	};
+	cpu.regs[2] = DRAM_BASE + 16;
+	cpu.fregs[1] = 1.0e-300;
+	cpu.execute(InstructionFormat::S(inst)).unwrap();
+	assert_eq!(f64::from_bits(cpu.bus.load(DRAM_BASE + 8, 8).unwrap()), 1.0e-300);
+
+	//a boxed single goes out as is
+	cpu.fregs[1] = Cpu::box_float(1.0);
+	cpu.execute(InstructionFormat::S(inst)).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 8, 8).unwrap(), 0xffff_ffff_3f80_0000);
+}
+
+#[test]
+fn FADDD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rm: 0,
+		instName: Instruction::FADDD,
+		rd: 0,
+		rs1: 1,
+		rs2: 2,
+	};
+	cpu.fregs[1] = 0.1;
+	cpu.fregs[2] = 0.2;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 0.1 + 0.2);
+
+	inst.instName = Instruction::FSUBD;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 0.1 - 0.2);
+
+	inst.instName = Instruction::FMULD;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 0.1 * 0.2);
+
+	inst.instName = Instruction::FDIVD;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 0.1 / 0.2);
+
+	inst.instName = Instruction::FSQRTD;
+	cpu.fregs[1] = 2.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], std::f64::consts::SQRT_2);
+}
+
+#[test]
+fn FMADDD_test() {
+	let fakeData : Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = R4Inst {
+		rm: 0,
+		rd: 0,
+		rs1: 1,
+		rs2: 2,
+		rs3: 3,
+		instName: Instruction::FMADDD,
+	};
+	cpu.fregs[1] = 1.5;
+	cpu.fregs[2] = -1.5;
+	cpu.fregs[3] = 3.0;
+	cpu.execute(InstructionFormat::R4(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 0.75);
+
+	inst.instName = Instruction::FMSUBD;
+	cpu.execute(InstructionFormat::R4(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], -5.25);
+
+	inst.instName = Instruction::FNMADDD;
+	cpu.execute(InstructionFormat::R4(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], -0.75);
+
+	inst.instName = Instruction::FNMSUBD;
+	cpu.execute(InstructionFormat::R4(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 5.25);
+
+	//only one rounding: 0.1 * 10 - 1 isnt 0
+	inst.instName = Instruction::FMADDD;
+	cpu.fregs[1] = 0.1;
+	cpu.fregs[2] = 10.0;
+	cpu.fregs[3] = -1.0;
+	cpu.execute(InstructionFormat::R4(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 2.0f64.powi(-54));
+
+	//exact zeros come out positive
+	inst.instName = Instruction::FNMSUBD;
+	cpu.fregs[1] = 1.5;
+	cpu.fregs[2] = 2.0;
+	cpu.fregs[3] = 3.0;
+	cpu.execute(InstructionFormat::R4(inst)).unwrap();
+	assert_eq!(cpu.fregs[0].to_bits(), 0);
+}
+
+#[test]
+fn FSGNJD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rm: 0,
+		rd: 0,
+		rs1: 1,
+		rs2: 2,
+		instName: Instruction::FSGNJD,
+	};
+	cpu.fregs[1] = -2.5;
+	cpu.fregs[2] = 1.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 2.5);
+
+	inst.instName = Instruction::FSGNJND;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], -2.5);
+
+	inst.instName = Instruction::FSGNJXD;
+	cpu.fregs[2] = -1.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 2.5);
+}
+
+#[test]
+fn FMIND_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rm: 0,
+		rd: 0,
+		rs1: 1,
+		rs2: 2,
+		instName: Instruction::FMIND,
+	};
+	cpu.fregs[1] = -2.5;
+	cpu.fregs[2] = 1.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], -2.5);
+	inst.instName = Instruction::FMAXD;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 1.0);
+
+	//NaNs lose
+	cpu.fregs[1] = f64::NAN;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 1.0);
+	cpu.fregs[2] = f64::NAN;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert!(cpu.fregs[0].is_nan());
+
+	//-0 is smaller than +0
+	cpu.fregs[1] = -0.0;
+	cpu.fregs[2] = 0.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0].to_bits(), 0);
+	inst.instName = Instruction::FMIND;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0].to_bits(), 1 << 63);
+}
+
+#[test]
+fn FEQD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rm: 0,
+		rd: 5,
+		rs1: 1,
+		rs2: 2,
+		instName: Instruction::FEQD,
+	};
+	cpu.fregs[1] = 1.0;
+	cpu.fregs[2] = 1.0 + f64::EPSILON;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 0);
+	inst.instName = Instruction::FLTD;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 1);
+	inst.instName = Instruction::FLED;
+	cpu.fregs[2] = 1.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 1);
+	cpu.fregs[2] = f64::NAN;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 0);
+}
+
+#[test]
+fn FCVTSD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rm: 0,
+		rd: 0,
+		rs1: 1,
+		rs2: 1,
+		instName: Instruction::FCVTSD,
+	};
+	//singles come out boxed
+	cpu.fregs[1] = 0.1;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0].to_bits() >> 32, 0xffff_ffff);
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), 0.1f32);
+
+	//and have to be boxed going in
+	inst.instName = Instruction::FCVTDS;
+	inst.rs1 = 0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 0.1f32 as f64);
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert!(cpu.fregs[0].is_nan());
+}
+
+#[test]
+fn FCVTWD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rm: 1,
+		rd: 5,
+		rs1: 1,
+		rs2: 0,
+		instName: Instruction::FCVTWD,
+	};
+	cpu.fregs[1] = -3.75;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5] as i64, -3);
+	cpu.fregs[1] = 1.0e10;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], i32::MAX as u64);
+
+	inst.instName = Instruction::FCVTWUD;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], u64::MAX);
+	cpu.fregs[1] = -3.75;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 0);
+
+	inst.instName = Instruction::FCVTLD;
+	cpu.fregs[1] = 1.0e10;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 10_000_000_000);
+	cpu.fregs[1] = f64::NAN;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], i64::MAX as u64);
+
+	inst.instName = Instruction::FCVTLUD;
+	cpu.fregs[1] = 1.0e20;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], u64::MAX);
+
+	//and back the other way
+	inst = RegRegInst {
+		rm: 0,
+		rd: 2,
+		rs1: 5,
+		rs2: 0,
+		instName: Instruction::FCVTDW,
+	};
+	cpu.regs[5] = 0xffff_ffff;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[2], -1.0);
+	inst.instName = Instruction::FCVTDWU;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[2], 4294967295.0);
+	inst.instName = Instruction::FCVTDL;
+	cpu.regs[5] = (-7i64) as u64;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[2], -7.0);
+	inst.instName = Instruction::FCVTDLU;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[2], 18446744073709551609.0);
+}
+
+#[test]
+fn FCLASSD_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let inst = RegRegInst {
+		rm: 0,
+		rd: 1,
+		rs1: 1,
+		rs2: 0,
+		instName: Instruction::FCLASSD,
+	};
+	let cases = [
+		(f64::NEG_INFINITY, 1 << 0),
+		(-1.0, 1 << 1),
+		(-f64::from_bits(1), 1 << 2),
+		(-0.0, 1 << 3),
+		(0.0, 1 << 4),
+		(f64::from_bits(1), 1 << 5),
+		(1.0, 1 << 6),
+		(f64::INFINITY, 1 << 7),
+		(f64::from_bits(0x7ff0_0000_0000_0001), 1 << 8),
+		(f64::NAN, 1 << 9),
+		//a boxed single is just a NaN as far as doubles go
+		(Cpu::box_float(1.0), 1 << 9),
+	];
+	for (val, class) in cases {
+		cpu.fregs[1] = val;
+		cpu.execute(InstructionFormat::R(inst)).unwrap();
+		assert_eq!(cpu.regs[1], class);
+	}
+}
+
+#[test]
+fn double_decode_test() {
+	let fakeData: Vec<u8> = vec![
+		0x07, 0x30, 0x05, 0x00, //fld ft0, 0(a0)
+		0x87, 0x30, 0x85, 0x00, //fld ft1, 8(a0)
+		0x53, 0x71, 0x10, 0x12, //fmul.d ft2, ft0, ft1
+		0xcb, 0x71, 0x10, 0x12, //fnmsub.d ft3, ft0, ft1, ft2
+		0x27, 0x38, 0x25, 0x00, //fsd ft2, 16(a0)
+		0x53, 0x72, 0x11, 0x40, //fcvt.s.d ft4, ft2
+		0xd3, 0x02, 0x02, 0x42, //fcvt.d.s ft5, ft4
+		0xd3, 0x15, 0x21, 0xc2, //fcvt.l.d a1, ft2, rtz
+		0x53, 0x83, 0x15, 0xd2, //fcvt.d.wu ft6, a1
+		0x53, 0x16, 0x10, 0xa2, //flt.d a2, ft0, ft1
+		0xd3, 0x86, 0x00, 0xe2, //fmv.x.d a3, ft1
+		0x53, 0x97, 0x01, 0xe2, //fclass.d a4, ft3
+		0xd3, 0x93, 0x10, 0x22, //fneg.d ft7, ft1
+		0x22, 0x24, //c.fldsp fs0, 8(sp)
+	];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.bus.store(DRAM_BASE + 0x100, 1.5f64.to_bits(), 8).unwrap();
+	cpu.bus.store(DRAM_BASE + 0x108, 2.25f64.to_bits(), 8).unwrap();
+	cpu.regs[10] = DRAM_BASE + 0x100;
+	cpu.regs[2] = DRAM_BASE + 0x100;
+	for _ in 0..14 {
+		cpu.step();
+	}
+	assert_eq!(cpu.pc, DRAM_BASE + 54);
+	assert_eq!(cpu.fregs[2], 3.375);
+	assert_eq!(cpu.fregs[3].to_bits(), 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x110, 8).unwrap(), 3.375f64.to_bits());
+	assert_eq!(Cpu::unbox_float(cpu.fregs[4]), 3.375);
+	assert_eq!(cpu.fregs[5], 3.375);
+	assert_eq!(cpu.regs[11], 3);
+	assert_eq!(cpu.fregs[6], 3.0);
+	assert_eq!(cpu.regs[12], 1);
+	assert_eq!(cpu.regs[13], 2.25f64.to_bits());
+	assert_eq!(cpu.regs[14], 1 << 4);
+	assert_eq!(cpu.fregs[7], -2.25);
+	assert_eq!(cpu.fregs[8], 2.25);
+}
+
+#[test]
+fn float_rounding_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rd: 0,
+		rs1: 1,
+		rs2: 2,
+		rm: 0,
+		instName: Instruction::FADDS,
+	};
+	//exactly halfway between 1 and the next single up
+	let half = 2.0f32.powi(-24);
+	let next = 1.0 + 2.0f32.powi(-23);
+	let cases = [(0, 1.0, -1.0), (1, 1.0, -1.0), (2, 1.0, -next), (3, next, -1.0), (4, next, -next)];
+	for (rm, up, down) in cases {
+		inst.rm = rm;
+		cpu.fregs[1] = Cpu::box_float(1.0);
+		cpu.fregs[2] = Cpu::box_float(half);
+		cpu.execute(InstructionFormat::R(inst)).unwrap();
+		assert_eq!(Cpu::unbox_float(cpu.fregs[0]), up);
+		cpu.fregs[1] = Cpu::box_float(-1.0);
+		cpu.fregs[2] = Cpu::box_float(-half);
+		cpu.execute(InstructionFormat::R(inst)).unwrap();
+		assert_eq!(Cpu::unbox_float(cpu.fregs[0]), down);
+	}
+
+	//dynamic takes it from frm
+	inst.rm = 7;
+	cpu.fcsr = 3 << 5;
+	cpu.fregs[1] = Cpu::box_float(1.0);
+	cpu.fregs[2] = Cpu::box_float(half);
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), next);
+
+	//reserved modes are illegal, whether its in the instruction or frm
+	cpu.fcsr = 5 << 5;
+	assert!(matches!(cpu.execute(InstructionFormat::R(inst)), Err(Exception::IllegalInstruction(_))));
+	inst.rm = 6;
+	cpu.fcsr = 0;
+	assert!(matches!(cpu.execute(InstructionFormat::R(inst)), Err(Exception::IllegalInstruction(_))));
+
+	//conversions to integers round the same way
+	inst = RegRegInst {
+		rd: 5,
+		rs1: 1,
+		rs2: 0,
+		rm: 0,
+		instName: Instruction::FCVTWD,
+	};
+	cpu.fregs[1] = -2.5;
+	for (rm, res) in [(0, -2), (1, -2), (2, -3), (3, -2), (4, -3)] {
+		inst.rm = rm;
+		cpu.execute(InstructionFormat::R(inst)).unwrap();
+		assert_eq!(cpu.regs[5] as i64, res);
+	}
+
+	//as does narrowing a double
+	inst = RegRegInst {
+		rd: 0,
+		rs1: 1,
+		rs2: 1,
+		rm: 1,
+		instName: Instruction::FCVTSD,
+	};
+	cpu.fregs[1] = 0.1;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.freg(fpu::SINGLE, 0), 0x3dcc_cccc);
+	inst.rm = 0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.freg(fpu::SINGLE, 0), 0x3dcc_cccd);
+}
+
+#[test]
+fn float_flags_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rd: 0,
+		rs1: 1,
+		rs2: 2,
+		rm: 0,
+		instName: Instruction::FDIVD,
+	};
+	//exact results dont set anything
+	cpu.fregs[1] = 1.0;
+	cpu.fregs[2] = 4.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fcsr, 0);
+
+	cpu.fregs[2] = 3.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fcsr, NX);
+
+	//they accrue until software clears them
+	cpu.fregs[2] = 0.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], f64::INFINITY);
+	assert_eq!(cpu.fcsr, NX | DZ);
+	cpu.fcsr = 0;
+
+	//0/0 is invalid, and NaNs that come out are always the canonical one
+	cpu.fregs[1] = 0.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0].to_bits(), 0x7ff8_0000_0000_0000);
+	assert_eq!(cpu.fcsr, NV);
+	cpu.fcsr = 0;
+
+	//overflow goes to infinity, or the largest number when rounding towards zero
+	inst.instName = Instruction::FMULD;
+	cpu.fregs[1] = f64::MAX;
+	cpu.fregs[2] = 2.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], f64::INFINITY);
+	assert_eq!(cpu.fcsr, OF | NX);
+	inst.rm = 1;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], f64::MAX);
+	cpu.fcsr = 0;
+
+	//underflow is only when a tiny result is inexact, and tininess is decided after rounding
+	inst.instName = Instruction::FMULS;
+	inst.rm = 0;
+	cpu.fregs[1] = Cpu::box_float(f32::MIN_POSITIVE);
+	cpu.fregs[2] = Cpu::box_float(0.5);
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fcsr, 0);
+	cpu.fregs[2] = Cpu::box_float(f32::from_bits(0x3f7f_ffff));
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::MIN_POSITIVE);
+	assert_eq!(cpu.fcsr, UF | NX);
+	cpu.fcsr = 0;
+	//this one rounds up to the smallest normal even with unlimited exponent, so isnt tiny
+	inst = RegRegInst {
+		rd: 0,
+		rs1: 1,
+		rs2: 1,
+		rm: 0,
+		instName: Instruction::FCVTSD,
+	};
+	cpu.fregs[1] = f32::MIN_POSITIVE as f64 - 2.0f64.powi(-151);
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(Cpu::unbox_float(cpu.fregs[0]), f32::MIN_POSITIVE);
+	assert_eq!(cpu.fcsr, NX);
+	inst.rm = 1;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fcsr, NX | UF);
+	cpu.fcsr = 0;
+
+	//feq only complains about signalling NaNs, flt and fle about any NaN
+	let snan = f64::from_bits(0x7ff0_0000_0000_0001);
+	inst = RegRegInst {
+		rd: 5,
+		rs1: 1,
+		rs2: 2,
+		rm: 2,
+		instName: Instruction::FEQD,
+	};
+	cpu.fregs[1] = f64::NAN;
+	cpu.fregs[2] = 1.0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fcsr, 0);
+	cpu.fregs[1] = snan;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fcsr, NV);
+	cpu.fcsr = 0;
+	inst.instName = Instruction::FLTD;
+	cpu.fregs[1] = f64::NAN;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 0);
+	assert_eq!(cpu.fcsr, NV);
+	cpu.fcsr = 0;
+
+	//and so do min and max, which still give back the number
+	inst.instName = Instruction::FMAXD;
+	inst.rd = 0;
+	cpu.fregs[1] = snan;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.fregs[0], 1.0);
+	assert_eq!(cpu.fcsr, NV);
+	cpu.fcsr = 0;
+
+	//out of range conversions are invalid but not inexact
+	inst = RegRegInst {
+		rd: 5,
+		rs1: 1,
+		rs2: 0,
+		rm: 1,
+		instName: Instruction::FCVTWUD,
+	};
+	cpu.fregs[1] = -1.5;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 0);
+	assert_eq!(cpu.fcsr, NV);
+	cpu.fcsr = 0;
+	cpu.fregs[1] = -0.5;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[5], 0);
+	assert_eq!(cpu.fcsr, NX);
+	cpu.fcsr = 0;
+
+	//inf * 0 is invalid even when the addend is a quiet NaN
+	let fma = R4Inst {
+		rd: 0,
+		rs1: 1,
+		rs2: 2,
+		rs3: 3,
+		rm: 0,
+		instName: Instruction::FMADDD,
+	};
+	cpu.fregs[1] = f64::INFINITY;
+	cpu.fregs[2] = 0.0;
+	cpu.fregs[3] = f64::NAN;
+	cpu.execute(InstructionFormat::R4(fma)).unwrap();
+	assert!(cpu.fregs[0].is_nan());
+	assert_eq!(cpu.fcsr, NV);
+
+	//the flags show up through fflags, and writing it clears them
+	let csr = RegImmInst {
+		rd: 6,
+		rs1: 0,
+		imm: FFLAGS as i16,
+		instName: Instruction::CSRRW,
+	};
+	cpu.execute(InstructionFormat::I(csr)).unwrap();
+	assert_eq!(cpu.regs[6], NV as u64);
+	assert_eq!(cpu.fcsr, 0);
 }
 
 
@@ -3206,33 +3992,34 @@ fn AMOADDD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOADDD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 11);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 11);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	
-	cpu.bus.store(0,u64::MAX,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64,-1);
-	assert_eq!(cpu.bus.load(0,8), 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 0);
 }
 
 
@@ -3241,33 +4028,34 @@ fn AMOADDW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOADDW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 11);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 11);
 
 	cpu.regs[2] = u32::MAX as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 
-	cpu.bus.store(0,u32::MAX as u64,4);
+	cpu.bus.store(DRAM_BASE,u32::MAX as u64,4).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i32,-1);
-	assert_eq!(cpu.bus.load(0,8), 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 0);
 }
 
 
@@ -3276,33 +4064,34 @@ fn AMOSWAPD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOSWAPD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8) as i64, -1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap() as i64, -1);
 
 	
-	cpu.bus.store(0,u64::MAX,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64,-1);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 }
 
 
@@ -3311,33 +4100,34 @@ fn AMOSWAPW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOSWAPW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	cpu.regs[2] = u32::MAX as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8) as i64, -1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap() as i32, -1);
 
 	
-	cpu.bus.store(0,u32::MAX as u64,4);
+	cpu.bus.store(DRAM_BASE,u32::MAX as u64,4).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64,-1);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 }
 
 
@@ -3346,33 +4136,34 @@ fn AMOORD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOORD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 11);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 11);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), u64::MAX);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), u64::MAX);
 
 	
-	cpu.bus.store(0,u64::MAX -1,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX -1,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64,-2);
-	assert_eq!(cpu.bus.load(0,8), u64::MAX );
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), u64::MAX );
 }
 
 
@@ -3381,33 +4172,34 @@ fn AMOORW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOORW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 11);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 11);
 
 	cpu.regs[2] = u32::MAX  as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), u64::MAX);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(), u32::MAX as u64);
 
 	
-	cpu.bus.store(0,u64::MAX -1,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX -1,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64,-2);
-	assert_eq!(cpu.bus.load(0,8), u64::MAX);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), u64::MAX);
 }
 
 #[test]
@@ -3415,32 +4207,33 @@ fn AMOANDD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOANDD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 0);
 
-	cpu.bus.store(0,10,8);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
 	cpu.regs[2] = u64::MAX - 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	
 	cpu.regs[2] = 8;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 8);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 8);
 }
 
 
@@ -3449,33 +4242,35 @@ fn AMOANDW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOANDW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 0);
 
-	cpu.bus.store(0,10,8);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
 	cpu.regs[2] = u32::MAX  as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	
-	cpu.bus.store(0,(u32::MAX as u64)+1,8);
+	cpu.bus.store(DRAM_BASE,(u32::MAX as u64)+1,8).unwrap();
 	cpu.regs[2] = 10;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 0);
+	cpu.execute(instFmt).unwrap();
+	//only the word is written, the upper half is left alone
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1 << 32);
 }
 
 
@@ -3484,32 +4279,33 @@ fn AMOXORD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOXORD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 11);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 11);
 
 	cpu.regs[2] = u64::MAX - 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), !(10));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), !(10));
 
 	
-	cpu.bus.store(0,9,8);
+	cpu.bus.store(DRAM_BASE,9,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 8);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 8);
 }
 
 
@@ -3518,33 +4314,34 @@ fn AMOXORW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOXORW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 11);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 11);
 
-	cpu.bus.store(0,10,8);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), !(10));
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(), !(10u32) as u64);
 
 	
-	cpu.bus.store(0,u32::MAX as u64,8);
+	cpu.bus.store(DRAM_BASE,u32::MAX as u64,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), u64::MAX -1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), u32::MAX as u64 - 1);
 }
 
 #[test]
@@ -3552,33 +4349,34 @@ fn AMOMAXD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMAXD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	
-	cpu.bus.store(0,u64::MAX,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64, -1);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 }
 
 #[test]
@@ -3586,33 +4384,34 @@ fn AMOMAXW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMAXW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	cpu.regs[2] = u32::MAX as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	
-	cpu.bus.store(0, u32::MAX as u64,8);
+	cpu.bus.store(DRAM_BASE, u32::MAX as u64,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64, -1);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 }
 
 #[test]
@@ -3620,33 +4419,34 @@ fn AMOMAXUD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMAXUD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), u64::MAX);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), u64::MAX);
 
 	
-	cpu.bus.store(0,u64::MAX,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], u64::MAX);
-	assert_eq!(cpu.bus.load(0,8), u64::MAX);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), u64::MAX);
 }
 
 #[test]
@@ -3654,33 +4454,35 @@ fn AMOMAXUW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMAXUW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 10);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 10);
 
 	cpu.regs[2] = u32::MAX as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,4), u32::MAX as u64);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(), u32::MAX as u64);
 
 	
-	cpu.bus.store(0,u32::MAX as u64,8);
+	cpu.bus.store(DRAM_BASE,u32::MAX as u64,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.regs[3], u32::MAX as u64);
-	assert_eq!(cpu.bus.load(0,4), u32::MAX as u64);
+	cpu.execute(instFmt).unwrap();
+	//32 bit results are sign extended, even the unsigned ones
+	assert_eq!(cpu.regs[3], u64::MAX);
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(), u32::MAX as u64);
 }
 
 #[test]
@@ -3688,33 +4490,34 @@ fn AMOMIND_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMIND,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8) as i64, -1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap() as i64, -1);
 
 	
-	cpu.bus.store(0,u64::MAX,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64, -1);
-	assert_eq!(cpu.bus.load(0,8) as i64, -1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap() as i64, -1);
 }
 
 #[test]
@@ -3722,33 +4525,34 @@ fn AMOMINW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMINW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	cpu.regs[2] = u32::MAX as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8) as i64, -1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap() as i32, -1);
 
 	
-	cpu.bus.store(0,u32::MAX as u64,8);
+	cpu.bus.store(DRAM_BASE,u32::MAX as u64,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3] as i64, -1);
-	assert_eq!(cpu.bus.load(0,8) as i64, -1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap() as i32, -1);
 }
 
 
@@ -3757,33 +4561,34 @@ fn AMOMINUD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMINUD,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	cpu.regs[2] = u64::MAX;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	
-	cpu.bus.store(0,u64::MAX,8);
+	cpu.bus.store(DRAM_BASE,u64::MAX,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], u64::MAX);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 }
 
 #[test]
@@ -3791,33 +4596,35 @@ fn AMOMINUW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 		instName: Instruction::AMOMINUW,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.regs[1] = 0;
+	cpu.regs[1] = DRAM_BASE;
 	cpu.regs[2] = 1;
 	
-	cpu.bus.store(0,10,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,10,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],10);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	cpu.regs[2] = u32::MAX as u64;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 
 	
-	cpu.bus.store(0, u32::MAX as u64,8);
+	cpu.bus.store(DRAM_BASE, u32::MAX as u64,8).unwrap();
 	cpu.regs[2] = 1;
 	instFmt = InstructionFormat::R(inst);
-	cpu.execute(instFmt);
-	assert_eq!(cpu.regs[3], u32::MAX as u64);
-	assert_eq!(cpu.bus.load(0,8), 1);
+	cpu.execute(instFmt).unwrap();
+	//32 bit results are sign extended, even the unsigned ones
+	assert_eq!(cpu.regs[3], u64::MAX);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 1);
 }
 
 
@@ -3826,30 +4633,31 @@ fn LRW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::LRW,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[1] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[1] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[1] = 3;
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE + 4, 0x4142434445464748, 8).unwrap();
+	cpu.regs[1] = DRAM_BASE + 4;
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0x45464748);
 
-	cpu.bus.store(0,0xffffffff,4);
-	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0xffffffff,4).unwrap();
+	cpu.regs[1] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3] as i64, -1);
 }
@@ -3860,30 +4668,31 @@ fn LRD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::LRD,
 		rs1: 1,
 		rs2: 2,
 		rd: 3,
 	};
 	let mut instFmt = InstructionFormat::R(inst);
-	cpu.bus.store(0,0x10,1);
-	cpu.regs[1] = 0;
+	cpu.bus.store(DRAM_BASE,0x10,1).unwrap();
+	cpu.regs[1] = DRAM_BASE;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0x10);
 	
-	cpu.bus.store(0,0x20,8);
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0x20,8).unwrap();
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0x20);
 
-	cpu.bus.store(3, 0x4142434445464748, 8);
-	cpu.regs[1] = 3;
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE + 8, 0x4142434445464748, 8).unwrap();
+	cpu.regs[1] = DRAM_BASE + 8;
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0x4142434445464748);
 
-	cpu.bus.store(0,0xffffffffffffffff,8);
-	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
+	cpu.bus.store(DRAM_BASE,0xffffffffffffffff,8).unwrap();
+	cpu.regs[1] = DRAM_BASE;
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3] as i64, -1);
 }
@@ -3894,6 +4703,7 @@ fn SCW_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::SCW,
 		rs1: 1,
 		rs2: 2,
@@ -3901,23 +4711,38 @@ fn SCW_test() {
 	};
 	let mut instFmt  = InstructionFormat::R(inst);
 	cpu.regs[2] = 0x10;
-	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
-	
-	assert_eq!(cpu.bus.load(0,4),0x10);
+	cpu.regs[1] = DRAM_BASE;
+	//nothing reserved, so nothing gets stored
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[3], 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(), 0);
+
+	cpu.bus.reserve(0, DRAM_BASE, 4, 0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[3], 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(),0x10);
+
+	//the reservation was used up by the last sc
+	cpu.regs[2] = 0x20;
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[3], 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(),0x10);
 
 	cpu.regs[2] = 0x2010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,4), 0x2010);
+	cpu.bus.reserve(0, DRAM_BASE, 4, 0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,4).unwrap(), 0x2010);
 
-	cpu.regs[1] = 0x4;
+	cpu.regs[1] = DRAM_BASE + 0x4;
 	cpu.regs[2] = 0x302010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(4,4), 0x302010);
+	cpu.bus.reserve(0, DRAM_BASE + 4, 4, 0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 4,4).unwrap(), 0x302010);
 
 	cpu.regs[2] = 0x40302010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(4,4), 0x40302010);
+	cpu.bus.reserve(0, DRAM_BASE + 4, 4, 0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 4,4).unwrap(), 0x40302010);
 
 }
 
@@ -3927,6 +4752,7 @@ fn SCD_test() {
 	let fakeData: Vec<u8> = vec![0;10];
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegRegInst {
+		rm: 0,
 		instName: Instruction::SCD,
 		rs1: 1,
 		rs2: 2,
@@ -3934,21 +4760,125 @@ fn SCD_test() {
 	};
 	let mut instFmt  = InstructionFormat::R(inst);
 	cpu.regs[2] = 0x10;
-	cpu.regs[1] = 0;
-	cpu.execute(instFmt);
-	
-	assert_eq!(cpu.bus.load(0,8),0x10);
+	cpu.regs[1] = DRAM_BASE;
+	//nothing reserved, so nothing gets stored
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[3], 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 0);
+
+	cpu.bus.reserve(0, DRAM_BASE, 8, 0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[3], 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(),0x10);
+
+	//the reservation was used up by the last sc
+	cpu.regs[2] = 0x20;
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.regs[3], 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(),0x10);
 
 	cpu.regs[2] = 0x2010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(0,8), 0x2010);
+	cpu.bus.reserve(0, DRAM_BASE, 8, 0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE,8).unwrap(), 0x2010);
 
-	cpu.regs[1] = 0x4;
+	cpu.regs[1] = DRAM_BASE + 0x8;
 	cpu.regs[2] = 0x8070605040302010;
-	cpu.execute(instFmt);
-	assert_eq!(cpu.bus.load(4,8), 0x8070605040302010);
+	cpu.bus.reserve(0, DRAM_BASE + 8, 8, 0);
+	cpu.execute(instFmt).unwrap();
+	assert_eq!(cpu.bus.load(DRAM_BASE + 8,8).unwrap(), 0x8070605040302010);
+
+
+}
+
+#[test]
+fn reservation_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let lr = InstructionFormat::R(RegRegInst { rm: 0, instName: Instruction::LRD, rs1: 1, rs2: 0, rd: 3 });
+	let sc = InstructionFormat::R(RegRegInst { rm: 0, instName: Instruction::SCW, rs1: 1, rs2: 2, rd: 3 });
+	cpu.regs[1] = DRAM_BASE + 0x100;
+	cpu.regs[2] = 5;
+
+	//a store from another hart to the reserved doubleword breaks it
+	cpu.execute(lr).unwrap();
+	cpu.bus.store(DRAM_BASE + 0x104, 1, 1).unwrap();
+	cpu.execute(sc).unwrap();
+	assert_eq!(cpu.regs[3], 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x100, 4).unwrap(), 0);
 
+	//but one just outside it doesnt
+	cpu.execute(lr).unwrap();
+	cpu.bus.store(DRAM_BASE + 0x108, 1, 8).unwrap();
+	cpu.execute(sc).unwrap();
+	assert_eq!(cpu.regs[3], 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x100, 4).unwrap(), 5);
 
+	//sc to somewhere other than what was reserved fails
+	cpu.execute(lr).unwrap();
+	cpu.regs[1] = DRAM_BASE + 0x200;
+	cpu.execute(sc).unwrap();
+	assert_eq!(cpu.regs[3], 1);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x200, 4).unwrap(), 0);
+
+	//another hart reserving the same address doesnt take it away
+	cpu.execute(lr).unwrap();
+	cpu.bus.reserve(1, DRAM_BASE + 0x200, 8, 0);
+	assert!(cpu.bus.reserved(0, DRAM_BASE + 0x200));
+	assert!(cpu.bus.reserved(1, DRAM_BASE + 0x204));
+
+	//a trap in between loses it
+	cpu.regs[1] = DRAM_BASE + 0x100;
+	cpu.execute(lr).unwrap();
+	cpu.trap(3, 0, false);
+	cpu.execute(sc).unwrap();
+	assert_eq!(cpu.regs[3], 1);
+}
+
+#[test]
+fn atomic_decode_test() {
+	let fakeData: Vec<u8> = vec![
+		0x2f, 0x25, 0xb6, 0x06, //amoadd.w.aqrl a0, a1, (a2)
+		0xaf, 0x36, 0xb6, 0x0c, //amoswap.d.aq a3, a1, (a2)
+		0x2f, 0x27, 0xb6, 0xe0, //amomaxu.w a4, a1, (a2)
+		0xaf, 0x37, 0xb6, 0xc2, //amominu.d.rl a5, a1, (a2)
+		0x2f, 0x28, 0x06, 0x14, //lr.w.aq a6, (a2)
+		0xaf, 0x28, 0xb6, 0x1a, //sc.w.rl a7, a1, (a2)
+		0xaf, 0x32, 0x06, 0x10, //lr.d t0, (a2)
+		0x2f, 0x33, 0xb6, 0x18, //sc.d t1, a1, (a2)
+	];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.regs[11] = 0x8000_0001;
+	cpu.regs[12] = DRAM_BASE + 0x100;
+	cpu.bus.store(DRAM_BASE + 0x100, 0x1111_1111_7fff_ffff, 8).unwrap();
+
+	//word ops only touch the word, and sign extend what they read
+	cpu.step();
+	assert_eq!(cpu.regs[10], 0x7fff_ffff);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x100, 8).unwrap(), 0x1111_1111_0000_0000);
+	cpu.step();
+	assert_eq!(cpu.regs[13], 0x1111_1111_0000_0000);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x100, 8).unwrap(), 0x8000_0001);
+	cpu.step();
+	assert_eq!(cpu.regs[14], 0xffff_ffff_8000_0001);
+	cpu.step();
+	assert_eq!(cpu.regs[15], 0x8000_0001);
+	cpu.step();
+	assert_eq!(cpu.regs[16], 0xffff_ffff_8000_0001);
+	cpu.regs[11] = 42;
+	cpu.step();
+	assert_eq!(cpu.regs[17], 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x100, 8).unwrap(), 42);
+	cpu.step();
+	assert_eq!(cpu.regs[5], 42);
+	cpu.regs[6] = 7;
+	cpu.step();
+	assert_eq!(cpu.regs[6], 0);
+	assert_eq!(cpu.pc, DRAM_BASE + 32);
+
+	//lr with something in rs2, and atomics that arent word or doubleword, are illegal
+	assert!(matches!(Cpu::decode(0x10b6282f), Err(Exception::IllegalInstruction(0x10b6282f))));
+	assert!(matches!(Cpu::decode(0x00b6452f), Err(Exception::IllegalInstruction(0x00b6452f))));
 }
 
 
@@ -3962,30 +4892,30 @@ fn CSRRS_test() {
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegImmInst {
 		rs1: 1,
-		imm: 0,
+		imm: MSCRATCH as i16,
 		rd: 3,
 		instName: Instruction::CSRRS,
 	};
 	cpu.regs[1] = 1;
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],0);
-	assert_eq!(cpu.csrs[0],1);
+	assert_eq!(cpu.csrs[MSCRATCH],1);
 
 	cpu.regs[1] = u64::MAX;
 
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 1);
-	assert_eq!(cpu.csrs[0], u64::MAX);
+	assert_eq!(cpu.csrs[MSCRATCH], u64::MAX);
 
-	inst.imm = 10;
+	inst.imm = SSCRATCH as i16;
 
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	
 	assert_eq!(cpu.regs[3], 0);
-	assert_eq!(cpu.csrs[10], u64::MAX);
+	assert_eq!(cpu.csrs[SSCRATCH], u64::MAX);
 }
 
 #[test]
@@ -3996,20 +4926,20 @@ fn CSRRSI_test() {
 		rs1: 1,
 		rd: 2,
 		instName: Instruction::CSRRSI,
-		imm: 0,
+		imm: MSCRATCH as i16,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],0);
-	assert_eq!(cpu.csrs[0],1);
+	assert_eq!(cpu.csrs[MSCRATCH],1);
 
-	inst.imm = 10;
+	inst.imm = SSCRATCH as i16;
 	inst.rs1 = 24;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0);
-	assert_eq!(cpu.csrs[10], 24);
+	assert_eq!(cpu.csrs[SSCRATCH], 24);
 }
 
 #[test]
@@ -4018,30 +4948,30 @@ fn CSRRC_test() {
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegImmInst {
 		rs1: 1,
-		imm: 0,
+		imm: MSCRATCH as i16,
 		rd: 3,
 		instName: Instruction::CSRRC,
 	};
 	cpu.regs[1] = 1;
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],0);
-	assert_eq!(cpu.csrs[0],0);
+	assert_eq!(cpu.csrs[MSCRATCH],0);
 
-	cpu.csrs[0] = 9;
-	cpu.execute(instFmt);
+	cpu.csrs[MSCRATCH] = 9;
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 9);
-	assert_eq!(cpu.csrs[0], 8);
+	assert_eq!(cpu.csrs[MSCRATCH], 8);
 
-	inst.imm = 10;
+	inst.imm = SSCRATCH as i16;
 	cpu.regs[1] = u64::MAX;
 
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	
 	assert_eq!(cpu.regs[3], 0);
-	assert_eq!(cpu.csrs[10], 0);
+	assert_eq!(cpu.csrs[SSCRATCH], 0);
 }
 
 
@@ -4053,21 +4983,21 @@ fn CSRRCI_test() {
 		rs1: 1,
 		rd: 2,
 		instName: Instruction::CSRRCI,
-		imm: 0,
+		imm: MSCRATCH as i16,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],0);
-	assert_eq!(cpu.csrs[0],0);
+	assert_eq!(cpu.csrs[MSCRATCH],0);
 
-	inst.imm = 10;
-	cpu.csrs[10] = 9;
+	inst.imm = SSCRATCH as i16;
+	cpu.csrs[SSCRATCH] = 9;
 	inst.rs1 = 1;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 0);
-	assert_eq!(cpu.csrs[10], 8);
+	assert_eq!(cpu.csrs[SSCRATCH], 8);
 }
 
 
@@ -4077,30 +5007,30 @@ fn CSRRW_test() {
 	let mut cpu = Cpu::new(fakeData);
 	let mut inst = RegImmInst {
 		rs1: 1,
-		imm: 0,
+		imm: MSCRATCH as i16,
 		rd: 3,
 		instName: Instruction::CSRRW,
 	};
 	cpu.regs[1] = 1;
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[3],0);
-	assert_eq!(cpu.csrs[0],1);
+	assert_eq!(cpu.csrs[MSCRATCH],1);
 
-	cpu.csrs[0] = 9;
-	cpu.execute(instFmt);
+	cpu.csrs[MSCRATCH] = 9;
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[3], 9);
-	assert_eq!(cpu.csrs[0], 1);
+	assert_eq!(cpu.csrs[MSCRATCH], 1);
 
-	inst.imm = 10;
+	inst.imm = SSCRATCH as i16;
 	cpu.regs[1] = u64::MAX;
 
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	
 	assert_eq!(cpu.regs[3], 0);
-	assert_eq!(cpu.csrs[10], u64::MAX);
+	assert_eq!(cpu.csrs[SSCRATCH], u64::MAX);
 }
 
 
@@ -4112,18 +5042,1700 @@ fn CSRRWI_test() {
 		rs1: 1,
 		rd: 2,
 		instName: Instruction::CSRRWI,
-		imm: 0,
+		imm: MSCRATCH as i16,
 	};
 	let mut instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 
 	assert_eq!(cpu.regs[2],0);
-	assert_eq!(cpu.csrs[0],1);
+	assert_eq!(cpu.csrs[MSCRATCH],1);
 
-	inst.imm = 10;
-	cpu.csrs[10] = 9;
+	inst.imm = SSCRATCH as i16;
+	cpu.csrs[SSCRATCH] = 9;
 	instFmt = InstructionFormat::I(inst);
-	cpu.execute(instFmt);
+	cpu.execute(instFmt).unwrap();
 	assert_eq!(cpu.regs[2], 9);
-	assert_eq!(cpu.csrs[10], 1);
+	assert_eq!(cpu.csrs[SSCRATCH], 1);
+}
+
+//a minimal ELF64 executable: the header, a program header for each (addr, data, memsz)
+//segment, then the segment data
+fn elf_image(e_type: u16, machine: u16, entry: u64, segs: &[(u64, &[u8], u64)]) -> Vec<u8> {
+	let phoff = 64;
+	let mut out = vec![0u8; phoff + 56 * segs.len()];
+	out[..4].copy_from_slice(&[0x7f, b'E', b'L', b'F']);
+	out[4] = 2; //64 bit
+	out[5] = 1; //little endian
+	out[6] = 1;
+	out[16..18].copy_from_slice(&e_type.to_le_bytes());
+	out[18..20].copy_from_slice(&machine.to_le_bytes());
+	out[24..32].copy_from_slice(&entry.to_le_bytes());
+	out[32..40].copy_from_slice(&(phoff as u64).to_le_bytes());
+	out[52..54].copy_from_slice(&64u16.to_le_bytes());
+	out[54..56].copy_from_slice(&56u16.to_le_bytes());
+	out[56..58].copy_from_slice(&(segs.len() as u16).to_le_bytes());
+	for (i, &(addr, data, memsz)) in segs.iter().enumerate() {
+		let ph = phoff + 56 * i;
+		let fileOff = out.len() as u64;
+		out[ph..ph + 4].copy_from_slice(&1u32.to_le_bytes()); //PT_LOAD
+		out[ph + 4..ph + 8].copy_from_slice(&7u32.to_le_bytes()); //rwx
+		out[ph + 8..ph + 16].copy_from_slice(&fileOff.to_le_bytes());
+		out[ph + 16..ph + 24].copy_from_slice(&addr.to_le_bytes());
+		out[ph + 24..ph + 32].copy_from_slice(&addr.to_le_bytes());
+		out[ph + 32..ph + 40].copy_from_slice(&(data.len() as u64).to_le_bytes());
+		out[ph + 40..ph + 48].copy_from_slice(&memsz.to_le_bytes());
+		out.extend_from_slice(data);
+	}
+	out
+}
+
+#[test]
+fn elf_test() {
+	let code: &[u8] = &[0x13, 0x05, 0x10, 0x00]; //addi a0, zero, 1
+	let image = elf_image(2, 243, DRAM_BASE + 0x10, &[(DRAM_BASE + 0x10, code, 4), (DRAM_BASE + 0x100, &[1, 2], 0x10)]);
+	assert!(elf::is_elf(&image));
+	let exe = Elf::parse(&image).unwrap();
+	assert_eq!(exe.entry, DRAM_BASE + 0x10);
+	assert_eq!(exe.segments.len(), 2);
+	assert_eq!(exe.segments[1].paddr, DRAM_BASE + 0x100);
+	assert_eq!(exe.segments[1].memsz, 0x10);
+	assert_eq!(exe.segments[1].data, vec![1, 2]);
+
+	//the rest of a segment past its data is .bss, and gets zeroed even if memory wasnt
+	let mut cpu = Cpu::new(vec![0xff; 0x200]);
+	exe.load(&mut cpu).unwrap();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x10);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x10, 4).unwrap(), 0x00100513);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x100, 2).unwrap(), 0x0201);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x102, 8).unwrap(), 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x10a, 6).unwrap(), 0);
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x110, 1).unwrap(), 0xff);
+
+	//segments that dont fit in memory dont get loaded
+	let exe = Elf::parse(&elf_image(2, 243, 0, &[(DRAM_BASE + DRAM_SIZE - 4, code, 8)])).unwrap();
+	assert!(exe.load(&mut cpu).is_err());
+	let exe = Elf::parse(&elf_image(2, 243, 0, &[(u64::MAX - 2, code, 4)])).unwrap();
+	assert!(exe.load(&mut cpu).is_err());
+
+	//anything that isnt a riscv executable gets turned away
+	let mut bad = image.clone();
+	bad[3] = b'G';
+	assert!(!elf::is_elf(&bad));
+	assert!(Elf::parse(&bad).is_err());
+	let mut bad = image.clone();
+	bad[4] = 1; //32 bit
+	assert!(Elf::parse(&bad).is_err());
+	assert!(Elf::parse(&elf_image(2, 62, 0, &[])).is_err()); //x86-64
+	assert!(Elf::parse(&elf_image(3, 243, 0, &[])).is_err()); //shared object
+	assert!(Elf::parse(&image[..40]).is_err());
+	//the second program header is cut off, then the second segment's data is
+	assert!(Elf::parse(&image[..64 + 56 + 20]).is_err());
+	assert!(Elf::parse(&image[..image.len() - 1]).is_err());
+	//more in the file than in memory
+	assert!(Elf::parse(&elf_image(2, 243, 0, &[(DRAM_BASE, code, 2)])).is_err());
+}
+
+#[test]
+fn memory_map_test() {
+	let fakeData: Vec<u8> = vec![0x13, 0, 0, 0];
+	let mut cpu = Cpu::new(fakeData);
+
+	//code gets loaded at the start of dram, and thats where execution starts
+	assert_eq!(cpu.pc, DRAM_BASE);
+	assert_eq!(cpu.bus.load(DRAM_BASE, 4).unwrap(), 0x13);
+
+	//nothing lives at 0 anymore
+	assert_eq!(cpu.bus.load(0, 4), Err(BusError::Unmapped(0)));
+
+	//accesses hanging off the end of dram dont get split
+	let end = DRAM_BASE + cpu.bus.dram.size();
+	assert_eq!(cpu.bus.load(end - 8, 8).unwrap(), 0);
+	assert_eq!(cpu.bus.load(end - 4, 8), Err(BusError::Unmapped(end - 4)));
+
+	let mut cpu = Cpu::with_bus(Bus::with_dram(Dram::New(vec![1, 2], 0x1000, 0x100)).unwrap());
+	assert_eq!(cpu.pc, 0x1000);
+	assert_eq!(cpu.regs[2], 0x1100);
+	assert_eq!(cpu.bus.load(0x1000, 2).unwrap(), 0x201);
+	assert_eq!(cpu.bus.store(0x1100, 1, 1), Err(BusError::Unmapped(0x1100)));
+
+	//dram cant be moved on top of a device
+	assert_eq!(Bus::with_dram(Dram::New(Vec::new(), CLINT_BASE - 0x1000, 0x2000)).err(), Some(BusError::Overlap(CLINT_BASE - 0x1000)));
+	assert_eq!(Bus::with_dram(Dram::New(Vec::new(), PLIC_BASE + 0x1000, 0x1000)).err(), Some(BusError::Overlap(PLIC_BASE + 0x1000)));
+	assert_eq!(Bus::with_dram(Dram::New(Vec::new(), UART_BASE + 0x80, 0x1000)).err(), Some(BusError::Overlap(UART_BASE + 0x80)));
+	assert!(Bus::with_dram(Dram::New(Vec::new(), UART_BASE + 0x100, 0x1000)).is_ok());
+}
+
+#[test]
+fn illegal_instruction_trap_test() {
+	//all ones isnt a valid 32 bit instruction
+	let fakeData: Vec<u8> = vec![0xff, 0xff, 0xff, 0xff];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.store_csr(MSTATUS, MSTATUS_MIE);
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MEPC), DRAM_BASE);
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+	assert_eq!(cpu.load_csr(MTVAL), 0xffffffff);
+	//interrupts get disabled, old enable and privilege are saved
+	let status = cpu.load_csr(MSTATUS);
+	assert_eq!(status & MSTATUS_MIE, 0);
+	assert_eq!(status & MSTATUS_MPIE, MSTATUS_MPIE);
+	assert_eq!((status & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT, 3);
+	assert_eq!(cpu.prv, privilege_level::M);
+
+	assert_eq!(Cpu::decode(0xffffffff).unwrap_err(), Exception::IllegalInstruction(0xffffffff));
+	//store with funct3 = 4
+	assert_eq!(Cpu::decode(0x00004023).unwrap_err(), Exception::IllegalInstruction(0x00004023));
+
+	//fadd.s f1, f2, f3 with reserved rounding mode 5 decodes fine and gets turned down in execute.
+	//mtval still gets the instruction
+	let fakeData: Vec<u8> = vec![0xd3, 0x50, 0x31, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.store_csr(MSTATUS, MSTATUS_FS);
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+	assert_eq!(cpu.load_csr(MTVAL), 0x003150d3);
+}
+
+#[test]
+fn access_fault_trap_test() {
+	//ld x1, 0(x2)
+	let fakeData: Vec<u8> = vec![0x83, 0x30, 0x01, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.regs[1] = 5;
+	cpu.regs[2] = 0x10;
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MEPC), DRAM_BASE);
+	assert_eq!(cpu.load_csr(MCAUSE), 5);
+	assert_eq!(cpu.load_csr(MTVAL), 0x10);
+	//destination is left alone
+	assert_eq!(cpu.regs[1], 5);
+
+	let mut inst = StoreInst {
+		instName: Instruction::SW,
+		rs1: 2,
+		rs2: 1,
+		imm: 0,
+	};
+	assert_eq!(cpu.execute(InstructionFormat::S(inst)), Err(Exception::StoreAccessFault(0x10)));
+	inst.imm = -0x10;
+	cpu.regs[2] = DRAM_BASE + cpu.bus.dram.size() + 0x10;
+	assert_eq!(cpu.execute(InstructionFormat::S(inst)), Err(Exception::StoreAccessFault(DRAM_BASE + cpu.bus.dram.size())));
+
+	//fetching from nowhere
+	cpu.pc = 0x1000;
+	assert_eq!(cpu.fetch(), Err(Exception::InstructionAccessFault(0x1000)));
+}
+
+#[test]
+fn misaligned_jump_trap_test() {
+	//jal x1, 2. Fine now that instructions only have to be 2 byte aligned
+	let fakeData: Vec<u8> = vec![0xef, 0x00, 0x20, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 2);
+	assert_eq!(cpu.regs[1], DRAM_BASE + 4);
+
+	//jal and branch offsets are always even and jalr clears bit 0, so the only way
+	//to get to an odd pc is from outside
+	cpu.pc = DRAM_BASE + 1;
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MEPC), DRAM_BASE);
+	assert_eq!(cpu.load_csr(MCAUSE), 0);
+	assert_eq!(cpu.load_csr(MTVAL), DRAM_BASE + 1);
+}
+
+#[test]
+fn DIV_by_zero_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegRegInst {
+		rm: 0,
+		instName: Instruction::DIV,
+		rd: 3,
+		rs1: 1,
+		rs2: 2,
+	};
+	cpu.regs[1] = 12;
+	cpu.regs[2] = 0;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[3], u64::MAX);
+
+	inst.instName = Instruction::REM;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[3], 12);
+
+	inst.instName = Instruction::DIVUW;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[3], u64::MAX);
+
+	inst.instName = Instruction::REMUW;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[3], 12);
+
+	//most negative number / -1 overflows, result is the dividend
+	inst.instName = Instruction::DIV;
+	cpu.regs[1] = i64::MIN as u64;
+	cpu.regs[2] = u64::MAX;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[3], i64::MIN as u64);
+
+	inst.instName = Instruction::REM;
+	cpu.execute(InstructionFormat::R(inst)).unwrap();
+	assert_eq!(cpu.regs[3], 0);
+}
+
+#[test]
+fn ECALL_test() {
+	//ecall, ecall, ecall
+	let fakeData: Vec<u8> = vec![0x73, 0x00, 0x00, 0x00, 0x73, 0x00, 0x00, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE);
+
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), 11);
+	assert_eq!(cpu.load_csr(MEPC), DRAM_BASE);
+
+	cpu.prv = privilege_level::S;
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), 9);
+	assert_eq!((cpu.load_csr(MSTATUS) & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT, 1);
+
+	cpu.prv = privilege_level::U;
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), 8);
+	assert_eq!(cpu.prv, privilege_level::M);
+}
+
+#[test]
+fn EBREAK_test() {
+	let fakeData: Vec<u8> = vec![0x73, 0x00, 0x10, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x40);
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x40);
+	assert_eq!(cpu.load_csr(MCAUSE), 3);
+	assert_eq!(cpu.load_csr(MTVAL), DRAM_BASE);
+}
+
+#[test]
+fn MRET_test() {
+	//mret
+	let fakeData: Vec<u8> = vec![0x73, 0x00, 0x20, 0x30];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MEPC, DRAM_BASE + 0x80);
+	cpu.store_csr(MSTATUS, MSTATUS_MPIE | (1 << MSTATUS_MPP_SHIFT) | MSTATUS_MPRV);
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x80);
+	assert_eq!(cpu.prv, privilege_level::S);
+	let status = cpu.load_csr(MSTATUS);
+	assert_eq!(status & MSTATUS_MIE, MSTATUS_MIE);
+	assert_eq!(status & MSTATUS_MPIE, MSTATUS_MPIE);
+	assert_eq!(status & MSTATUS_MPP, 0);
+	assert_eq!(status & MSTATUS_MPRV, 0);
+
+	//only machine mode gets to mret
+	cpu.pc = DRAM_BASE;
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+}
+
+#[test]
+fn SRET_test() {
+	//sret
+	let fakeData: Vec<u8> = vec![0x73, 0x00, 0x20, 0x10];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.prv = privilege_level::S;
+	cpu.store_csr(SEPC, DRAM_BASE + 0x80);
+	cpu.store_csr(MSTATUS, MSTATUS_SPIE);
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x80);
+	assert_eq!(cpu.prv, privilege_level::U);
+	let status = cpu.load_csr(MSTATUS);
+	assert_eq!(status & MSTATUS_SIE, MSTATUS_SIE);
+	assert_eq!(status & MSTATUS_SPIE, MSTATUS_SPIE);
+
+	//tsr makes sret trap when in supervisor mode
+	cpu.pc = DRAM_BASE;
+	cpu.prv = privilege_level::S;
+	cpu.store_csr(MSTATUS, MSTATUS_TSR);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+}
+
+#[test]
+fn WFI_test() {
+	//wfi, addi x1, x0, 1
+	let fakeData: Vec<u8> = vec![0x73, 0x00, 0x50, 0x10, 0x93, 0x00, 0x10, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 4);
+	assert!(cpu.wfi);
+	//nothing pending, so stays parked
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 4);
+	assert_eq!(cpu.regs[1], 0);
+
+	//pending but not enabled in mstatus still wakes it up
+	cpu.store_csr(MIE, MIP_SSIP);
+	cpu.store_csr(MIP, MIP_SSIP);
+	cpu.step();
+	assert!(!cpu.wfi);
+	assert_eq!(cpu.regs[1], 1);
+
+	//illegal from user mode
+	cpu.store_csr(MIP, 0);
+	cpu.pc = DRAM_BASE;
+	cpu.prv = privilege_level::U;
+	cpu.step();
+	assert!(!cpu.wfi);
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+}
+
+#[test]
+fn SFENCEVMA_test() {
+	//sfence.vma x0, x0
+	let fakeData: Vec<u8> = vec![0x73, 0x00, 0x00, 0x12];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.prv = privilege_level::S;
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 4);
+
+	cpu.pc = DRAM_BASE;
+	cpu.prv = privilege_level::U;
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+	assert_eq!(cpu.load_csr(MTVAL), 0x12000073);
+}
+
+#[test]
+fn trap_delegation_test() {
+	//ecall
+	let fakeData: Vec<u8> = vec![0x73, 0x00, 0x00, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.store_csr(STVEC, DRAM_BASE + 0x200);
+	cpu.store_csr(MEDELEG, 1 << 8);
+	cpu.store_csr(MSTATUS, MSTATUS_SIE);
+	cpu.prv = privilege_level::U;
+
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x200);
+	assert_eq!(cpu.prv, privilege_level::S);
+	assert_eq!(cpu.load_csr(SCAUSE), 8);
+	assert_eq!(cpu.load_csr(SEPC), DRAM_BASE);
+	let status = cpu.load_csr(MSTATUS);
+	assert_eq!(status & MSTATUS_SIE, 0);
+	assert_eq!(status & MSTATUS_SPIE, MSTATUS_SPIE);
+	assert_eq!(status & MSTATUS_SPP, 0);
+
+	//delegation never applies to traps taken from machine mode
+	cpu.pc = DRAM_BASE;
+	cpu.prv = privilege_level::M;
+	cpu.store_csr(MEDELEG, 1 << 11);
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MCAUSE), 11);
+}
+
+#[test]
+fn csr_access_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let mut inst = RegImmInst {
+		rs1: 1,
+		imm: MSCRATCH as i16,
+		rd: 3,
+		instName: Instruction::CSRRW,
+	};
+	cpu.regs[1] = 5;
+	//supervisor cant touch machine csrs
+	cpu.prv = privilege_level::S;
+	assert_eq!(cpu.execute(InstructionFormat::I(inst)).unwrap_err(), Exception::IllegalInstruction(0x340091f3));
+	inst.imm = SSCRATCH as i16;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	assert_eq!(cpu.load_csr(SSCRATCH), 5);
+
+	//read only csrs can be read but not written
+	cpu.prv = privilege_level::M;
+	inst.imm = MHARTID as i16;
+	assert!(cpu.execute(InstructionFormat::I(inst)).is_err());
+	inst.instName = Instruction::CSRRS;
+	inst.rs1 = 0;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	assert_eq!(cpu.regs[3], 0);
+
+	//csrs that dont exist are illegal too
+	inst.imm = 0x7c0;
+	assert!(cpu.execute(InstructionFormat::I(inst)).is_err());
+
+	//user counters need mcounteren/scounteren
+	inst.imm = CYCLE as i16;
+	cpu.prv = privilege_level::U;
+	assert!(cpu.execute(InstructionFormat::I(inst)).is_err());
+	cpu.store_csr(MCOUNTEREN, 1);
+	cpu.store_csr(SCOUNTEREN, 1);
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+
+	//tvm traps satp from supervisor
+	inst.imm = SATP as i16;
+	cpu.prv = privilege_level::S;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	cpu.store_csr(MSTATUS, MSTATUS_TVM);
+	assert!(cpu.execute(InstructionFormat::I(inst)).is_err());
+}
+
+#[test]
+fn csr_warl_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+
+	//misa is fixed, rv64 with IMAFDCSU
+	let misa = cpu.load_csr(MISA);
+	cpu.store_csr(MISA, 0);
+	assert_eq!(cpu.load_csr(MISA), misa);
+	assert_eq!(misa >> 62, 2);
+	assert_eq!(misa & 0x3ffffff, (1 << 0) | (1 << 2) | (1 << 3) | (1 << 5) | (1 << 8) | (1 << 12) | (1 << 18) | (1 << 20));
+
+	//mepc only needs to be 2 byte aligned
+	cpu.store_csr(MEPC, 0x1003);
+	assert_eq!(cpu.load_csr(MEPC), 0x1002);
+
+	//mstatus: uxl/sxl are hardwired to 64 bit, reserved mpp keeps the old value
+	cpu.store_csr(MSTATUS, u64::MAX);
+	let status = cpu.load_csr(MSTATUS);
+	assert_eq!((status & MSTATUS_UXL) >> 32, 2);
+	assert_eq!((status & MSTATUS_SXL) >> 34, 2);
+	assert_eq!(status & MSTATUS_SD, MSTATUS_SD);
+	cpu.store_csr(MSTATUS, 2 << MSTATUS_MPP_SHIFT);
+	assert_eq!(cpu.load_csr(MSTATUS) & MSTATUS_MPP, MSTATUS_MPP);
+
+	//sstatus is a view of mstatus
+	cpu.store_csr(MSTATUS, MSTATUS_MIE | MSTATUS_SIE);
+	assert_eq!(cpu.load_csr(SSTATUS) & (MSTATUS_MIE | MSTATUS_SIE), MSTATUS_SIE);
+	cpu.store_csr(SSTATUS, MSTATUS_SPP);
+	assert_eq!(cpu.load_csr(MSTATUS) & (MSTATUS_MIE | MSTATUS_SIE | MSTATUS_SPP), MSTATUS_MIE | MSTATUS_SPP);
+
+	//sie/sip only see delegated interrupts
+	cpu.store_csr(MIDELEG, u64::MAX);
+	assert_eq!(cpu.load_csr(MIDELEG), MIP_SSIP | MIP_STIP | MIP_SEIP);
+	cpu.store_csr(MIE, u64::MAX);
+	assert_eq!(cpu.load_csr(SIE), MIP_SSIP | MIP_STIP | MIP_SEIP);
+
+	//reserved trap vector modes become direct
+	cpu.store_csr(MTVEC, DRAM_BASE | 3);
+	assert_eq!(cpu.load_csr(MTVEC), DRAM_BASE);
+	cpu.store_csr(MTVEC, DRAM_BASE | 1);
+	assert_eq!(cpu.load_csr(MTVEC), DRAM_BASE | 1);
+
+	//unsupported satp modes are ignored
+	cpu.store_csr(SATP, 15 << 60);
+	assert_eq!(cpu.load_csr(SATP), 0);
+
+	cpu.store_csr(MEDELEG, u64::MAX);
+	assert_eq!(cpu.load_csr(MEDELEG) & (1 << 11), 0);
+
+	//fflags and frm are views of fcsr
+	cpu.store_csr(FCSR, 0xff);
+	assert_eq!(cpu.load_csr(FFLAGS), 0x1f);
+	assert_eq!(cpu.load_csr(FRM), 0x7);
+	cpu.store_csr(FRM, 1);
+	assert_eq!(cpu.load_csr(FCSR), 0x3f);
+
+	//software only gets to write the supervisor bits of mip
+	let inst = RegImmInst {
+		rs1: 1,
+		imm: MIP as i16,
+		rd: 0,
+		instName: Instruction::CSRRW,
+	};
+	cpu.regs[1] = u64::MAX;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	assert_eq!(cpu.load_csr(MIP), MIP_SSIP | MIP_STIP | MIP_SEIP);
+}
+
+//builds a pte pointing at a physical address
+fn make_pte(paddr: u64, flags: u64) -> u64 {
+	((paddr >> 12) << 10) | flags
+}
+
+#[test]
+fn sv39_translation_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let root = DRAM_BASE + 0x10000;
+	let mid = DRAM_BASE + 0x11000;
+	let leaf = DRAM_BASE + 0x12000;
+	let page = DRAM_BASE + 0x20000;
+	//0x4000_0000 -> page, user rw
+	cpu.bus.store(root + 8, make_pte(mid, PTE_V), 8).unwrap();
+	cpu.bus.store(mid, make_pte(leaf, PTE_V), 8).unwrap();
+	cpu.bus.store(leaf, make_pte(page, PTE_V | PTE_R | PTE_W | PTE_U), 8).unwrap();
+	//0x4000_1000 -> page + 0x1000, user execute only
+	cpu.bus.store(leaf + 8, make_pte(page + 0x1000, PTE_V | PTE_X | PTE_U), 8).unwrap();
+	//0x8000_0000 -> DRAM_BASE as a supervisor gigapage
+	cpu.bus.store(root + 16, make_pte(DRAM_BASE, PTE_V | PTE_R | PTE_W | PTE_X), 8).unwrap();
+	cpu.bus.store(page + 8, 0x1234, 8).unwrap();
+	cpu.store_csr(SATP, (8 << 60) | (root >> 12));
+
+	//machine mode doesnt translate
+	assert_eq!(cpu.load(0x4000_0008, 8).unwrap_err(), Exception::LoadAccessFault(0x4000_0008));
+
+	//supervisor needs sum to touch user pages
+	cpu.prv = privilege_level::S;
+	assert_eq!(cpu.load(0x4000_0008, 8).unwrap_err(), Exception::LoadPageFault(0x4000_0008));
+	cpu.store_csr(MSTATUS, MSTATUS_SUM);
+	assert_eq!(cpu.load(0x4000_0008, 8).unwrap(), 0x1234);
+	let pte = cpu.bus.load(leaf, 8).unwrap();
+	assert_eq!(pte & (PTE_A | PTE_D), PTE_A);
+	cpu.store(0x4000_0010, 0x5678, 8).unwrap();
+	assert_eq!(cpu.bus.load(page + 0x10, 8).unwrap(), 0x5678);
+	assert_eq!(cpu.bus.load(leaf, 8).unwrap() & PTE_D, PTE_D);
+	//gigapage keeps the low bits of the address
+	assert_eq!(cpu.load(0x8002_0008, 8).unwrap(), 0x1234);
+
+	//user cant touch supervisor pages
+	cpu.prv = privilege_level::U;
+	assert_eq!(cpu.load(0x8000_0000, 4).unwrap_err(), Exception::LoadPageFault(0x8000_0000));
+	assert_eq!(cpu.store(0x8000_0000, 0, 4).unwrap_err(), Exception::StorePageFault(0x8000_0000));
+	assert_eq!(cpu.load(0x4000_0008, 8).unwrap(), 0x1234);
+
+	//execute only pages can be read with mxr
+	assert_eq!(cpu.load(0x4000_1000, 8).unwrap_err(), Exception::LoadPageFault(0x4000_1000));
+	cpu.store_csr(MSTATUS, MSTATUS_MXR);
+	cpu.load(0x4000_1000, 8).unwrap();
+	assert_eq!(cpu.store(0x4000_1000, 0, 8).unwrap_err(), Exception::StorePageFault(0x4000_1000));
+
+	//addresses have to be sign extended from bit 38
+	assert_eq!(cpu.load(0x80_0000_0000, 8).unwrap_err(), Exception::LoadPageFault(0x80_0000_0000));
+	//unmapped
+	assert_eq!(cpu.load(0x4000_2000, 8).unwrap_err(), Exception::LoadPageFault(0x4000_2000));
+
+	//mprv makes machine mode loads use mpp's privilege
+	cpu.prv = privilege_level::M;
+	cpu.store_csr(MSTATUS, MSTATUS_MPRV | (1 << MSTATUS_MPP_SHIFT));
+	assert_eq!(cpu.load(0x4000_0008, 8).unwrap_err(), Exception::LoadPageFault(0x4000_0008));
+}
+
+#[test]
+fn instruction_page_fault_test() {
+	//addi x1, x0, 1
+	let fakeData: Vec<u8> = vec![0x93, 0x00, 0x10, 0x00];
+	let mut cpu = Cpu::new(fakeData);
+	let root = DRAM_BASE + 0x10000;
+	cpu.bus.store(root + 16, make_pte(DRAM_BASE, PTE_V | PTE_R | PTE_X | PTE_A), 8).unwrap();
+	cpu.store_csr(SATP, (8 << 60) | (root >> 12));
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.prv = privilege_level::S;
+
+	cpu.step();
+	assert_eq!(cpu.regs[1], 1);
+	assert_eq!(cpu.pc, DRAM_BASE + 4);
+
+	//supervisor cant run user pages, even with sum
+	cpu.bus.store(root + 16, make_pte(DRAM_BASE, PTE_V | PTE_R | PTE_X | PTE_U), 8).unwrap();
+	cpu.tlb.flush(None, None);
+	cpu.store_csr(MSTATUS, MSTATUS_SUM);
+	cpu.pc = DRAM_BASE;
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), 12);
+	assert_eq!(cpu.load_csr(MTVAL), DRAM_BASE);
+	assert_eq!(cpu.prv, privilege_level::M);
+}
+
+#[test]
+fn sv48_sv57_translation_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let page = DRAM_BASE + 0x20000;
+	cpu.bus.store(page, 0xabcd, 8).unwrap();
+	cpu.prv = privilege_level::S;
+
+	//sv48 - 0x8000_0000_0000 isnt canonical, but 0x1_0000_0000_0000 is too big as well
+	//map 0x0000_7f80_0000_0000 through 4 levels
+	let va: u64 = 0x7f80_0000_0000;
+	let tables = [DRAM_BASE + 0x10000, DRAM_BASE + 0x11000, DRAM_BASE + 0x12000, DRAM_BASE + 0x13000];
+	for level in 0..3 {
+		let vpn = (va >> (12 + 9 * (3 - level))) & 0x1ff;
+		cpu.bus.store(tables[level as usize] + vpn * 8, make_pte(tables[level as usize + 1], PTE_V), 8).unwrap();
+	}
+	cpu.bus.store(tables[3], make_pte(page, PTE_V | PTE_R), 8).unwrap();
+	cpu.store_csr(SATP, (9 << 60) | (tables[0] >> 12));
+	assert_eq!(cpu.load_csr(SATP) >> 60, 9);
+	assert_eq!(cpu.load(va, 8).unwrap(), 0xabcd);
+	assert_eq!(cpu.load(0x8000_0000_0000, 8).unwrap_err(), Exception::LoadPageFault(0x8000_0000_0000));
+
+	//sv57 - one more level on top
+	let root = DRAM_BASE + 0x14000;
+	cpu.bus.store(root, make_pte(tables[0], PTE_V), 8).unwrap();
+	cpu.store_csr(SATP, (10 << 60) | (root >> 12));
+	assert_eq!(cpu.load(va, 8).unwrap(), 0xabcd);
+
+	//misaligned superpage
+	cpu.bus.store(tables[2], make_pte(page, PTE_V | PTE_R), 8).unwrap();
+	cpu.tlb.flush(None, None);
+	assert_eq!(cpu.load(va, 8).unwrap_err(), Exception::LoadPageFault(va));
+}
+
+#[test]
+fn tlb_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let root = DRAM_BASE + 0x10000;
+	let mid = DRAM_BASE + 0x11000;
+	let leaf = DRAM_BASE + 0x12000;
+	let page = DRAM_BASE + 0x20000;
+	cpu.bus.store(root + 8, make_pte(mid, PTE_V), 8).unwrap();
+	cpu.bus.store(mid, make_pte(leaf, PTE_V), 8).unwrap();
+	cpu.bus.store(leaf, make_pte(page, PTE_V | PTE_R | PTE_W), 8).unwrap();
+	cpu.bus.store(leaf + 8, make_pte(page + 0x1000, PTE_V | PTE_R | PTE_W | PTE_G), 8).unwrap();
+	cpu.bus.store(page, 1, 8).unwrap();
+	cpu.bus.store(page + 0x1000, 2, 8).unwrap();
+	//asid 5
+	cpu.store_csr(SATP, (8 << 60) | (5 << 44) | (root >> 12));
+	cpu.prv = privilege_level::S;
+
+	assert_eq!(cpu.load(0x4000_0000, 8).unwrap(), 1);
+	assert_eq!(cpu.load(0x4000_0000, 8).unwrap(), 1);
+	assert_eq!(cpu.tlb.misses, 1);
+	assert_eq!(cpu.tlb.hits, 1);
+	//first store has to walk again to set D
+	cpu.store(0x4000_0000, 1, 8).unwrap();
+	cpu.store(0x4000_0000, 1, 8).unwrap();
+	assert_eq!(cpu.tlb.misses, 2);
+	assert_eq!(cpu.tlb.hits, 2);
+	assert_eq!(cpu.load(0x4000_1000, 8).unwrap(), 2);
+
+	//remapping without a sfence still uses the old translation
+	cpu.bus.store(leaf, make_pte(page + 0x1000, PTE_V | PTE_R | PTE_W), 8).unwrap();
+	assert_eq!(cpu.load(0x4000_0000, 8).unwrap(), 1);
+
+	let mut inst = RegImmInst {
+		rs1: 1,
+		imm: 0x120,
+		rd: 0,
+		instName: Instruction::SFENCEVMA,
+	};
+	//sfence on a different address doesnt help
+	cpu.regs[1] = 0x4000_2000;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	assert_eq!(cpu.load(0x4000_0000, 8).unwrap(), 1);
+	//on the right one does
+	cpu.regs[1] = 0x4000_0008;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	assert_eq!(cpu.load(0x4000_0000, 8).unwrap(), 2);
+	assert_eq!(cpu.tlb.len(), 2);
+
+	//flushing an asid leaves global pages alone
+	inst.rs1 = 0;
+	inst.imm = 0x120 | 2;
+	cpu.regs[2] = 5;
+	cpu.execute(InstructionFormat::I(inst)).unwrap();
+	assert_eq!(cpu.tlb.len(), 1);
+	assert_eq!(cpu.tlb.lookup(7, 0x4000_1000).unwrap().translate(0x4000_1008), page + 0x1008);
+
+	//satp writes throw everything away
+	cpu.store_csr(SATP, (8 << 60) | (6 << 44) | (root >> 12));
+	assert_eq!(cpu.tlb.len(), 0);
+	assert_eq!(cpu.tlb.flushes, 5);
+}
+
+#[test]
+fn pmp_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	//nothing set up means no checks
+	cpu.prv = privilege_level::U;
+	cpu.load(DRAM_BASE, 8).unwrap();
+
+	//entry 0: TOR up to DRAM_BASE + 0x1000, read only
+	//entry 1: NAPOT 4K at DRAM_BASE + 0x1000, read/write
+	//entry 2: NA4 at DRAM_BASE + 0x2000, execute only
+	cpu.store_csr(PMPADDR0, (DRAM_BASE + 0x1000) >> 2);
+	cpu.store_csr(PMPADDR0 + 1, ((DRAM_BASE + 0x1000) >> 2) | 0x1ff);
+	cpu.store_csr(PMPADDR0 + 2, (DRAM_BASE + 0x2000) >> 2);
+	let cfg0 = (PMP_TOR << PMP_A_SHIFT) | PMP_R;
+	let cfg1 = (PMP_NAPOT << PMP_A_SHIFT) | PMP_R | PMP_W;
+	let cfg2 = (PMP_NA4 << PMP_A_SHIFT) | PMP_X;
+	cpu.store_csr(PMPCFG0, (cfg0 as u64) | ((cfg1 as u64) << 8) | ((cfg2 as u64) << 16));
+
+	cpu.load(DRAM_BASE, 8).unwrap();
+	assert_eq!(cpu.store(DRAM_BASE, 0, 8).unwrap_err(), Exception::StoreAccessFault(DRAM_BASE));
+	cpu.store(DRAM_BASE + 0x1000, 0, 8).unwrap();
+	cpu.store(DRAM_BASE + 0x1ff8, 0, 8).unwrap();
+	//an entry has to cover the whole access
+	assert_eq!(cpu.load(DRAM_BASE + 0x2000, 8).unwrap_err(), Exception::LoadAccessFault(DRAM_BASE + 0x2000));
+	assert_eq!(cpu.load(DRAM_BASE + 0x2000, 4).unwrap_err(), Exception::LoadAccessFault(DRAM_BASE + 0x2000));
+	//nothing matches
+	assert_eq!(cpu.load(DRAM_BASE + 0x3000, 4).unwrap_err(), Exception::LoadAccessFault(DRAM_BASE + 0x3000));
+
+	//machine mode ignores entries that arent locked
+	cpu.prv = privilege_level::M;
+	cpu.store(DRAM_BASE, 0, 8).unwrap();
+	cpu.load(DRAM_BASE + 0x3000, 4).unwrap();
+
+	//fetches need X
+	cpu.prv = privilege_level::S;
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.pc = DRAM_BASE + 0x1000;
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), 1);
+	assert_eq!(cpu.load_csr(MTVAL), DRAM_BASE + 0x1000);
+
+	//locking applies to machine mode and stops further changes
+	cpu.store_csr(PMPCFG0, ((cfg0 | PMP_L) as u64) | ((cfg1 as u64) << 8) | ((cfg2 as u64) << 16));
+	assert_eq!(cpu.store(DRAM_BASE, 0, 8).unwrap_err(), Exception::StoreAccessFault(DRAM_BASE));
+	cpu.store_csr(PMPCFG0, 0);
+	assert_eq!(cpu.pmp_cfg(0), cfg0 | PMP_L);
+	assert_eq!(cpu.pmp_cfg(1), 0);
+	cpu.store_csr(PMPADDR0, 0);
+	assert_eq!(cpu.load_csr(PMPADDR0), (DRAM_BASE + 0x1000) >> 2);
+
+	//write without read is reserved
+	cpu.store_csr(PMPCFG0 + 2, PMP_W as u64);
+	assert_eq!(cpu.pmp_cfg(8), 0);
+}
+
+#[test]
+fn pmp_page_walk_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	let root = DRAM_BASE + 0x10000;
+	cpu.bus.store(root + 16, make_pte(DRAM_BASE, PTE_V | PTE_R | PTE_W | PTE_A | PTE_D), 8).unwrap();
+	cpu.store_csr(SATP, (8 << 60) | (root >> 12));
+	//entry 0 covers the page table with no permissions, entry 1 lets everything else through
+	cpu.store_csr(PMPADDR0, (root >> 2) | 0x1ff);
+	cpu.store_csr(PMPADDR0 + 1, u64::MAX);
+	let cfg1 = (PMP_NAPOT << PMP_A_SHIFT) | PMP_R | PMP_W | PMP_X;
+	cpu.store_csr(PMPCFG0, ((PMP_NAPOT << PMP_A_SHIFT) as u64) | ((cfg1 as u64) << 8));
+	cpu.prv = privilege_level::S;
+
+	//walk cant read the page table, so its an access fault on the original address
+	assert_eq!(cpu.load(0x8000_0008, 8).unwrap_err(), Exception::LoadAccessFault(0x8000_0008));
+	cpu.store_csr(PMPCFG0, (((PMP_NAPOT << PMP_A_SHIFT) | PMP_R) as u64) | ((cfg1 as u64) << 8));
+	assert_eq!(cpu.load(0x8000_0008, 8).unwrap(), 0);
+}
+
+#[test]
+fn timer_interrupt_test() {
+	//addi x1, x1, 1 over and over
+	let fakeData: Vec<u8> = [0x93, 0x80, 0x10, 0x00].repeat(16);
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.store_csr(MIE, MIP_MTIP);
+	cpu.bus.store(CLINT_BASE + 0x4000, 3, 8).unwrap();
+
+	//mstatus.MIE is off, so it just shows up in mip
+	cpu.step();
+	cpu.step();
+	cpu.step();
+	assert_eq!(cpu.load_csr(MIP) & MIP_MTIP, MIP_MTIP);
+	assert_eq!(cpu.regs[1], 3);
+	assert_eq!(cpu.load_csr(TIME), 3);
+
+	cpu.store_csr(MSTATUS, MSTATUS_MIE);
+	cpu.step();
+	assert_eq!(cpu.regs[1], 3);
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MEPC), DRAM_BASE + 12);
+	assert_eq!(cpu.load_csr(MCAUSE), (1 << 63) | 7);
+	assert_eq!(cpu.load_csr(MSTATUS) & MSTATUS_MIE, 0);
+
+	//moving mtimecmp forward clears it
+	cpu.bus.store(CLINT_BASE + 0x4000, u64::MAX, 8).unwrap();
+	cpu.step();
+	assert_eq!(cpu.load_csr(MIP) & MIP_MTIP, 0);
+}
+
+#[test]
+fn software_interrupt_test() {
+	let fakeData: Vec<u8> = [0x93, 0x80, 0x10, 0x00].repeat(4);
+	let mut cpu = Cpu::new(fakeData);
+	//vectored mode
+	cpu.store_csr(MTVEC, (DRAM_BASE + 0x100) | 1);
+	cpu.store_csr(MIE, MIP_MSIP | MIP_MTIP | MIP_SSIP);
+	cpu.prv = privilege_level::S;
+
+	cpu.bus.store(CLINT_BASE, 1, 4).unwrap();
+	assert_eq!(cpu.bus.load(CLINT_BASE, 4).unwrap(), 1);
+	cpu.bus.store(CLINT_BASE + 0x4000, 0, 8).unwrap();
+	cpu.store_csr(MIP, MIP_SSIP);
+	//machine interrupts are always enabled below M, and software beats timer
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), (1 << 63) | 3);
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100 + 4 * 3);
+	assert_eq!(cpu.prv, privilege_level::M);
+
+	//delegated supervisor interrupts go to stvec, and arent taken from M
+	cpu.bus.store(CLINT_BASE, 0, 4).unwrap();
+	cpu.bus.store(CLINT_BASE + 0x4000, u64::MAX, 8).unwrap();
+	cpu.store_csr(MIDELEG, MIP_SSIP);
+	cpu.store_csr(STVEC, DRAM_BASE + 0x200);
+	cpu.store_csr(MSTATUS, MSTATUS_MIE | MSTATUS_SIE);
+	cpu.pc = DRAM_BASE;
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 4);
+	cpu.prv = privilege_level::U;
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x200);
+	assert_eq!(cpu.load_csr(SCAUSE), (1 << 63) | 1);
+	assert_eq!(cpu.prv, privilege_level::S);
+}
+
+#[test]
+fn plic_test() {
+	let fakeData: Vec<u8> = [0x93, 0x80, 0x10, 0x00].repeat(4);
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.store_csr(MIE, MIP_MEIP | MIP_SEIP);
+
+	//source 3 priority 2, source 5 priority 1. Both enabled for machine mode
+	cpu.bus.store(PLIC_BASE + 3 * 4, 2, 4).unwrap();
+	cpu.bus.store(PLIC_BASE + 5 * 4, 1, 4).unwrap();
+	cpu.bus.store(PLIC_BASE + 0x2000, (1 << 3) | (1 << 5), 4).unwrap();
+	cpu.bus.plic.set_irq(5, true);
+	cpu.bus.plic.set_irq(3, true);
+	assert_eq!(cpu.bus.load(PLIC_BASE + 0x1000, 4).unwrap(), (1 << 3) | (1 << 5));
+
+	//threshold masks out anything at or below it
+	cpu.bus.store(PLIC_BASE + 0x20_0000, 2, 4).unwrap();
+	cpu.step();
+	assert_eq!(cpu.load_csr(MIP) & MIP_MEIP, 0);
+	cpu.bus.store(PLIC_BASE + 0x20_0000, 0, 4).unwrap();
+	cpu.store_csr(MSTATUS, MSTATUS_MIE);
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), (1 << 63) | 11);
+
+	//claims come out in priority order
+	assert_eq!(cpu.bus.load(PLIC_BASE + 0x20_0004, 4).unwrap(), 3);
+	assert_eq!(cpu.bus.load(PLIC_BASE + 0x20_0004, 4).unwrap(), 5);
+	assert_eq!(cpu.bus.load(PLIC_BASE + 0x20_0004, 4).unwrap(), 0);
+	cpu.step();
+	assert_eq!(cpu.load_csr(MIP) & MIP_MEIP, 0);
+
+	//3 is still held high, so it comes back after complete. 5 has gone low
+	cpu.bus.plic.set_irq(5, false);
+	cpu.bus.store(PLIC_BASE + 0x20_0004, 5, 4).unwrap();
+	cpu.bus.store(PLIC_BASE + 0x20_0004, 3, 4).unwrap();
+	assert_eq!(cpu.bus.load(PLIC_BASE + 0x1000, 4).unwrap(), 1 << 3);
+
+	//supervisor context drives seip
+	cpu.bus.store(PLIC_BASE + 0x2000, 0, 4).unwrap();
+	cpu.bus.store(PLIC_BASE + 0x2080, 1 << 3, 4).unwrap();
+	cpu.step();
+	assert_eq!(cpu.load_csr(MIP) & (MIP_MEIP | MIP_SEIP), MIP_SEIP);
+	assert_eq!(cpu.bus.load(PLIC_BASE + 0x20_1004, 4).unwrap(), 3);
+}
+
+#[test]
+fn uart_test() {
+	let fakeData: Vec<u8> = vec![0;10];
+	let mut cpu = Cpu::new(fakeData);
+	//transmitter is always empty, nothing to read yet
+	assert_eq!(cpu.bus.load(UART_BASE + 5, 1).unwrap(), 0x60);
+	assert_eq!(cpu.bus.load(UART_BASE + 2, 1).unwrap(), 0x1);
+
+	//divisor latch sits under rbr/ier while dlab is set
+	cpu.bus.store(UART_BASE + 3, 0x80, 1).unwrap();
+	cpu.bus.store(UART_BASE, 3, 1).unwrap();
+	cpu.bus.store(UART_BASE + 1, 0, 1).unwrap();
+	cpu.bus.store(UART_BASE + 3, 0x03, 1).unwrap();
+	assert_eq!(cpu.bus.load(UART_BASE + 1, 1).unwrap(), 0);
+	cpu.bus.store(UART_BASE + 3, 0x83, 1).unwrap();
+	assert_eq!(cpu.bus.load(UART_BASE, 1).unwrap(), 3);
+	cpu.bus.store(UART_BASE + 3, 0x03, 1).unwrap();
+
+	//loopback: what goes out comes back in
+	cpu.bus.store(UART_BASE + 4, 0x10, 1).unwrap();
+	cpu.bus.store(UART_BASE, b'a' as u64, 1).unwrap();
+	cpu.bus.store(UART_BASE, b'b' as u64, 1).unwrap();
+	assert_eq!(cpu.bus.load(UART_BASE + 5, 1).unwrap() & 1, 1);
+	assert_eq!(cpu.bus.load(UART_BASE, 1).unwrap(), b'a' as u64);
+	assert_eq!(cpu.bus.load(UART_BASE, 1).unwrap(), b'b' as u64);
+	assert_eq!(cpu.bus.load(UART_BASE + 5, 1).unwrap() & 1, 0);
+
+	//fifo enabled shows up in iir, and clearing it throws away whats been received
+	cpu.bus.device::<Uart>().unwrap().receive(b'c');
+	cpu.bus.store(UART_BASE + 2, 0x03, 1).unwrap();
+	assert_eq!(cpu.bus.load(UART_BASE + 2, 1).unwrap(), 0xc1);
+	assert_eq!(cpu.bus.load(UART_BASE + 5, 1).unwrap() & 1, 0);
+}
+
+#[test]
+fn uart_interrupt_test() {
+	let fakeData: Vec<u8> = [0x93, 0x80, 0x10, 0x00].repeat(4);
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.store_csr(MIE, MIP_MEIP);
+	cpu.store_csr(MSTATUS, MSTATUS_MIE);
+	cpu.bus.store(PLIC_BASE + 4 * UART_IRQ as u64, 1, 4).unwrap();
+	cpu.bus.store(PLIC_BASE + 0x2000, 1 << UART_IRQ, 4).unwrap();
+
+	//receive interrupt
+	cpu.bus.store(UART_BASE + 1, 0x1, 1).unwrap();
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 4);
+	cpu.bus.device::<Uart>().unwrap().receive(b'x');
+	cpu.step();
+	assert_eq!(cpu.load_csr(MCAUSE), (1 << 63) | 11);
+	assert_eq!(cpu.bus.load(PLIC_BASE + 0x20_0004, 4).unwrap(), UART_IRQ as u64);
+	assert_eq!(cpu.bus.load(UART_BASE + 2, 1).unwrap(), 0x4);
+	assert_eq!(cpu.bus.load(UART_BASE, 1).unwrap(), b'x' as u64);
+	cpu.bus.store(PLIC_BASE + 0x20_0004, UART_IRQ as u64, 4).unwrap();
+
+	//transmit empty interrupt goes off as soon as its enabled, and reading iir clears it
+	cpu.bus.store(UART_BASE + 1, 0x2, 1).unwrap();
+	assert!(cpu.bus.device::<Uart>().unwrap().irq_pending());
+	assert_eq!(cpu.bus.load(UART_BASE + 2, 1).unwrap(), 0x2);
+	assert!(!cpu.bus.device::<Uart>().unwrap().irq_pending());
+	assert_eq!(cpu.bus.load(UART_BASE + 2, 1).unwrap(), 0x1);
+}
+
+//a counter that raises its interrupt line once it gets to a limit
+struct CountdownDevice {
+	count: u64,
+	limit: u64,
+}
+
+impl Device for CountdownDevice {
+	fn load(&mut self, offset: u64, _size: u8) -> u64 {
+		match offset {
+			0 => self.count,
+			8 => self.limit,
+			_ => 0,
+		}
+	}
+
+	fn store(&mut self, offset: u64, data: u64, _size: u8) {
+		match offset {
+			0 => self.count = data,
+			8 => self.limit = data,
+			_ => {},
+		}
+	}
+
+	fn reset(&mut self) {
+		self.count = 0;
+		self.limit = u64::MAX;
+	}
+
+	fn tick(&mut self) {
+		self.count += 1;
+	}
+
+	fn irq(&self) -> Option<usize> {
+		Some(7)
+	}
+
+	fn irq_pending(&self) -> bool {
+		self.count >= self.limit
+	}
+}
+
+#[test]
+fn device_test() {
+	let fakeData: Vec<u8> = [0x93, 0x80, 0x10, 0x00].repeat(4);
+	let mut cpu = Cpu::new(fakeData);
+	let base = 0x2000_0000;
+
+	//nothing can go on top of dram, the interrupt controllers, the uart or each other
+	let countdown = || Box::new(CountdownDevice { count: 5, limit: 0 });
+	assert_eq!(cpu.bus.add_device(DRAM_BASE + 0x1000, 0x100, countdown()), Err(BusError::Overlap(DRAM_BASE + 0x1000)));
+	assert_eq!(cpu.bus.add_device(CLINT_BASE - 0x10, 0x20, countdown()), Err(BusError::Overlap(CLINT_BASE - 0x10)));
+	assert_eq!(cpu.bus.add_device(UART_BASE + 0xff, 1, countdown()), Err(BusError::Overlap(UART_BASE + 0xff)));
+	assert_eq!(cpu.bus.add_device(base, 0, countdown()), Err(BusError::Overlap(base)));
+	assert_eq!(cpu.bus.add_device(base, 0x10, countdown()), Ok(()));
+	assert_eq!(cpu.bus.add_device(base + 0xf, 0x10, countdown()), Err(BusError::Overlap(base + 0xf)));
+	assert_eq!(cpu.bus.add_device(base + 0x10, 0x10, countdown()), Ok(()));
+
+	//accesses get offsets from where the device was mapped, and it was reset on the way in
+	assert_eq!(cpu.bus.load(base, 8).unwrap(), 0);
+	assert_eq!(cpu.bus.load(base + 8, 8).unwrap(), u64::MAX);
+	assert_eq!(cpu.bus.load(base + 0x20, 8), Err(BusError::Unmapped(base + 0x20)));
+
+	//its line goes through the plic like any other
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	cpu.store_csr(MIE, MIP_MEIP);
+	cpu.store_csr(MSTATUS, MSTATUS_MIE);
+	cpu.bus.store(PLIC_BASE + 4 * 7, 1, 4).unwrap();
+	cpu.bus.store(PLIC_BASE + 0x2000, 1 << 7, 4).unwrap();
+	cpu.bus.store(base + 8, 2, 8).unwrap();
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 4);
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MCAUSE), (1 << 63) | 11);
+	assert_eq!(cpu.bus.device::<CountdownDevice>().unwrap().count, 2);
+}
+
+#[test]
+fn rvc_test() {
+	let fakeData: Vec<u8> = vec![
+		0x15, 0x45, //c.li a0, 5
+		0x0d, 0x05, //c.addi a0, 3
+		0xaa, 0x85, //c.mv a1, a0
+		0xaa, 0x95, //c.add a1, a1, a0
+		0x13, 0x04, 0x00, 0xf8, //li s0, -128 (a full size one in the middle)
+		0x0d, 0x84, //c.srai s0, 3
+		0x3d, 0x71, //c.addi16sp sp, -32
+		0x2e, 0xe4, //c.sdsp a1, 8(sp)
+		0xa2, 0x66, //c.ldsp a3, 8(sp)
+		0x11, 0xe1, //c.bnez a0, 4
+		0x00, 0x00, //all zeros, illegal
+		0x82, 0x92, //c.jalr t0
+	];
+	let mut cpu = Cpu::new(fakeData);
+	cpu.store_csr(MTVEC, DRAM_BASE + 0x100);
+	let sp = cpu.regs[2];
+	for _ in 0..10 {
+		cpu.step();
+	}
+	assert_eq!(cpu.regs[10], 8);
+	assert_eq!(cpu.regs[11], 16);
+	assert_eq!(cpu.regs[8], (-16i64) as u64);
+	assert_eq!(cpu.regs[2], sp - 32);
+	assert_eq!(cpu.regs[13], 16);
+	//the branch skipped the illegal one
+	assert_eq!(cpu.pc, DRAM_BASE + 24);
+	assert_eq!(cpu.load_csr(MINSTRET), 10);
+
+	//links go to the next instruction, which is only 2 bytes on
+	cpu.regs[5] = DRAM_BASE + 0x42;
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x42);
+	assert_eq!(cpu.regs[1], DRAM_BASE + 26);
+
+	//illegal compressed instructions report the 16 bits
+	cpu.pc = DRAM_BASE + 22;
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 0x100);
+	assert_eq!(cpu.load_csr(MCAUSE), 2);
+	assert_eq!(cpu.load_csr(MTVAL), 0);
+
+	//the expansions themselves
+	assert_eq!(rvc::expand(0x4515), Ok(0x00500513)); //addi a0, x0, 5
+	assert_eq!(rvc::expand(0x9002), Ok(0x00100073)); //ebreak
+	assert_eq!(rvc::expand(0x8082), Ok(0x00008067)); //jalr x0, 0(ra)
+	assert_eq!(rvc::expand(0x6105), Ok(0x02010113)); //addi sp, sp, 32
+	assert_eq!(rvc::expand(0x8082 & !0x80), Err(Exception::IllegalInstruction(0x8002))); //c.jr x0
+}
+
+#[test]
+fn smp_test() {
+	let fakeData: Vec<u8> = [0x93, 0x80, 0x10, 0x00].repeat(16);
+	let mut machine = Machine::New(Cpu::new(fakeData), 2, 3);
+	assert_eq!(machine.harts(), 2);
+	assert_eq!(machine.hart(0).load_csr(MHARTID), 0);
+	assert_eq!(machine.hart(1).load_csr(MHARTID), 1);
+	assert_eq!(machine.hart(1).pc, DRAM_BASE);
+
+	//three instructions on hart 0, then three on hart 1, each with their own registers
+	for _ in 0..3 {
+		machine.step();
+	}
+	assert_eq!(machine.current(), 1);
+	assert_eq!(machine.hart(0).regs[1], 3);
+	assert_eq!(machine.hart(1).regs[1], 0);
+	for _ in 0..4 {
+		machine.step();
+	}
+	assert_eq!(machine.current(), 0);
+	assert_eq!(machine.hart(0).regs[1], 4);
+	assert_eq!(machine.hart(1).regs[1], 3);
+	assert_eq!(machine.bus().clint.mtime, 7);
+
+	//hart 0 sends hart 1 an ipi through the clint
+	let hart1 = machine.hart_mut(1);
+	hart1.store_csr(MTVEC, DRAM_BASE + 0x100);
+	hart1.store_csr(MIE, MIP_MSIP);
+	hart1.store_csr(MSTATUS, MSTATUS_MIE);
+	machine.bus().store(CLINT_BASE + 4, 1, 4).unwrap();
+	machine.step();
+	machine.step();
+	assert_eq!(machine.hart(0).load_csr(MIP) & MIP_MSIP, 0);
+	machine.step();
+	assert_eq!(machine.hart(1).load_csr(MCAUSE), (1 << 63) | 3);
+	assert_eq!(machine.hart(1).pc, DRAM_BASE + 0x100);
+
+	//a store from one hart breaks another hart's reservation
+	machine.bus().reserve(0, DRAM_BASE + 0x200, 8, 0);
+	machine.hart_mut(1).store(DRAM_BASE + 0x204, 1, 4).unwrap();
+	assert!(!machine.bus().reserved(0, DRAM_BASE + 0x200));
+}
+
+#[test]
+fn dram_atomic_test() {
+	let dram = Dram::New(vec![0x11, 0x22, 0x33], 0x1000, 0x20);
+	assert_eq!(dram.load(0, 4), 0x332211);
+
+	//part of a doubleword gets swapped out without touching the rest
+	assert_eq!(dram.fetch_update(4, 4, |old| old + 0xffff_ffff), 0);
+	assert_eq!(dram.load(0, 8), 0xffff_ffff_0033_2211);
+	let (old, version) = dram.load_reserved(4, 4);
+	assert_eq!(old, 0xffff_ffff);
+	assert!(dram.store_conditional(4, 4, version, 5));
+	assert_eq!(dram.load(0, 8), 0x0000_0005_0033_2211);
+	//that used the version up
+	assert!(!dram.store_conditional(4, 4, version, 6));
+	//a store puts the same value back, but sc still fails
+	let (_, version) = dram.load_reserved(4, 4);
+	dram.store(4, 6, 4);
+	dram.store(4, 5, 4);
+	assert!(!dram.store_conditional(4, 4, version, 6));
+	assert_eq!(dram.load(4, 4), 5);
+
+	//misaligned accesses across two doublewords still work, a byte at a time
+	dram.store(6, 0x8877_6655_4433_2211, 8);
+	assert_eq!(dram.load(6, 8), 0x8877_6655_4433_2211);
+	assert_eq!(dram.load(8, 2), 0x4433);
+
+	//shared memory shows up both ways
+	let other = dram.share();
+	other.store(0x18, 42, 8);
+	assert_eq!(dram.load(0x18, 8), 42);
+
+	//a bus on another thread goes back to home for everything that isnt dram
+	let home = Arc::new(Mutex::new(Bus::with_dram(Dram::New(Vec::new(), DRAM_BASE, 0x1000)).unwrap()));
+	let mut a = Bus::attach(&home, 4);
+	let mut b = Bus::attach(&home, 4);
+	b.store(CLINT_BASE, 1, 4).unwrap();
+	assert_eq!(a.load(CLINT_BASE, 4).unwrap(), 1);
+	assert!(a.clint.software_pending(0));
+	for _ in 0..4 {
+		a.tick();
+	}
+	assert_eq!(b.load(CLINT_BASE + 0xbff8, 8).unwrap(), 4);
+
+	//sc only goes through if nothing was stored since the lr, even when the value ended up
+	//back where it was
+	assert_eq!(a.load_reserved(0, DRAM_BASE + 0x10, 4), Ok(0));
+	b.store(DRAM_BASE + 0x10, 7, 4).unwrap();
+	b.store(DRAM_BASE + 0x10, 0, 4).unwrap();
+	assert_eq!(a.store_conditional(0, DRAM_BASE + 0x10, 1, 4), Ok(false));
+	assert_eq!(a.load_reserved(0, DRAM_BASE + 0x10, 4), Ok(0));
+	assert_eq!(a.store_conditional(0, DRAM_BASE + 0x14, 1, 4), Ok(false));
+	assert_eq!(a.load_reserved(0, DRAM_BASE + 0x10, 4), Ok(0));
+	b.amo(DRAM_BASE + 0x10, 4, |old| old).unwrap();
+	assert_eq!(a.store_conditional(0, DRAM_BASE + 0x10, 1, 4), Ok(false));
+	assert_eq!(a.load_reserved(0, DRAM_BASE + 0x10, 4), Ok(0));
+	assert_eq!(a.store_conditional(0, DRAM_BASE + 0x10, 1, 4), Ok(true));
+	assert_eq!(b.amo(DRAM_BASE + 0x10, 4, |old| old + 1), Ok(1));
+	assert_eq!(a.load(DRAM_BASE + 0x10, 4).unwrap(), 2);
+}
+
+#[test]
+fn threaded_test() {
+	let fakeData: Vec<u8> = vec![
+		0x17, 0x13, 0x00, 0x00, //auipc t1, 1
+		0x13, 0x0e, 0x83, 0x00, //addi t3, t1, 8
+		0x93, 0x02, 0x80, 0x3e, //addi t0, zero, 1000
+		0x93, 0x03, 0x10, 0x00, //addi t2, zero, 1
+		0x2f, 0x20, 0x73, 0x00, //amoadd.w zero, t2, (t1)
+		0x2f, 0x25, 0x0e, 0x10, //lr.w a0, (t3)
+		0x13, 0x05, 0x15, 0x00, //addi a0, a0, 1
+		0xaf, 0x25, 0xae, 0x18, //sc.w a1, a0, (t3)
+		0xe3, 0x9a, 0x05, 0xfe, //bnez a1, -12
+		0x93, 0x82, 0xf2, 0xff, //addi t0, t0, -1
+		0xe3, 0x94, 0x02, 0xfe, //bnez t0, -24
+		0x67, 0x00, 0x00, 0x00, //jr zero
+	];
+	let mut machine = Machine::New(Cpu::new(fakeData), 4, 16);
+	machine.run_threaded(|_, cpu| cpu.pc != 0);
+
+	//every increment made it, whether it was an amo or an lr/sc loop
+	assert_eq!(machine.bus().load(DRAM_BASE + 0x1000, 4).unwrap(), 4000);
+	assert_eq!(machine.bus().load(DRAM_BASE + 0x1008, 4).unwrap(), 4000);
+	for id in 0..4 {
+		assert_eq!(machine.hart(id).pc, 0);
+		assert_eq!(machine.hart(id).regs[5], 0);
+	}
+	//the devices got a tick for every instruction
+	let insts: u64 = (0..4).map(|id| machine.hart(id).load_csr(MCYCLE)).sum();
+	assert_eq!(machine.bus().clint.mtime, insts);
+}
+
+//a static executable with one segment at 0x10000
+fn user_exe(code: Vec<u8>) -> Elf {
+	Elf {
+		entry: 0x10000,
+		segments: vec![Segment { paddr: 0x10000, vaddr: 0x10000, memsz: 0x1800, flags: PF_R | PF_X, data: code }],
+		phdr: 0,
+		phnum: 0,
+		phentsize: 0,
+	}
+}
+
+#[test]
+fn user_mode_test() {
+	let code: Vec<u8> = vec![
+		0x13, 0x05, 0x00, 0x00, //addi a0, zero, 0
+		0x93, 0x08, 0x60, 0x0d, //addi a7, zero, 214
+		0x73, 0x00, 0x00, 0x00, //ecall
+		0x13, 0x04, 0x05, 0x00, //addi s0, a0, 0
+		0x83, 0x34, 0x01, 0x00, //ld s1, 0(sp)
+		0x13, 0x05, 0x70, 0x00, //addi a0, zero, 7
+		0x93, 0x08, 0xe0, 0x05, //addi a7, zero, 94
+		0x73, 0x00, 0x00, 0x00, //ecall
+	];
+	let mut cpu = Cpu::with_bus(Bus::with_dram(Dram::New(Vec::new(), USER_BASE, 0x100_0000)).unwrap());
+	let args = vec!["prog".to_string(), "arg".to_string()];
+	let mut exe = user_exe(code);
+	exe.phdr = 0x10040;
+	exe.phnum = 1;
+	exe.phentsize = 56;
+	let mut linux = Linux::New(&mut cpu, &exe, &args, &["A=1".to_string()]).unwrap();
+	assert_eq!(cpu.prv, privilege_level::U);
+	assert_eq!(cpu.pc, 0x10000);
+
+	//argc, then argv and envp, then the aux vector
+	let sp = cpu.regs[2];
+	assert_eq!(sp % 16, 0);
+	assert_eq!(cpu.bus.load(sp, 8).unwrap(), 2);
+	let argv1 = cpu.bus.load(sp + 16, 8).unwrap();
+	assert_eq!(cpu.bus.read_bytes(argv1, 4).unwrap(), b"arg\0");
+	assert_eq!(cpu.bus.load(sp + 24, 8).unwrap(), 0);
+	let envp0 = cpu.bus.load(sp + 32, 8).unwrap();
+	assert_eq!(cpu.bus.read_bytes(envp0, 4).unwrap(), b"A=1\0");
+	assert_eq!(cpu.bus.load(sp + 40, 8).unwrap(), 0);
+	let mut auxv = std::collections::HashMap::new();
+	let mut at = sp + 48;
+	loop {
+		let key = cpu.bus.load(at, 8).unwrap();
+		auxv.insert(key, cpu.bus.load(at + 8, 8).unwrap());
+		at += 16;
+		if key == 0 {
+			break;
+		}
+	}
+	assert_eq!(auxv[&3], 0x10040); //AT_PHDR
+	assert_eq!(auxv[&5], 1); //AT_PHNUM
+	assert_eq!(auxv[&6], 0x1000); //AT_PAGESZ
+	assert_eq!(auxv[&9], 0x10000); //AT_ENTRY
+	assert_eq!(auxv[&16] & 0x112d, 0x112d); //AT_HWCAP has imafdc
+	assert_eq!(cpu.bus.read_bytes(auxv[&15], 8).unwrap(), b"riscv64\0"); //AT_PLATFORM
+	//AT_RANDOM points at 16 bytes on the stack, above the table
+	assert!(auxv[&25] > at && auxv[&25] + 16 <= USER_BASE + 0x100_0000);
+
+	//the exit status comes out of exit_group, and the heap starts after the program
+	let status = loop {
+		cpu.step();
+		if let Some(status) = linux.service(&mut cpu).unwrap() {
+			break status;
+		}
+	};
+	assert_eq!(status, 7);
+	assert_eq!(cpu.regs[8], 0x12000);
+	assert_eq!(cpu.regs[9], 2);
+
+	//anything that isnt an ecall kills it
+	let mut cpu = Cpu::with_bus(Bus::with_dram(Dram::New(Vec::new(), USER_BASE, 0x100_0000)).unwrap());
+	let mut linux = Linux::New(&mut cpu, &user_exe(vec![0xff; 4]), &args, &[]).unwrap();
+	cpu.step();
+	let fault = linux.service(&mut cpu).unwrap_err();
+	assert_eq!(fault, Fault { cause: 2, pc: 0x10000, tval: 0xffff_ffff });
+	assert_eq!(fault.signal(), 4);
+}
+
+#[test]
+fn initial_stack_test() {
+	let mut cpu = Cpu::with_bus(Bus::with_dram(Dram::New(Vec::new(), USER_BASE, 0x100_0000)).unwrap());
+	let args = vec!["/bin/prog".to_string(), "a".to_string(), "bc".to_string()];
+	let env = vec!["HOME=/".to_string(), "X=12345".to_string()];
+	let mut exe = user_exe(Vec::new());
+	exe.phdr = 0x10040;
+	exe.phnum = 3;
+	exe.phentsize = 56;
+	Linux::New(&mut cpu, &exe, &args, &env).unwrap();
+	let top = USER_BASE + 0x100_0000;
+
+	//sp is 16 byte aligned whatever length the strings come to, and sits on argc
+	let sp = cpu.regs[2];
+	assert_eq!(sp % 16, 0);
+	assert_eq!(cpu.bus.load(sp, 8).unwrap(), 3);
+	let mut at = sp + 8;
+	let mut strings = |cpu: &mut Cpu, at: &mut u64| {
+		let mut out = Vec::new();
+		loop {
+			let ptr = cpu.bus.load(*at, 8).unwrap();
+			*at += 8;
+			if ptr == 0 {
+				return out;
+			}
+			assert!(ptr > sp && ptr < top);
+			let mut s = Vec::new();
+			while let b @ 1.. = cpu.bus.load(ptr + s.len() as u64, 1).unwrap() as u8 {
+				s.push(b);
+			}
+			out.push(String::from_utf8(s).unwrap());
+		}
+	};
+	assert_eq!(strings(&mut cpu, &mut at), args);
+	assert_eq!(strings(&mut cpu, &mut at), env);
+
+	//then key/value pairs up to AT_NULL
+	let mut auxv = Vec::new();
+	loop {
+		let pair = (cpu.bus.load(at, 8).unwrap(), cpu.bus.load(at + 8, 8).unwrap());
+		auxv.push(pair);
+		at += 16;
+		if pair.0 == 0 {
+			break;
+		}
+	}
+	let aux = |key: u64| auxv.iter().find(|pair| pair.0 == key).map(|pair| pair.1);
+	assert_eq!(auxv.last(), Some(&(0, 0)));
+	assert_eq!(aux(3), Some(0x10040)); //AT_PHDR
+	assert_eq!(aux(4), Some(56)); //AT_PHENT
+	assert_eq!(aux(5), Some(3)); //AT_PHNUM
+	assert_eq!(aux(9), Some(0x10000)); //AT_ENTRY
+	assert_eq!(aux(31), Some(cpu.bus.load(sp + 8, 8).unwrap())); //AT_EXECFN is argv[0]
+	//AT_RANDOM is 16 bytes between the table and the top of the stack
+	let random = aux(25).unwrap();
+	assert!(random >= at && random + 16 <= top);
+
+	//an environment bigger than the stack is E2BIG, rather than running over whatever is below it
+	let mut cpu = Cpu::with_bus(Bus::with_dram(Dram::New(Vec::new(), USER_BASE, 0x100_0000)).unwrap());
+	let huge = vec!["X".repeat(9 * 1024 * 1024)];
+	let err = Linux::New(&mut cpu, &user_exe(Vec::new()), &args, &huge).err().unwrap();
+	assert_eq!(err.kind(), std::io::ErrorKind::ArgumentListTooLong);
+}
+
+#[test]
+fn syscall_test() {
+	let mut cpu = Cpu::with_bus(Bus::with_dram(Dram::New(Vec::new(), USER_BASE, 0x100_0000)).unwrap());
+	let mut linux = Linux::New(&mut cpu, &user_exe(Vec::new()), &["prog".to_string()], &[]).unwrap();
+	let mut call = |cpu: &mut Cpu, nr: u64, args: &[u64]| {
+		cpu.regs[17] = nr;
+		for (i, &arg) in args.iter().enumerate() {
+			cpu.regs[10 + i] = arg;
+		}
+		assert_eq!(linux.syscall(cpu), None);
+		cpu.regs[10] as i64
+	};
+	let buf = 0x20000;
+
+	//brk moves up from the end of the program
+	assert_eq!(call(&mut cpu, 214, &[0]), 0x12000);
+	assert_eq!(call(&mut cpu, 214, &[0x30000]), 0x30000);
+	assert_eq!(call(&mut cpu, 214, &[0x10]), 0x30000);
+
+	//anonymous mmaps come down from under the stack, page sized
+	let first = call(&mut cpu, 222, &[0, 0x1800, 3, 0x22, u64::MAX, 0]);
+	let second = call(&mut cpu, 222, &[0, 0x1000, 3, 0x22, u64::MAX, 0]);
+	assert_eq!(first % 0x1000, 0);
+	assert_eq!(second, first - 0x1000);
+	assert_eq!(call(&mut cpu, 222, &[0, 0x100_0000, 3, 0x22, u64::MAX, 0]), -12);
+
+	assert_eq!(call(&mut cpu, 160, &[buf]), 0);
+	assert_eq!(cpu.bus.read_bytes(buf, 6).unwrap(), b"Linux\0");
+	assert_eq!(cpu.bus.read_bytes(buf + 65 * 4, 8).unwrap(), b"riscv64\0");
+
+	//files go through to the host
+	let path = std::env::temp_dir().join(format!("riscvEmu-syscall-{}", std::process::id()));
+	std::fs::write(&path, "hello").unwrap();
+	let mut name = path.to_string_lossy().into_owned().into_bytes();
+	name.push(0);
+	cpu.bus.write_bytes(buf, &name).unwrap();
+	assert_eq!(call(&mut cpu, 56, &[-100i64 as u64, buf, 0, 0]), 3);
+	assert_eq!(call(&mut cpu, 63, &[3, buf + 0x100, 16]), 5);
+	assert_eq!(cpu.bus.read_bytes(buf + 0x100, 5).unwrap(), b"hello");
+	assert_eq!(call(&mut cpu, 63, &[3, buf + 0x100, 16]), 0);
+	assert_eq!(call(&mut cpu, 62, &[3, 1, 0]), 1);
+	assert_eq!(call(&mut cpu, 80, &[3, buf + 0x200]), 0);
+	assert_eq!(cpu.bus.load(buf + 0x200 + 48, 8).unwrap(), 5);
+	assert_eq!(call(&mut cpu, 57, &[3]), 0);
+	assert_eq!(call(&mut cpu, 57, &[3]), -9);
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(call(&mut cpu, 56, &[-100i64 as u64, buf, 0, 0]), -2);
+
+	//bad pointers and things we dont do
+	assert_eq!(call(&mut cpu, 64, &[1, 0x10, 16]), -14);
+	assert_eq!(call(&mut cpu, 278, &[buf, 32, 0]), 32);
+	assert_eq!(call(&mut cpu, 113, &[1, buf]), 0);
+	assert_eq!(call(&mut cpu, 29, &[1, 0x5401, 0]), -25);
+	assert_eq!(call(&mut cpu, 9999, &[]), -38);
+}
+
+#[test]
+fn user_memory_test() {
+	//sd zero, 0(a0)
+	let code = vec![0x23, 0x30, 0x05, 0x00];
+	let mut cpu = Cpu::with_bus(Bus::with_dram(Dram::New(Vec::new(), USER_BASE, 0x100_0000)).unwrap());
+	let mut linux = Linux::New(&mut cpu, &user_exe(code), &["prog".to_string()], &[]).unwrap();
+	let mut call = |cpu: &mut Cpu, nr: u64, args: &[u64]| {
+		cpu.regs[17] = nr;
+		for (i, &arg) in args.iter().enumerate() {
+			cpu.regs[10 + i] = arg;
+		}
+		assert_eq!(linux.syscall(cpu), None);
+		cpu.regs[10] as i64
+	};
+	let readable = |cpu: &mut Cpu, addr: u64| mm::accessible(cpu, addr, 1, AccessType::Load);
+	let writable = |cpu: &mut Cpu, addr: u64| mm::accessible(cpu, addr, 1, AccessType::Store);
+
+	//the program is read and execute only, and nothing below it is mapped
+	assert!(readable(&mut cpu, 0x10000) && !writable(&mut cpu, 0x10000));
+	assert!(!readable(&mut cpu, 0xf000) && !readable(&mut cpu, 0x12000));
+
+	//three pages, read only, then open up the middle one
+	let map = call(&mut cpu, 222, &[0, 0x3000, 1, 0x22, u64::MAX, 0]) as u64;
+	assert!(readable(&mut cpu, map) && !writable(&mut cpu, map));
+	let path = std::env::temp_dir().join(format!("riscvEmu-mm-{}", std::process::id()));
+	std::fs::write(&path, "hello").unwrap();
+	let mut name = path.to_string_lossy().into_owned().into_bytes();
+	name.push(0);
+	let name = {
+		let at = call(&mut cpu, 222, &[0, 0x1000, 3, 0x22, u64::MAX, 0]) as u64;
+		cpu.bus.write_bytes(at, &name).unwrap();
+		at
+	};
+	assert_eq!(call(&mut cpu, 56, &[-100i64 as u64, name, 0, 0]), 3);
+	assert_eq!(call(&mut cpu, 63, &[3, map + 0x1000, 16]), -14);
+	assert_eq!(call(&mut cpu, 226, &[map + 0x1000, 0x1000, 3]), 0);
+	assert_eq!(call(&mut cpu, 63, &[3, map + 0x1000, 16]), 5);
+	assert!(!writable(&mut cpu, map) && writable(&mut cpu, map + 0x1000) && !writable(&mut cpu, map + 0x2000));
+
+	//punching a hole leaves both sides mapped
+	assert_eq!(call(&mut cpu, 215, &[map + 0x1000, 0x1000]), 0);
+	assert!(readable(&mut cpu, map) && !readable(&mut cpu, map + 0x1000) && readable(&mut cpu, map + 0x2000));
+	assert_eq!(call(&mut cpu, 226, &[map, 0x3000, 3]), -12);
+	assert_eq!(call(&mut cpu, 215, &[map + 1, 0x1000]), -22);
+
+	//the hole can be taken back, and comes back zeroed. A hint that doesnt fit gets moved
+	assert_eq!(call(&mut cpu, 222, &[map + 0x1000, 0x1000, 3, 0x100022, u64::MAX, 0]), map as i64 + 0x1000);
+	assert_eq!(cpu.bus.load(map + 0x1000, 8).unwrap(), 0);
+	assert_eq!(call(&mut cpu, 222, &[map, 0x1000, 3, 0x100022, u64::MAX, 0]), -17);
+	assert_ne!(call(&mut cpu, 222, &[map, 0x1000, 3, 0x22, u64::MAX, 0]), map as i64);
+
+	//file mappings get the file, then zeroes to the end of the page
+	let file = call(&mut cpu, 222, &[0, 0x1000, 1, 0x2, 3, 0]) as u64;
+	assert_eq!(cpu.bus.read_bytes(file, 8).unwrap(), b"hello\0\0\0");
+	assert!(readable(&mut cpu, file) && !writable(&mut cpu, file));
+	assert_eq!(call(&mut cpu, 222, &[0, 0x1000, 1, 0x2, 3, 1]), -22);
+	//lengths past the end of memory (or of the address space) are refused before anything is allocated
+	assert_eq!(call(&mut cpu, 222, &[0, 1 << 46, 1, 0x2, 3, 0]), -12);
+	assert_eq!(call(&mut cpu, 222, &[0, u64::MAX, 1, 0x2, 3, 0]), -12);
+	assert_eq!(call(&mut cpu, 222, &[0, u64::MAX - 0x800, 3, 0x22, u64::MAX, 0]), -12);
+	assert_eq!(call(&mut cpu, 222, &[0, 1 << 46, 1, 0x2, 99, 0]), -12);
+	assert_eq!(call(&mut cpu, 222, &[0, 0x1000, 1, 0x2, 99, 0]), -9);
+	assert_eq!(call(&mut cpu, 215, &[map, u64::MAX]), -22);
+	assert_eq!(call(&mut cpu, 226, &[map, u64::MAX, 1]), -12);
+	assert!(readable(&mut cpu, map));
+	assert_eq!(call(&mut cpu, 57, &[3]), 0);
+	std::fs::remove_file(&path).unwrap();
+
+	//the heap only exists up to the break
+	let brk = call(&mut cpu, 214, &[0]) as u64;
+	assert!(!readable(&mut cpu, brk));
+	assert_eq!(call(&mut cpu, 214, &[u64::MAX]), brk as i64);
+	assert_eq!(call(&mut cpu, 214, &[u64::MAX - 0x800]), brk as i64);
+	assert_eq!(call(&mut cpu, 214, &[brk + 0x2010]), brk as i64 + 0x2010);
+	assert!(writable(&mut cpu, brk) && writable(&mut cpu, brk + 0x2fff));
+	cpu.bus.store(brk + 0x1008, 0xff, 8).unwrap();
+	assert_eq!(call(&mut cpu, 214, &[brk + 0x1000]), brk as i64 + 0x1000);
+	assert!(!readable(&mut cpu, brk + 0x1000));
+	assert_eq!(call(&mut cpu, 214, &[brk + 0x2000]), brk as i64 + 0x2000);
+	assert_eq!(cpu.bus.load(brk + 0x1008, 8).unwrap(), 0);
+
+	//and the guest itself faults writing to read only memory
+	cpu.regs[10] = map;
+	cpu.step();
+	let fault = linux.service(&mut cpu).unwrap_err();
+	assert_eq!(fault, Fault { cause: 15, pc: 0x10000, tval: map });
+	assert_eq!(fault.signal(), 11);
+}
+
+#[test]
+fn newlib_test() {
+	let code: Vec<u8> = vec![
+		0x13, 0x05, 0x30, 0x00, //addi a0, zero, 3
+		0x93, 0x08, 0xd0, 0x05, //addi a7, zero, 93
+		0x73, 0x00, 0x00, 0x00, //ecall
+	];
+	let mut cpu = Cpu::new(code);
+	cpu.hostCalls = true;
+	let mut newlib = Newlib::New(DRAM_BASE + 12);
+	//the ecall doesnt trap, it just waits for the host to pick it up
+	cpu.step();
+	cpu.step();
+	cpu.step();
+	assert!(cpu.ecalled);
+	assert_eq!(cpu.pc, DRAM_BASE + 12);
+	assert_eq!(cpu.prv, privilege_level::M);
+	assert_eq!(cpu.load_csr(MCAUSE), 0);
+	assert_eq!(newlib.service(&mut cpu), Some(3));
+	assert!(!cpu.ecalled);
+	assert_eq!(newlib.service(&mut cpu), None);
+
+	let mut call = |cpu: &mut Cpu, nr: u64, args: &[u64]| {
+		cpu.regs[17] = nr;
+		for (i, &arg) in args.iter().enumerate() {
+			cpu.regs[10 + i] = arg;
+		}
+		assert_eq!(newlib.syscall(cpu), None);
+		cpu.regs[10] as i64
+	};
+	let buf = DRAM_BASE + 0x1000;
+	let path = std::env::temp_dir().join(format!("riscvEmu-newlib-{}", std::process::id()));
+	let mut name = path.to_string_lossy().into_owned().into_bytes();
+	name.push(0);
+	cpu.bus.write_bytes(buf, &name).unwrap();
+	cpu.bus.write_bytes(buf + 0x100, b"hi there").unwrap();
+
+	//newlib's own open flags: O_WRONLY | O_CREAT | O_TRUNC
+	assert_eq!(call(&mut cpu, 1024, &[buf, 0x601, 0o644]), 3);
+	assert_eq!(call(&mut cpu, 64, &[3, buf + 0x100, 8]), 8);
+	assert_eq!(call(&mut cpu, 57, &[3]), 0);
+	assert_eq!(call(&mut cpu, 57, &[3]), -9);
+	assert_eq!(std::fs::read(&path).unwrap(), b"hi there");
+	//and O_EXCL on something that exists is EEXIST, same number in newlib
+	assert_eq!(call(&mut cpu, 1024, &[buf, 0xa01, 0o644]), -17);
+
+	assert_eq!(call(&mut cpu, 56, &[-100i64 as u64, buf, 0, 0]), 3);
+	assert_eq!(call(&mut cpu, 62, &[3, 3, 0]), 3);
+	assert_eq!(call(&mut cpu, 63, &[3, buf + 0x200, 16]), 5);
+	assert_eq!(cpu.bus.read_bytes(buf + 0x200, 5).unwrap(), b"there");
+	assert_eq!(call(&mut cpu, 80, &[3, buf + 0x300]), 0);
+	assert_eq!(cpu.bus.load(buf + 0x300 + 48, 8).unwrap(), 8);
+	assert_eq!(call(&mut cpu, 63, &[3, 0x10, 16]), -14);
+	assert_eq!(call(&mut cpu, 57, &[3]), 0);
+	std::fs::remove_file(&path).unwrap();
+	assert_eq!(call(&mut cpu, 1024, &[buf, 0, 0]), -2);
+
+	assert_eq!(call(&mut cpu, 169, &[buf + 0x400, 0]), 0);
+	assert!(cpu.bus.load(buf + 0x400, 8).unwrap() > 0);
+	assert!(cpu.bus.load(buf + 0x408, 8).unwrap() < 1_000_000);
+
+	//the heap goes from the end of the program up to the stack
+	assert_eq!(call(&mut cpu, 214, &[0]), DRAM_BASE as i64 + 12);
+	assert_eq!(call(&mut cpu, 214, &[DRAM_BASE + 0x10_0000]), DRAM_BASE as i64 + 0x10_0000);
+	let sp = cpu.regs[2];
+	assert_eq!(call(&mut cpu, 214, &[sp]), DRAM_BASE as i64 + 0x10_0000);
+
+	assert_eq!(call(&mut cpu, 9999, &[]), -88);
+}
+
+#[test]
+fn semihosting_test() {
+	let code: Vec<u8> = vec![
+		0x13, 0x05, 0x10, 0x01, //addi a0, zero, 0x11
+		0x13, 0x10, 0xf0, 0x01, //slli zero, zero, 0x1f
+		0x73, 0x00, 0x10, 0x00, //ebreak
+		0x13, 0x50, 0x70, 0x40, //srai zero, zero, 7
+		0x73, 0x00, 0x10, 0x00, //ebreak
+	];
+	let mut cpu = Cpu::new(code.clone());
+	cpu.semihosting = true;
+	let mut semihost = Semihost::New(&["prog".to_string(), "one".to_string(), "two".to_string()]);
+	for _ in 0..3 {
+		cpu.step();
+	}
+	assert!(cpu.semihostCalled);
+	assert_eq!(cpu.pc, DRAM_BASE + 12);
+	assert_eq!(semihost.service(&mut cpu), None);
+	assert!(cpu.regs[10] > 1_000_000_000);
+	//the srai is a nop, and an ebreak on its own is still a breakpoint
+	cpu.step();
+	assert_eq!(cpu.pc, DRAM_BASE + 16);
+	cpu.step();
+	assert!(!cpu.semihostCalled);
+	assert_eq!(cpu.load_csr(MCAUSE), 3);
+	assert_eq!(cpu.load_csr(MEPC), DRAM_BASE + 16);
+
+	//and without --semihosting the whole sequence is just a breakpoint
+	let mut cpu = Cpu::new(code);
+	for _ in 0..3 {
+		cpu.step();
+	}
+	assert!(!cpu.semihostCalled);
+	assert_eq!(cpu.load_csr(MCAUSE), 3);
+
+	let mut call = |cpu: &mut Cpu, op: u64, block: &[u64]| {
+		let at = DRAM_BASE + 0x1000;
+		for (i, &val) in block.iter().enumerate() {
+			cpu.bus.store(at + 8 * i as u64, val, 8).unwrap();
+		}
+		cpu.regs[10] = op;
+		cpu.regs[11] = at;
+		assert_eq!(semihost.call(cpu), None);
+		cpu.regs[10] as i64
+	};
+	let name = DRAM_BASE + 0x2000;
+	let data = DRAM_BASE + 0x3000;
+	let path = std::env::temp_dir().join(format!("riscvEmu-semihost-{}", std::process::id()));
+	let path = path.to_string_lossy().into_owned();
+	cpu.bus.write_bytes(name, path.as_bytes()).unwrap();
+	let len = path.len() as u64;
+	cpu.bus.write_bytes(data, b"hello world").unwrap();
+
+	//"w", then "r". Handles start at 1
+	assert_eq!(call(&mut cpu, 0x01, &[name, 4, len]), 1);
+	assert_eq!(call(&mut cpu, 0x05, &[1, data, 11]), 0);
+	assert_eq!(call(&mut cpu, 0x02, &[1]), 0);
+	assert_eq!(call(&mut cpu, 0x02, &[1]), -1);
+	assert_eq!(call(&mut cpu, 0x13, &[]), 9);
+	assert_eq!(std::fs::read(&path).unwrap(), b"hello world");
+	assert_eq!(call(&mut cpu, 0x01, &[name, 0, len]), 1);
+	assert_eq!(call(&mut cpu, 0x0c, &[1]), 11);
+	assert_eq!(call(&mut cpu, 0x09, &[1]), 0);
+	assert_eq!(call(&mut cpu, 0x0a, &[1, 6]), 0);
+	//reads give back how many bytes they were short
+	assert_eq!(call(&mut cpu, 0x06, &[1, data + 0x100, 16]), 11);
+	assert_eq!(cpu.bus.read_bytes(data + 0x100, 5).unwrap(), b"world");
+	assert_eq!(call(&mut cpu, 0x06, &[1, data + 0x100, 16]), 16);
+	assert_eq!(call(&mut cpu, 0x02, &[1]), 0);
+
+	let renamed = format!("{}-renamed", path);
+	cpu.bus.write_bytes(name + 0x100, renamed.as_bytes()).unwrap();
+	assert_eq!(call(&mut cpu, 0x0f, &[name, len, name + 0x100, renamed.len() as u64]), 0);
+	assert_eq!(call(&mut cpu, 0x01, &[name, 0, len]), -1);
+	assert_eq!(call(&mut cpu, 0x13, &[]), 2);
+	assert_eq!(call(&mut cpu, 0x08, &[-1i64 as u64]), 1);
+	assert_eq!(call(&mut cpu, 0x08, &[3]), 0);
+	assert_eq!(call(&mut cpu, 0x0e, &[name + 0x100, renamed.len() as u64]), 0);
+	assert!(!std::path::Path::new(&renamed).exists());
+	assert_eq!(call(&mut cpu, 0x01, &[name, 12, len]), -1);
+
+	//the command line length gets written back into the block
+	assert_eq!(call(&mut cpu, 0x15, &[data, 64]), 0);
+	assert_eq!(cpu.bus.read_bytes(data, 13).unwrap(), b"prog one two\0");
+	assert_eq!(cpu.bus.load(DRAM_BASE + 0x1008, 8).unwrap(), 12);
+	assert_eq!(call(&mut cpu, 0x15, &[data, 12]), -1);
+	assert_eq!(call(&mut cpu, 0x0d, &[data, 7, 256]), 0);
+	assert_ne!(cpu.bus.load(data, 1).unwrap(), 0);
+	assert_eq!(call(&mut cpu, 0x31, &[]), 1_000_000);
+	assert_eq!(call(&mut cpu, 0x12, &[]), -1);
+	assert_eq!(call(&mut cpu, 0x99, &[]), -1);
+
+	//normal exits give their code, anything else is a crash
+	let mut exit = |cpu: &mut Cpu, op: u64, arg: u64, block: &[u64]| {
+		for (i, &val) in block.iter().enumerate() {
+			cpu.bus.store(data + 8 * i as u64, val, 8).unwrap();
+		}
+		cpu.regs[10] = op;
+		cpu.regs[11] = arg;
+		semihost.call(cpu)
+	};
+	assert_eq!(exit(&mut cpu, 0x18, data, &[0x20026, 5]), Some(5));
+	assert_eq!(exit(&mut cpu, 0x20, data, &[0x20026, 0]), Some(0));
+	assert_eq!(exit(&mut cpu, 0x18, data, &[0x20023, 0]), Some(1));
+	assert_eq!(exit(&mut cpu, 0x18, 0x20026, &[]), Some(0));
 }
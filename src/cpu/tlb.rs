@@ -0,0 +1,96 @@
+//software tlb
+//caches leaf ptes from page table walks so that most accesses dont have to walk
+//entries are keyed by asid + 4K virtual page number. Superpages get one entry per
+//4K page thats actually used, since thats simpler than looking up every page size
+//
+//like a real tlb this isnt kept coherent with memory - software has to sfence.vma
+//after changing page tables
+
+use std::collections::HashMap;
+
+//the asid global entries are filed under, so they match every address space
+const GLOBAL_ASID: u32 = u32::MAX;
+//once its this full just throw everything away rather than bothering with replacement
+const TLB_CAPACITY: usize = 4096;
+
+#[derive(Debug)]
+#[derive(Copy, Clone)]
+pub struct TlbEntry {
+	pub pte: u64,
+	pub level: u64, //0 = 4K page, 1 = megapage, ...
+	pub paddr: u64, //physical address of the start of the (super)page
+}
+
+impl TlbEntry {
+	//size of the page this entry maps, minus 1
+	fn offset_mask(&self) -> u64 {
+		(1u64 << (12 + 9 * self.level)) - 1
+	}
+
+	pub fn translate(&self, vaddr: u64) -> u64 {
+		self.paddr | (vaddr & self.offset_mask())
+	}
+
+	//whether this entry maps vaddr, given the 4K page it was filed under
+	fn maps(&self, vpn: u64, vaddr: u64) -> bool {
+		((vpn << 12) & !self.offset_mask()) == (vaddr & !self.offset_mask())
+	}
+}
+
+pub struct Tlb {
+	entries: HashMap<(u32, u64), TlbEntry>,
+	pub hits: u64,
+	pub misses: u64,
+	pub flushes: u64,
+}
+
+impl Tlb {
+	pub fn New() -> Tlb {
+		Tlb {
+			entries: HashMap::new(),
+			hits: 0,
+			misses: 0,
+			flushes: 0,
+		}
+	}
+
+	pub fn lookup(&self, asid: u16, vaddr: u64) -> Option<TlbEntry> {
+		let vpn = vaddr >> 12;
+		self.entries.get(&(asid as u32, vpn))
+			.or_else(|| self.entries.get(&(GLOBAL_ASID, vpn)))
+			.copied()
+	}
+
+	pub fn insert(&mut self, asid: u16, global: bool, vaddr: u64, entry: TlbEntry) {
+		if self.entries.len() >= TLB_CAPACITY {
+			self.entries.clear();
+		}
+		let asid = if global { GLOBAL_ASID } else { asid as u32 };
+		self.entries.insert((asid, vaddr >> 12), entry);
+	}
+
+	//sfence.vma
+	//no address means every page, no asid means every address space (and global pages)
+	//global pages only go away when no asid is given
+	pub fn flush(&mut self, vaddr: Option<u64>, asid: Option<u16>) {
+		self.flushes += 1;
+		match (vaddr, asid) {
+			(None, None) => self.entries.clear(),
+			_ => self.entries.retain(|&(entryAsid, vpn), entry| {
+				let asidMatch = match asid {
+					Some(asid) => entryAsid == asid as u32,
+					None => true,
+				};
+				let addrMatch = match vaddr {
+					Some(vaddr) => entry.maps(vpn, vaddr),
+					None => true,
+				};
+				!(asidMatch && addrMatch)
+			}),
+		}
+	}
+
+	pub fn len(&self) -> usize {
+		self.entries.len()
+	}
+}
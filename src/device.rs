@@ -0,0 +1,32 @@
+//what a memory mapped peripheral has to look like to sit on the bus
+//the bus hands each device offsets relative to where it was mapped, so a device
+//doesnt care what address it lives at
+//
+//interrupt lines go to the plic: a device says which source its wired to with irq(),
+//and the bus copies irq_pending() across every tick
+//
+//devices have to be Send, since with --threads the bus they sit on is shared between threads
+
+use std::any::Any;
+
+pub trait Device: Any + Send {
+	//reads can have side effects (popping a fifo, claiming an interrupt), so these get &mut
+	fn load(&mut self, offset: u64, size: u8) -> u64;
+	fn store(&mut self, offset: u64, data: u64, size: u8);
+
+	//back to the state it powers on in
+	fn reset(&mut self) {}
+
+	//called once per instruction
+	fn tick(&mut self) {}
+
+	//plic source this device is wired to, if any
+	fn irq(&self) -> Option<usize> {
+		None
+	}
+
+	//whether the interrupt line is being held high
+	fn irq_pending(&self) -> bool {
+		false
+	}
+}
@@ -1,63 +1,194 @@
 #![allow(non_snake_case)]
+//memory is kept as atomic doublewords so harts on different threads can share it
+//plain loads and stores are relaxed, since riscv doesnt promise any ordering without
+//a fence or an aq/rl bit. AMOs and sc are whole doubleword compare and swaps, so they
+//stay atomic even when they only change part of one
+//
+//sc has to fail if anything was stored to its doubleword since the lr, even a store that
+//put the same value back. So every doubleword has a version that stores bump, shared with
+//the other doublewords that land on the same slot of a fixed table. It works like a seqlock:
+//odd means a store is halfway through, lr remembers the even version it read under, and sc
+//only stores if it can take the version from exactly that. Two doublewords sharing a
+//version just means the odd sc fails for no reason, which riscv allows
+//
+//doublewords are little endian like everything else, byte 0 of memory is the bottom
+//byte of word 0
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::hint;
+use std::sync::atomic::{self, AtomicU64, Ordering};
+
+//default memory map. Same place QEMU virt and Spike put DRAM
+pub const DRAM_BASE: u64 = 0x8000_0000;
+pub const DRAM_SIZE: u64 = 128*1024*1024;
+
+//slots in the version table
+const VERSIONS: usize = 4096;
 
 pub struct Dram {
-	pub dram : Vec<u8>,
+	words: Arc<[AtomicU64]>,
+	versions: Arc<[AtomicU64]>, //see the top
+	size: u64, //in bytes. The last word can hang off the end
+	pub base: u64, //physical address the first byte of dram lives at
 }
 
+//mask for the bottom size bytes of a doubleword
+fn mask(size: u8) -> u64 {
+	if size >= 8 { u64::MAX } else { (1u64 << (size as u64 * 8)) - 1 }
+}
 
 impl Dram {
-	pub fn New(code: Vec<u8>) -> Dram {
-		let mut mem = Self {
-			dram: vec![0; 1024*1024],
+	pub fn New(code: Vec<u8>, base: u64, size: u64) -> Dram {
+		//zeroed straight from the allocator, so untouched memory doesnt cost anything
+		let words = unsafe { Arc::<[AtomicU64]>::new_zeroed_slice(size.div_ceil(8) as usize).assume_init() };
+		let mem = Self {
+			words,
+			versions: (0..VERSIONS).map(|_| AtomicU64::new(0)).collect(),
+			size,
+			base,
 		};
-		//funky splice method
-		//good for replacing subsection of a vector
-		//takes a range (usually a lloop, but could be every second)
-		//and takes an iterator (or smn that can go into one)
-		//needs .cloned() because it needs direct reference? Like, T instead of &T??
-		//idk
-		mem.dram.splice(..code.len(), code.iter().cloned());
+		mem.write_bytes(0, &code);
 		mem
 	}
 
-	pub fn load(&self, addr: u64, size: u8) -> u64 {
-		//Idea
-		//addr as type generic
-		//dynamically find its size
-		//loop for i in 0..size, grabbing a byte each time
-		//after loop has finished, just take bottom 8 bytes
-		//then assort a u64 from the array (padding if less than 8 bytes)
-		//then return u64
-		//then dont need like, a function per integer size
-		let addrInd = addr as usize;
-		let mut sizeBound: usize  = size as usize;
-		if size > 8 {
-			sizeBound = 8;
+	//another handle on the same memory, for a hart running on another thread
+	pub fn share(&self) -> Dram {
+		Self {
+			words: self.words.clone(),
+			versions: self.versions.clone(),
+			size: self.size,
+			base: self.base,
+		}
+	}
+
+	pub fn size(&self) -> u64 {
+		self.size
+	}
+
+	//whether the whole access [addr, addr+size) falls inside dram
+	pub fn contains(&self, addr: u64, size: u64) -> bool {
+		addr >= self.base && (addr - self.base).checked_add(size).is_some_and(|end| end <= self.size())
+	}
+
+	//the word an access starts in, and how far into it. None if it spills into the next word
+	fn word(&self, offset: u64, size: u8) -> Option<(&AtomicU64, u64)> {
+		let shift = (offset % 8) * 8;
+		if offset % 8 + size as u64 > 8 {
+			return None;
+		}
+		Some((&self.words[(offset / 8) as usize], shift))
+	}
+
+	//version of the doubleword at offset
+	fn version(&self, offset: u64) -> &AtomicU64 {
+		&self.versions[(offset / 8) as usize % VERSIONS]
+	}
+
+	//runs a store to the doubleword at offset with its version held, so no lr or sc
+	//gets in the middle of it, and bumps the version after
+	fn stored<T>(&self, offset: u64, store: impl FnOnce() -> T) -> T {
+		let version = self.version(offset);
+		let mut v = version.load(Ordering::Relaxed);
+		while v & 1 != 0 || version.compare_exchange_weak(v, v + 1, Ordering::Acquire, Ordering::Relaxed).is_err() {
+			hint::spin_loop();
+			v = version.load(Ordering::Relaxed);
+		}
+		let result = store();
+		version.store(v + 2, Ordering::Release);
+		result
+	}
+
+	//offsets are relative to the start of dram, the bus does the translating
+	pub fn load(&self, offset: u64, size: u8) -> u64 {
+		let size = size.min(8);
+		match self.word(offset, size) {
+			Some((word, shift)) => (word.load(Ordering::Relaxed) >> shift) & mask(size),
+			//misaligned across two words, a byte at a time
+			None => (0..size as u64).fold(0, |data, i| data | (self.load(offset + i, 1) << (8 * i))),
 		}
-		let mut bytes : [u8;8] = [0;8];
+	}
 
-		for i in (0..(sizeBound)) {
-			bytes[i] = (self.dram[addrInd + i ]);
+	pub fn store(&self, offset: u64, data: u64, size: u8) {
+		let size = size.min(8);
+		match self.word(offset, size) {
+			Some((word, _)) if size == 8 => self.stored(offset, || word.store(data, Ordering::Relaxed)),
+			Some(_) => {
+				self.fetch_update(offset, size, |_| data);
+			},
+			None => {
+				for i in 0..size as u64 {
+					self.store(offset + i, data >> (8 * i), 1);
+				}
+			},
 		}
+	}
+
+	//atomically replaces size bytes with op(old), handing back old. Has to sit inside
+	//one doubleword, which anything naturally aligned does
+	pub fn fetch_update(&self, offset: u64, size: u8, op: impl Fn(u64) -> u64) -> u64 {
+		self.stored(offset, || self.update(offset, size, op))
+	}
 
-		let instCode = u64::from_le_bytes(bytes);
-		
-		instCode
+	//fetch_update without touching the version
+	fn update(&self, offset: u64, size: u8, op: impl Fn(u64) -> u64) -> u64 {
+		let (word, shift) = self.word(offset, size).expect("atomic access across doublewords");
+		let fieldMask = mask(size) << shift;
+		let old = word.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |w| {
+			Some((w & !fieldMask) | ((op((w & fieldMask) >> shift) << shift) & fieldMask))
+		});
+		//the closure never gives up, so this is always Ok
+		(old.unwrap_or_else(|w| w) & fieldMask) >> shift
 	}
 
-	//tried to make this generic being have data be any type T
-	//but then it had to be able to be bitshifted by a u8 - thats fine
-	//but then is had to be able to be coerced into a u8 - but not T, but the results of T after being bitshifted
-	//how the hell does anyone do anything useful in a function with generics
-	pub fn store(&mut self, addr: u64, data: u64, size: u8) {
-		let addrInd = addr as usize;
-		let mut tmpSize = size;
-		if tmpSize > 8 {
-			tmpSize = 8;
+	//lr. Gives back what was read and the version it was read under, which is what sc needs
+	pub fn load_reserved(&self, offset: u64, size: u8) -> (u64, u64) {
+		let version = self.version(offset);
+		loop {
+			let v = version.load(Ordering::Acquire);
+			if v & 1 == 0 {
+				let data = self.load(offset, size);
+				//nobody stored while we were reading
+				atomic::fence(Ordering::Acquire);
+				if version.load(Ordering::Relaxed) == v {
+					return (data, v);
+				}
+			}
+			hint::spin_loop();
 		}
-		for i in (0..(tmpSize as usize)) {
-			self.dram[addrInd + i] = (data >> (i*8) & 0xff) as u8;
-		//	println!(" mem val stored is {:x}", self.dram[addrInd + i]);
+	}
+
+	//sc. Stores data only if nothing has been stored to the doubleword since the lr that
+	//read version. Whether it did
+	pub fn store_conditional(&self, offset: u64, size: u8, version: u64, data: u64) -> bool {
+		let lock = self.version(offset);
+		if lock.compare_exchange(version, version + 1, Ordering::Acquire, Ordering::Relaxed).is_err() {
+			return false;
+		}
+		self.update(offset, size, |_| data);
+		lock.store(version + 2, Ordering::Release);
+		true
+	}
+
+	//copies len bytes out, for things outside the guest that want a buffer from it
+	pub fn read_bytes(&self, offset: u64, len: u64) -> Vec<u8> {
+		(0..len).map(|i| self.load(offset + i, 1) as u8).collect()
+	}
+
+	//for copying whole program images in. Whole doublewords go in one go
+	pub fn write_bytes(&self, offset: u64, bytes: &[u8]) {
+		let mut i = 0;
+		while i < bytes.len() {
+			let at = offset + i as u64;
+			if at.is_multiple_of(8) && bytes.len() - i >= 8 {
+				let chunk: [u8; 8] = bytes[i..i + 8].try_into().unwrap();
+				self.store(at, u64::from_le_bytes(chunk), 8);
+				i += 8;
+			}
+			else {
+				self.store(at, bytes[i] as u64, 1);
+				i += 1;
+			}
 		}
 	}
 
@@ -0,0 +1,173 @@
+//ELF64 loader
+//only cares about what we need to get a program running:
+//the header (to check its actually a riscv executable), the entry point
+//and the PT_LOAD segments from the program headers
+//Everything is little endian since thats all the emulator supports
+
+use std::io;
+
+use crate::cpu::Cpu;
+
+const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+const ELFCLASS64: u8 = 2;
+const ELFDATA2LSB: u8 = 1;
+const ET_EXEC: u16 = 2;
+const EM_RISCV: u16 = 243;
+const PT_LOAD: u32 = 1;
+//segment permissions
+pub const PF_X: u32 = 1;
+pub const PF_W: u32 = 2;
+pub const PF_R: u32 = 4;
+
+//size of the ELF64 file header and a single program header
+const EHDR_SIZE: usize = 64;
+const PHDR_SIZE: usize = 56;
+
+//a loadable chunk of the program
+#[derive(Debug)]
+pub struct Segment {
+	pub paddr: u64,
+	pub vaddr: u64, //where a user process sees it. Usually the same as paddr
+	pub memsz: u64, //can be bigger than data - rest is .bss and is zero filled
+	pub flags: u32, //PF_R/PF_W/PF_X, what a user process is allowed to do with it
+	pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub struct Elf {
+	pub entry: u64,
+	pub segments: Vec<Segment>,
+	pub phdr: u64, //virtual address the program headers get loaded to, 0 if they dont
+	pub phnum: u64,
+	pub phentsize: u64,
+}
+
+fn invalid(msg: String) -> io::Error {
+	io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+//helpers for pulling little endian values out of the file
+//bounds are checked by the caller
+fn read_u16(bytes: &[u8], off: usize) -> u16 {
+	u16::from_le_bytes([bytes[off], bytes[off + 1]])
+}
+
+fn read_u32(bytes: &[u8], off: usize) -> u32 {
+	let mut raw = [0u8; 4];
+	raw.copy_from_slice(&bytes[off..off + 4]);
+	u32::from_le_bytes(raw)
+}
+
+fn read_u64(bytes: &[u8], off: usize) -> u64 {
+	let mut raw = [0u8; 8];
+	raw.copy_from_slice(&bytes[off..off + 8]);
+	u64::from_le_bytes(raw)
+}
+
+//checks for the elf magic number. Anything else gets treated as a flat binary
+pub fn is_elf(bytes: &[u8]) -> bool {
+	bytes.len() >= 4 && bytes[..4] == ELF_MAGIC
+}
+
+impl Elf {
+	pub fn parse(bytes: &[u8]) -> io::Result<Elf> {
+		if !is_elf(bytes) {
+			return Err(invalid("not an ELF file (bad magic number)".to_string()));
+		}
+		if bytes.len() < EHDR_SIZE {
+			return Err(invalid("ELF file is too small to hold a header".to_string()));
+		}
+		if bytes[4] != ELFCLASS64 {
+			return Err(invalid(format!("ELF class {} is not supported, only ELF64 is", bytes[4])));
+		}
+		if bytes[5] != ELFDATA2LSB {
+			return Err(invalid("ELF file is not little endian".to_string()));
+		}
+		let e_type = read_u16(bytes, 16);
+		let e_machine = read_u16(bytes, 18);
+		if e_machine != EM_RISCV {
+			return Err(invalid(format!("ELF machine {} is not RISC-V ({})", e_machine, EM_RISCV)));
+		}
+		if e_type != ET_EXEC {
+			return Err(invalid(format!("ELF type {} is not supported, only executables are", e_type)));
+		}
+
+		let entry = read_u64(bytes, 24);
+		let phoff = read_u64(bytes, 32) as usize;
+		let phentsize = read_u16(bytes, 54) as usize;
+		let phnum = read_u16(bytes, 56) as usize;
+		if phentsize < PHDR_SIZE {
+			return Err(invalid(format!("ELF program header size {} is too small", phentsize)));
+		}
+
+		let mut segments = Vec::new();
+		let mut phdr = 0;
+		for i in 0..phnum {
+			let off = phoff.saturating_add(i * phentsize);
+			if off.saturating_add(PHDR_SIZE) > bytes.len() {
+				return Err(invalid(format!("ELF program header {} is past the end of the file", i)));
+			}
+			if read_u32(bytes, off) != PT_LOAD {
+				continue;
+			}
+			let fileOff = read_u64(bytes, off + 8) as usize;
+			let vaddr = read_u64(bytes, off + 16);
+			let paddr = read_u64(bytes, off + 24);
+			let filesz = read_u64(bytes, off + 32) as usize;
+			let memsz = read_u64(bytes, off + 40);
+			let flags = read_u32(bytes, off + 4);
+			if fileOff.checked_add(filesz).is_none_or(|end| end > bytes.len()) {
+				return Err(invalid(format!("ELF segment {} is past the end of the file", i)));
+			}
+			if (filesz as u64) > memsz {
+				return Err(invalid(format!("ELF segment {} has a file size bigger than its memory size", i)));
+			}
+			//whichever segment covers the program headers in the file has them in memory too
+			let phEnd = phoff.saturating_add(phnum * phentsize);
+			if phdr == 0 && fileOff <= phoff && phEnd <= fileOff + filesz {
+				phdr = vaddr + (phoff - fileOff) as u64;
+			}
+			segments.push(Segment {
+				paddr,
+				vaddr,
+				memsz,
+				flags,
+				data: bytes[fileOff..fileOff + filesz].to_vec(),
+			});
+		}
+
+		Ok(Elf {
+			entry,
+			segments,
+			phdr,
+			phnum: phnum as u64,
+			phentsize: phentsize as u64,
+		})
+	}
+
+	//copies every segment into memory at its physical address
+	//and points the cpu at the entry point
+	pub fn load(&self, cpu: &mut Cpu) -> io::Result<()> {
+		self.place(cpu, |seg| seg.paddr)
+	}
+
+	//same but at the virtual addresses, for user programs. Their pages map one to one
+	pub fn load_user(&self, cpu: &mut Cpu) -> io::Result<()> {
+		self.place(cpu, |seg| seg.vaddr)
+	}
+
+	fn place(&self, cpu: &mut Cpu, at: impl Fn(&Segment) -> u64) -> io::Result<()> {
+		for seg in &self.segments {
+			let addr = at(seg);
+			if !cpu.bus.dram.contains(addr, seg.memsz) {
+				return Err(invalid(format!("ELF segment at {:#x} ({:#x} bytes) doesnt fit in memory", addr, seg.memsz)));
+			}
+			//.bss - memory may not be fresh so zero it explicitly
+			let mut image = seg.data.clone();
+			image.resize(seg.memsz as usize, 0);
+			cpu.bus.write_bytes(addr, &image).map_err(|e| invalid(format!("ELF segment load failed: {:x?}", e)))?;
+		}
+		cpu.pc = self.entry;
+		Ok(())
+	}
+}
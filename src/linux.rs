@@ -0,0 +1,814 @@
+//linux user mode emulation, like qemu-user
+//runs a statically linked riscv linux executable as if it were a process on the host.
+//The guest runs in U mode under page tables that map its pages one to one (see mm.rs),
+//and there is no kernel in memory. Every trap comes back here instead: ecalls are linux
+//system calls and get serviced on the host, anything else is a crash
+//
+//memory is one chunk of dram. The program goes where its segments ask, the heap (brk)
+//grows up from the end of it, the stack sits at the top, and mmaps are put in the
+//highest gap below the stack that fits. Pages only exist once something maps them, and
+//touching them against their protection is a segfault
+//
+//syscall numbers and struct layouts are the asm-generic ones riscv64 linux uses.
+//Errors go back as -errno in a0. The host is linux too, so its errnos pass straight through
+#![allow(non_snake_case)]
+
+use std::convert::TryInto;
+use std::env;
+use std::fmt;
+use std::fs::{self, File, Metadata, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::{AsFd, AsRawFd};
+use std::os::unix::fs::{FileExt, MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::cpu::{Cpu, privilege_level};
+use crate::elf::{Elf, PF_R, PF_W, PF_X};
+use crate::mm::{self, AddressSpace, PROT_READ, PROT_WRITE, PROT_EXEC};
+use crate::cpu::AccessType;
+use crate::regs::*;
+
+//where dram goes by default. Page 0 is left out so null pointers crash
+pub const USER_BASE: u64 = 0x1000;
+
+use crate::cpu::PAGE_SIZE;
+const STACK_SIZE: u64 = 8 * 1024 * 1024;
+
+const SYS_GETCWD: u64 = 17;
+const SYS_DUP: u64 = 23;
+const SYS_DUP3: u64 = 24;
+const SYS_FCNTL: u64 = 25;
+const SYS_IOCTL: u64 = 29;
+const SYS_MKDIRAT: u64 = 34;
+const SYS_UNLINKAT: u64 = 35;
+const SYS_FACCESSAT: u64 = 48;
+const SYS_OPENAT: u64 = 56;
+const SYS_CLOSE: u64 = 57;
+const SYS_LSEEK: u64 = 62;
+const SYS_READ: u64 = 63;
+const SYS_WRITE: u64 = 64;
+const SYS_READV: u64 = 65;
+const SYS_WRITEV: u64 = 66;
+const SYS_READLINKAT: u64 = 78;
+const SYS_NEWFSTATAT: u64 = 79;
+const SYS_FSTAT: u64 = 80;
+const SYS_EXIT: u64 = 93;
+const SYS_EXIT_GROUP: u64 = 94;
+const SYS_SET_TID_ADDRESS: u64 = 96;
+const SYS_FUTEX: u64 = 98;
+const SYS_SET_ROBUST_LIST: u64 = 99;
+const SYS_CLOCK_GETTIME: u64 = 113;
+const SYS_SCHED_YIELD: u64 = 124;
+const SYS_KILL: u64 = 129;
+const SYS_TKILL: u64 = 130;
+const SYS_TGKILL: u64 = 131;
+const SYS_SIGALTSTACK: u64 = 132;
+const SYS_RT_SIGACTION: u64 = 134;
+const SYS_RT_SIGPROCMASK: u64 = 135;
+const SYS_UNAME: u64 = 160;
+const SYS_GETTIMEOFDAY: u64 = 169;
+const SYS_GETPID: u64 = 172;
+const SYS_GETPPID: u64 = 173;
+const SYS_GETUID: u64 = 174;
+const SYS_GETEUID: u64 = 175;
+const SYS_GETGID: u64 = 176;
+const SYS_GETEGID: u64 = 177;
+const SYS_GETTID: u64 = 178;
+const SYS_BRK: u64 = 214;
+const SYS_MUNMAP: u64 = 215;
+const SYS_MMAP: u64 = 222;
+const SYS_MPROTECT: u64 = 226;
+const SYS_MADVISE: u64 = 233;
+const SYS_PRLIMIT64: u64 = 261;
+const SYS_GETRANDOM: u64 = 278;
+
+const EBADF: i64 = 9;
+const EAGAIN: i64 = 11;
+const ENOMEM: i64 = 12;
+const EFAULT: i64 = 14;
+const EEXIST: i64 = 17;
+const EINVAL: i64 = 22;
+const ENOTTY: i64 = 25;
+const ERANGE: i64 = 34;
+const ENOSYS: i64 = 38;
+
+const AT_FDCWD: i64 = -100;
+const AT_SYMLINK_NOFOLLOW: u64 = 0x100;
+const AT_REMOVEDIR: u64 = 0x200;
+const AT_EMPTY_PATH: u64 = 0x1000;
+
+const O_ACCMODE: u64 = 0o3;
+const O_CREAT: u64 = 0o100;
+const O_EXCL: u64 = 0o200;
+const O_TRUNC: u64 = 0o1000;
+const O_APPEND: u64 = 0o2000;
+
+const MAP_FIXED: u64 = 0x10;
+const MAP_FIXED_NOREPLACE: u64 = 0x100000;
+const MAP_ANONYMOUS: u64 = 0x20;
+
+const F_DUPFD: u64 = 0;
+const F_DUPFD_CLOEXEC: u64 = 1030;
+
+const CLOCK_REALTIME: u64 = 0;
+const CLOCK_REALTIME_COARSE: u64 = 5;
+
+const RLIMIT_STACK: u64 = 3;
+const RLIMIT_NOFILE: u64 = 7;
+const MAX_FILES: u64 = 1024;
+const IOV_MAX: u64 = 1024;
+
+const AT_NULL: u64 = 0;
+const AT_PHDR: u64 = 3;
+const AT_PHENT: u64 = 4;
+const AT_PHNUM: u64 = 5;
+const AT_PAGESZ: u64 = 6;
+const AT_BASE: u64 = 7;
+const AT_FLAGS: u64 = 8;
+const AT_ENTRY: u64 = 9;
+const AT_UID: u64 = 11;
+const AT_EUID: u64 = 12;
+const AT_GID: u64 = 13;
+const AT_EGID: u64 = 14;
+const AT_HWCAP: u64 = 16;
+const AT_PLATFORM: u64 = 15;
+const AT_CLKTCK: u64 = 17;
+const AT_SECURE: u64 = 23;
+const AT_RANDOM: u64 = 25;
+const AT_HWCAP2: u64 = 26;
+const AT_EXECFN: u64 = 31;
+
+//the letters in misa, one bit each, same as linux reports them
+const HWCAP: u64 = ext(b'i') | ext(b'm') | ext(b'a') | ext(b'f') | ext(b'd') | ext(b'c');
+
+const fn ext(letter: u8) -> u64 {
+	1 << (letter - b'a')
+}
+
+//a trap out of the guest that wasnt a syscall. Theres no kernel to hand it to,
+//so it ends the program like the signal linux would have sent
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct Fault {
+	pub cause: u64,
+	pub pc: u64,
+	pub tval: u64,
+}
+
+impl Fault {
+	//the signal linux would kill the process with
+	pub fn signal(&self) -> i32 {
+		match self.cause {
+			2 => 4, //SIGILL
+			3 => 5, //SIGTRAP
+			0 | 4 | 6 => 7, //SIGBUS
+			_ => 11, //SIGSEGV
+		}
+	}
+}
+
+impl fmt::Display for Fault {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let what = match self.cause {
+			0 => "misaligned jump",
+			1 | 12 => "bad instruction fetch",
+			2 => "illegal instruction",
+			3 => "breakpoint",
+			4 => "misaligned load",
+			5 | 13 => "bad load",
+			6 => "misaligned store",
+			7 | 15 => "bad store",
+			_ => "trap",
+		};
+		write!(f, "{} at pc {:#x} (address/instruction {:#x})", what, self.pc, self.tval)
+	}
+}
+
+pub struct Linux {
+	files: Vec<Option<File>>, //host file behind each guest fd
+	exe: String, //what /proc/self/exe points to
+	mm: AddressSpace,
+	brkStart: u64,
+	brk: u64,
+	mmapTop: u64, //bottom of the stack. mmaps go below here
+	start: Instant, //for the clocks that dont follow wall time
+}
+
+//guest pages are mapped one to one, so once the mmu says the guest could do the access
+//itself everything goes straight to the bus
+fn read_mem(cpu: &mut Cpu, addr: u64, len: u64) -> Result<Vec<u8>, i64> {
+	if !mm::accessible(cpu, addr, len, AccessType::Load) {
+		return Err(EFAULT);
+	}
+	cpu.bus.read_bytes(addr, len).map_err(|_| EFAULT)
+}
+
+fn write_mem(cpu: &mut Cpu, addr: u64, bytes: &[u8]) -> Result<(), i64> {
+	if !mm::accessible(cpu, addr, bytes.len() as u64, AccessType::Store) {
+		return Err(EFAULT);
+	}
+	cpu.bus.write_bytes(addr, bytes).map_err(|_| EFAULT)
+}
+
+//nul terminated string, as long as a path can be
+fn read_str(cpu: &mut Cpu, addr: u64) -> Result<String, i64> {
+	let mut bytes = Vec::new();
+	for i in 0..4096 {
+		let at = addr.wrapping_add(i);
+		if (i == 0 || at.is_multiple_of(PAGE_SIZE)) && !mm::accessible(cpu, at, 1, AccessType::Load) {
+			return Err(EFAULT);
+		}
+		let b = cpu.bus.load(at, 1).map_err(|_| EFAULT)? as u8;
+		if b == 0 {
+			return Ok(String::from_utf8_lossy(&bytes).into_owned());
+		}
+		bytes.push(b);
+	}
+	Err(ERANGE)
+}
+
+fn errno(e: io::Error) -> i64 {
+	e.raw_os_error().map_or(EINVAL, |e| e as i64)
+}
+
+pub fn push64(buf: &mut Vec<u8>, val: u64) {
+	buf.extend_from_slice(&val.to_le_bytes());
+}
+
+fn push32(buf: &mut Vec<u8>, val: u32) {
+	buf.extend_from_slice(&val.to_le_bytes());
+}
+
+//None if it would go past the top of the address space. Guests can ask for anything
+fn page_up(addr: u64) -> Option<u64> {
+	addr.checked_next_multiple_of(PAGE_SIZE)
+}
+
+fn segment_prot(flags: u32) -> u64 {
+	let mut prot = 0;
+	if flags & PF_R != 0 {
+		prot |= PROT_READ;
+	}
+	if flags & PF_W != 0 {
+		prot |= PROT_WRITE;
+	}
+	if flags & PF_X != 0 {
+		prot |= PROT_EXEC;
+	}
+	prot
+}
+
+//the owner of /proc/self is whoever we're running as. Saves needing libc for getuid
+fn ids() -> (u64, u64) {
+	fs::metadata("/proc/self").map_or((0, 0), |m| (m.uid() as u64, m.gid() as u64))
+}
+
+fn random_bytes(len: usize) -> io::Result<Vec<u8>> {
+	let mut buf = vec![0; len];
+	File::open("/dev/urandom")?.read_exact(&mut buf)?;
+	Ok(buf)
+}
+
+//riscv64 struct stat. libgloss uses the same one
+pub fn stat_bytes(m: &Metadata) -> Vec<u8> {
+	let mut buf = Vec::with_capacity(128);
+	push64(&mut buf, m.dev());
+	push64(&mut buf, m.ino());
+	push32(&mut buf, m.mode());
+	push32(&mut buf, m.nlink() as u32);
+	push32(&mut buf, m.uid());
+	push32(&mut buf, m.gid());
+	push64(&mut buf, m.rdev());
+	push64(&mut buf, 0);
+	push64(&mut buf, m.size());
+	push32(&mut buf, m.blksize() as u32);
+	push32(&mut buf, 0);
+	push64(&mut buf, m.blocks());
+	push64(&mut buf, m.atime() as u64);
+	push64(&mut buf, m.atime_nsec() as u64);
+	push64(&mut buf, m.mtime() as u64);
+	push64(&mut buf, m.mtime_nsec() as u64);
+	push64(&mut buf, m.ctime() as u64);
+	push64(&mut buf, m.ctime_nsec() as u64);
+	push64(&mut buf, 0);
+	buf
+}
+
+//builds the stack execve leaves behind in [bottom, top), and hands back sp.
+//from sp up: argc, argv pointers, 0, envp pointers, 0, then the aux vector as
+//key/value pairs ending in AT_NULL. The strings they point at sit right under top.
+//Too much to fit is E2BIG, like linux
+fn initial_stack(cpu: &mut Cpu, exe: &Elf, args: &[String], env: &[String], bottom: u64, top: u64) -> io::Result<u64> {
+	let tooBig = || io::Error::new(io::ErrorKind::ArgumentListTooLong, "arguments and environment dont fit on the stack");
+	//strings go right at the top
+	let mut sp = top;
+	let mut put = |cpu: &mut Cpu, bytes: &[u8]| -> io::Result<u64> {
+		sp = sp.checked_sub(bytes.len() as u64).filter(|&sp| sp >= bottom).ok_or_else(tooBig)?;
+		cpu.bus.write_bytes(sp, bytes).map_err(|_| io::Error::other("stack doesnt fit in memory"))?;
+		Ok(sp)
+	};
+	let mut strs = |cpu: &mut Cpu, list: &[String]| -> io::Result<Vec<u64>> {
+		let mut ptrs = Vec::new();
+		for s in list {
+			let mut bytes = s.as_bytes().to_vec();
+			bytes.push(0);
+			ptrs.push(put(cpu, &bytes)?);
+		}
+		Ok(ptrs)
+	};
+	let argv = strs(cpu, args)?;
+	let envp = strs(cpu, env)?;
+	let platform = put(cpu, b"riscv64\0")?;
+	let random = random_bytes(16).unwrap_or_else(|_| {
+		let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+		now.to_le_bytes().to_vec()
+	});
+	let random = put(cpu, &random)?;
+
+	let (uid, gid) = ids();
+	let auxv = [
+		(AT_PHDR, exe.phdr),
+		(AT_PHENT, exe.phentsize),
+		(AT_PHNUM, exe.phnum),
+		(AT_PAGESZ, PAGE_SIZE),
+		(AT_BASE, 0), //no interpreter, static programs only
+		(AT_FLAGS, 0),
+		(AT_ENTRY, exe.entry),
+		(AT_UID, uid),
+		(AT_EUID, uid),
+		(AT_GID, gid),
+		(AT_EGID, gid),
+		(AT_HWCAP, HWCAP),
+		(AT_HWCAP2, 0),
+		(AT_PLATFORM, platform),
+		(AT_CLKTCK, 100),
+		(AT_SECURE, 0),
+		(AT_RANDOM, random),
+		(AT_EXECFN, argv.first().copied().unwrap_or(0)),
+		(AT_NULL, 0),
+	];
+	let mut table = Vec::new();
+	push64(&mut table, argv.len() as u64);
+	for &p in argv.iter() {
+		push64(&mut table, p);
+	}
+	push64(&mut table, 0);
+	for &p in envp.iter() {
+		push64(&mut table, p);
+	}
+	push64(&mut table, 0);
+	for &(key, val) in auxv.iter() {
+		push64(&mut table, key);
+		push64(&mut table, val);
+	}
+	let sp = sp.checked_sub(table.len() as u64).map(|sp| sp & !0xf).filter(|&sp| sp >= bottom).ok_or_else(tooBig)?;
+	cpu.bus.write_bytes(sp, &table).map_err(|_| io::Error::other("stack doesnt fit in memory"))?;
+	Ok(sp)
+}
+
+impl Linux {
+	//loads the program and sets up the stack the way the kernel would for execve:
+	//argc, argv, envp and the aux vector, with sp pointing at argc
+	pub fn New(cpu: &mut Cpu, exe: &Elf, args: &[String], env: &[String]) -> io::Result<Linux> {
+		let mut mm = AddressSpace::New(cpu)?;
+		let top = mm.limit();
+		//segments that share a page get to do whatever either of them can
+		let mut pages = std::collections::BTreeMap::new();
+		for seg in &exe.segments {
+			let end = seg.vaddr.checked_add(seg.memsz).filter(|&end| seg.memsz == 0 || mm.fits(seg.vaddr, end));
+			let Some(end) = end else {
+				return Err(io::Error::new(io::ErrorKind::InvalidData, format!("ELF segment at {:#x} doesnt fit in memory", seg.vaddr)));
+			};
+			for page in (seg.vaddr & !(PAGE_SIZE - 1)..end).step_by(PAGE_SIZE as usize) {
+				*pages.entry(page).or_insert(0) |= segment_prot(seg.flags);
+			}
+		}
+		for (&page, &prot) in pages.iter() {
+			mm.map(cpu, page, page + PAGE_SIZE, prot);
+		}
+		exe.load_user(cpu)?;
+
+		//segments were all checked to fit above, so this cant go over
+		let brkStart = page_up(exe.segments.iter().map(|seg| seg.vaddr + seg.memsz).max().unwrap_or(mm.base())).unwrap_or(top);
+		let mmapTop = (top - STACK_SIZE.min(top - mm.base())) & !(PAGE_SIZE - 1);
+		if brkStart > mmapTop || !mm.is_free(mmapTop, top) {
+			return Err(io::Error::new(io::ErrorKind::InvalidData, "program doesnt leave room for a stack"));
+		}
+		mm.map(cpu, mmapTop, top, PROT_READ | PROT_WRITE);
+
+		let sp = initial_stack(cpu, exe, args, env, mmapTop, top)?;
+
+		//everything else starts at 0, a0 especially (its the exit hook from the dynamic linker)
+		cpu.regs = [0; 32];
+		cpu.regs[2] = sp;
+		cpu.prv = privilege_level::U;
+		//let the program read the counters, same as linux does
+		cpu.store_csr(MCOUNTEREN, 0x7);
+		cpu.store_csr(SCOUNTEREN, 0x7);
+
+		let stdio = |fd: io::Result<std::os::fd::OwnedFd>| fd.ok().map(File::from);
+		Ok(Linux {
+			files: vec![
+				stdio(io::stdin().as_fd().try_clone_to_owned()),
+				stdio(io::stdout().as_fd().try_clone_to_owned()),
+				stdio(io::stderr().as_fd().try_clone_to_owned()),
+			],
+			exe: args.first().cloned().unwrap_or_default(),
+			mm,
+			brkStart,
+			brk: brkStart,
+			mmapTop,
+			start: Instant::now(),
+		})
+	}
+
+	//call after every step. If the guest trapped, deals with it and puts it back in U mode.
+	//Gives back the exit status once the guest has exited
+	pub fn service(&mut self, cpu: &mut Cpu) -> Result<Option<i32>, Fault> {
+		if cpu.prv != privilege_level::U {
+			let cause = cpu.load_csr(MCAUSE);
+			let pc = cpu.load_csr(MEPC);
+			if cause != 8 {
+				return Err(Fault { cause, pc, tval: cpu.load_csr(MTVAL) });
+			}
+			//ecall is never compressed
+			cpu.pc = pc + 4;
+			cpu.prv = privilege_level::U;
+			return Ok(self.syscall(cpu));
+		}
+		Ok(None)
+	}
+
+	//a7 is the syscall number, a0-a5 the arguments, and the result goes back in a0
+	pub fn syscall(&mut self, cpu: &mut Cpu) -> Option<i32> {
+		let a = [cpu.regs[10], cpu.regs[11], cpu.regs[12], cpu.regs[13], cpu.regs[14], cpu.regs[15]];
+		let result = match cpu.regs[17] {
+			SYS_EXIT | SYS_EXIT_GROUP => return Some(a[0] as i32 & 0xff),
+			SYS_KILL | SYS_TKILL | SYS_TGKILL => {
+				//only ever us on the other end. Signal 0 is just checking we exist
+				let sig = if cpu.regs[17] == SYS_TGKILL { a[2] } else { a[1] };
+				if sig != 0 {
+					return Some(128 + sig as i32);
+				}
+				Ok(0)
+			},
+			SYS_READ => self.read(cpu, a[0], &[(a[1], a[2])]),
+			SYS_WRITE => self.write(cpu, a[0], &[(a[1], a[2])]),
+			SYS_READV | SYS_WRITEV => {
+				self.iovecs(cpu, a[1], a[2]).and_then(|iov| {
+					if cpu.regs[17] == SYS_READV { self.read(cpu, a[0], &iov) } else { self.write(cpu, a[0], &iov) }
+				})
+			},
+			SYS_OPENAT => self.openat(cpu, a[0], a[1], a[2], a[3]),
+			SYS_CLOSE => self.close(a[0]),
+			SYS_LSEEK => self.lseek(a[0], a[1], a[2]),
+			SYS_FSTAT => self.file(a[0]).and_then(|f| f.metadata().map_err(errno))
+				.and_then(|m| write_mem(cpu, a[1], &stat_bytes(&m)).map(|_| 0)),
+			SYS_NEWFSTATAT => self.fstatat(cpu, a[0], a[1], a[2], a[3]),
+			SYS_FACCESSAT => self.path(cpu, a[0], a[1]).and_then(|p| fs::metadata(p).map(|_| 0).map_err(errno)),
+			SYS_MKDIRAT => self.path(cpu, a[0], a[1]).and_then(|p| fs::create_dir(p).map(|_| 0).map_err(errno)),
+			SYS_UNLINKAT => self.path(cpu, a[0], a[1]).and_then(|p| {
+				if a[2] & AT_REMOVEDIR != 0 { fs::remove_dir(p) } else { fs::remove_file(p) }.map(|_| 0).map_err(errno)
+			}),
+			SYS_READLINKAT => self.readlinkat(cpu, a[0], a[1], a[2], a[3]),
+			SYS_GETCWD => self.getcwd(cpu, a[0], a[1]),
+			SYS_DUP => self.file(a[0]).and_then(|f| f.try_clone().map_err(errno)).and_then(|f| self.add_file(f, 0)),
+			SYS_DUP3 => self.dup3(a[0], a[1]),
+			SYS_FCNTL => match a[1] {
+				F_DUPFD | F_DUPFD_CLOEXEC => self.file(a[0]).and_then(|f| f.try_clone().map_err(errno)).and_then(|f| self.add_file(f, a[2])),
+				//nothing else that can be asked matters without exec or nonblocking io
+				_ => self.file(a[0]).map(|_| 0),
+			},
+			//nothing here is a terminal as far as the guest can tell
+			SYS_IOCTL => self.file(a[0]).and(Err(ENOTTY)),
+			SYS_BRK => Ok(self.set_brk(cpu, a[0])),
+			SYS_MMAP => self.mmap(cpu, &a),
+			SYS_MUNMAP => self.munmap(cpu, a[0], a[1]),
+			SYS_MPROTECT => self.mprotect(cpu, a[0], a[1], a[2]),
+			SYS_MADVISE => Ok(0),
+			SYS_CLOCK_GETTIME => {
+				let (secs, nanos) = self.clock(a[0]);
+				let mut buf = Vec::new();
+				push64(&mut buf, secs);
+				push64(&mut buf, nanos);
+				write_mem(cpu, a[1], &buf).map(|_| 0)
+			},
+			SYS_GETTIMEOFDAY => {
+				let (secs, nanos) = self.clock(CLOCK_REALTIME);
+				let mut buf = Vec::new();
+				push64(&mut buf, secs);
+				push64(&mut buf, nanos / 1000);
+				if a[0] != 0 { write_mem(cpu, a[0], &buf).map(|_| 0) } else { Ok(0) }
+			},
+			SYS_GETRANDOM => {
+				if !cpu.bus.dram.contains(a[0], a[1]) {
+					Err(EFAULT)
+				}
+				else {
+					random_bytes(a[1] as usize).map_err(errno).and_then(|buf| write_mem(cpu, a[0], &buf)).map(|_| a[1])
+				}
+			},
+			SYS_UNAME => {
+				let mut buf = Vec::new();
+				for field in ["Linux", "riscvEmu", "6.1.0", "#1", "riscv64", "(none)"] {
+					let mut bytes = field.as_bytes().to_vec();
+					bytes.resize(65, 0);
+					buf.extend_from_slice(&bytes);
+				}
+				write_mem(cpu, a[0], &buf).map(|_| 0)
+			},
+			SYS_PRLIMIT64 => {
+				let limit = match a[1] {
+					RLIMIT_STACK => STACK_SIZE,
+					RLIMIT_NOFILE => MAX_FILES,
+					_ => u64::MAX,
+				};
+				let mut buf = Vec::new();
+				push64(&mut buf, limit);
+				push64(&mut buf, limit);
+				if a[3] != 0 { write_mem(cpu, a[3], &buf).map(|_| 0) } else { Ok(0) }
+			},
+			//no signals ever get delivered, so handlers and masks are just remembered as empty
+			SYS_RT_SIGACTION => if a[2] != 0 { write_mem(cpu, a[2], &[0; 24]).map(|_| 0) } else { Ok(0) },
+			SYS_RT_SIGPROCMASK => if a[2] != 0 { write_mem(cpu, a[2], &vec![0; a[3].min(128) as usize]).map(|_| 0) } else { Ok(0) },
+			SYS_SIGALTSTACK => Ok(0),
+			//theres only ever one thread, so theres never anyone to wait for
+			SYS_FUTEX => if a[1] & 0x7f == 0 { Err(EAGAIN) } else { Ok(0) },
+			SYS_SET_ROBUST_LIST | SYS_SCHED_YIELD => Ok(0),
+			SYS_SET_TID_ADDRESS | SYS_GETPID | SYS_GETTID => Ok(process::id() as u64),
+			SYS_GETPPID => Ok(std::os::unix::process::parent_id() as u64),
+			SYS_GETUID | SYS_GETEUID => Ok(ids().0),
+			SYS_GETGID | SYS_GETEGID => Ok(ids().1),
+			_ => Err(ENOSYS),
+		};
+		cpu.regs[10] = match result {
+			Ok(val) => val,
+			Err(e) => (-e) as u64,
+		};
+		None
+	}
+
+	fn file(&mut self, fd: u64) -> Result<&mut File, i64> {
+		self.files.get_mut(fd as usize).and_then(|f| f.as_mut()).ok_or(EBADF)
+	}
+
+	//lowest free fd thats at least min
+	fn add_file(&mut self, file: File, min: u64) -> Result<u64, i64> {
+		let fd = (min as usize..MAX_FILES as usize).find(|&fd| self.files.get(fd).is_none_or(|f| f.is_none())).ok_or(EINVAL)?;
+		if self.files.len() <= fd {
+			self.files.resize_with(fd + 1, || None);
+		}
+		self.files[fd] = Some(file);
+		Ok(fd as u64)
+	}
+
+	fn close(&mut self, fd: u64) -> Result<u64, i64> {
+		self.files.get_mut(fd as usize).and_then(|f| f.take()).map(|_| 0).ok_or(EBADF)
+	}
+
+	fn dup3(&mut self, old: u64, new: u64) -> Result<u64, i64> {
+		if old == new || new >= MAX_FILES {
+			return Err(EINVAL);
+		}
+		let file = self.file(old)?.try_clone().map_err(errno)?;
+		if self.files.len() <= new as usize {
+			self.files.resize_with(new as usize + 1, || None);
+		}
+		self.files[new as usize] = Some(file);
+		Ok(new)
+	}
+
+	//a path relative to dirfd. Host fds show up under /proc/self/fd, so relative
+	//paths under some other directory dont need anything special
+	fn path(&mut self, cpu: &mut Cpu, dirfd: u64, addr: u64) -> Result<PathBuf, i64> {
+		let path = PathBuf::from(read_str(cpu, addr)?);
+		if path.is_absolute() || dirfd as i64 == AT_FDCWD {
+			return Ok(path);
+		}
+		let dir = self.file(dirfd)?.as_raw_fd();
+		Ok(PathBuf::from(format!("/proc/self/fd/{}", dir)).join(path))
+	}
+
+	//the (base, len) pairs of an iovec array
+	fn iovecs(&mut self, cpu: &mut Cpu, addr: u64, count: u64) -> Result<Vec<(u64, u64)>, i64> {
+		if count > IOV_MAX {
+			return Err(EINVAL);
+		}
+		let raw = read_mem(cpu, addr, count * 16)?;
+		Ok(raw.chunks(16).map(|iov| {
+			let word = |i: usize| u64::from_le_bytes(iov[i..i + 8].try_into().unwrap());
+			(word(0), word(8))
+		}).collect())
+	}
+
+	//fills each buffer in turn, stopping early if the file runs dry
+	fn read(&mut self, cpu: &mut Cpu, fd: u64, bufs: &[(u64, u64)]) -> Result<u64, i64> {
+		let mut total = 0;
+		for &(addr, len) in bufs {
+			if !mm::accessible(cpu, addr, len, AccessType::Store) {
+				return Err(EFAULT);
+			}
+			let mut buf = vec![0; len as usize];
+			let n = self.file(fd)?.read(&mut buf).map_err(errno)?;
+			write_mem(cpu, addr, &buf[..n])?;
+			total += n as u64;
+			if n < buf.len() {
+				break;
+			}
+		}
+		Ok(total)
+	}
+
+	//everything goes out in one write, so writev stays in one piece
+	fn write(&mut self, cpu: &mut Cpu, fd: u64, bufs: &[(u64, u64)]) -> Result<u64, i64> {
+		let mut data = Vec::new();
+		for &(addr, len) in bufs {
+			data.extend(read_mem(cpu, addr, len)?);
+		}
+		self.file(fd)?.write(&data).map(|n| n as u64).map_err(errno)
+	}
+
+	fn openat(&mut self, cpu: &mut Cpu, dirfd: u64, addr: u64, flags: u64, mode: u64) -> Result<u64, i64> {
+		let path = self.path(cpu, dirfd, addr)?;
+		let mut opts = OpenOptions::new();
+		match flags & O_ACCMODE {
+			0 => opts.read(true),
+			1 => opts.write(true),
+			_ => opts.read(true).write(true),
+		};
+		if flags & O_CREAT != 0 {
+			if flags & O_EXCL != 0 { opts.create_new(true) } else { opts.create(true) };
+		}
+		opts.truncate(flags & O_TRUNC != 0);
+		opts.append(flags & O_APPEND != 0);
+		//anything else (O_DIRECTORY, O_NOFOLLOW, O_CLOEXEC...) the host can deal with itself
+		let handled = O_ACCMODE | O_CREAT | O_EXCL | O_TRUNC | O_APPEND;
+		opts.custom_flags((flags & !handled) as i32);
+		opts.mode(mode as u32);
+		let file = opts.open(path).map_err(errno)?;
+		self.add_file(file, 0)
+	}
+
+	fn lseek(&mut self, fd: u64, offset: u64, whence: u64) -> Result<u64, i64> {
+		let pos = match whence {
+			0 => SeekFrom::Start(offset),
+			1 => SeekFrom::Current(offset as i64),
+			2 => SeekFrom::End(offset as i64),
+			_ => return Err(EINVAL),
+		};
+		self.file(fd)?.seek(pos).map_err(errno)
+	}
+
+	fn fstatat(&mut self, cpu: &mut Cpu, dirfd: u64, addr: u64, buf: u64, flags: u64) -> Result<u64, i64> {
+		let meta = if flags & AT_EMPTY_PATH != 0 && read_str(cpu, addr)?.is_empty() {
+			self.file(dirfd)?.metadata()
+		}
+		else {
+			let path = self.path(cpu, dirfd, addr)?;
+			if flags & AT_SYMLINK_NOFOLLOW != 0 { fs::symlink_metadata(path) } else { fs::metadata(path) }
+		};
+		write_mem(cpu, buf, &stat_bytes(&meta.map_err(errno)?))?;
+		Ok(0)
+	}
+
+	//no nul on the end, and cut short if it doesnt fit
+	fn readlinkat(&mut self, cpu: &mut Cpu, dirfd: u64, addr: u64, buf: u64, size: u64) -> Result<u64, i64> {
+		let path = self.path(cpu, dirfd, addr)?;
+		let target = if path == Path::new("/proc/self/exe") {
+			PathBuf::from(&self.exe)
+		}
+		else {
+			fs::read_link(path).map_err(errno)?
+		};
+		let bytes = target.to_string_lossy().into_owned().into_bytes();
+		let n = bytes.len().min(size as usize);
+		write_mem(cpu, buf, &bytes[..n])?;
+		Ok(n as u64)
+	}
+
+	fn getcwd(&mut self, cpu: &mut Cpu, buf: u64, size: u64) -> Result<u64, i64> {
+		let mut cwd = env::current_dir().map_err(errno)?.to_string_lossy().into_owned().into_bytes();
+		cwd.push(0);
+		if cwd.len() as u64 > size {
+			return Err(ERANGE);
+		}
+		write_mem(cpu, buf, &cwd)?;
+		Ok(cwd.len() as u64)
+	}
+
+	//the break can move anywhere from the end of the program up to whatever is mapped
+	//above it. Asking for anything else (0 included) just gets told where it is now
+	fn set_brk(&mut self, cpu: &mut Cpu, addr: u64) -> u64 {
+		if addr < self.brkStart {
+			return self.brk;
+		}
+		let (Some(old), Some(new)) = (page_up(self.brk), page_up(addr)) else {
+			return self.brk;
+		};
+		if new > old {
+			if !self.mm.is_free(old, new) {
+				return self.brk;
+			}
+			self.mm.map(cpu, old, new, PROT_READ | PROT_WRITE);
+		}
+		else if new < old {
+			self.mm.unmap(cpu, new, old);
+		}
+		//the tail of the last page has to come back zeroed if it was given back earlier
+		if addr > self.brk {
+			let _ = cpu.bus.write_bytes(self.brk, &vec![0; (addr.min(old) - self.brk) as usize]);
+		}
+		self.brk = addr;
+		self.brk
+	}
+
+	//anonymous or file backed. File mappings are always private copies, even MAP_SHARED ones,
+	//so writes never go back to the file
+	//takes the syscall args as they come: addr, len, prot, flags, fd, offset
+	fn mmap(&mut self, cpu: &mut Cpu, args: &[u64]) -> Result<u64, i64> {
+		let (addr, len, prot, flags, fd, offset) = (args[0], args[1], args[2], args[3], args[4], args[5]);
+		if len == 0 || !offset.is_multiple_of(PAGE_SIZE) {
+			return Err(EINVAL);
+		}
+		//nothing bigger than the address space can ever be mapped, so dont go allocating for it
+		let len = page_up(len).filter(|&len| len <= self.mm.limit() - self.mm.base()).ok_or(ENOMEM)?;
+		//read the file first, so a bad fd doesnt leave a fixed mapping clobbered.
+		//Only as much as the file has, the rest of the mapping is zeroes anyway
+		let mut buf = Vec::new();
+		if flags & MAP_ANONYMOUS == 0 {
+			let file = self.file(fd)?;
+			let size = file.metadata().map_err(errno)?.len();
+			buf = vec![0; len.min(size.saturating_sub(offset)) as usize];
+			let mut filled = 0;
+			while filled < buf.len() {
+				match file.read_at(&mut buf[filled..], offset + filled as u64).map_err(errno)? {
+					0 => break,
+					n => filled += n,
+				}
+			}
+			buf.truncate(filled);
+		}
+
+		let end = addr.checked_add(len).unwrap_or(0);
+		let start = if flags & (MAP_FIXED | MAP_FIXED_NOREPLACE) != 0 {
+			if !addr.is_multiple_of(PAGE_SIZE) {
+				return Err(EINVAL);
+			}
+			if !self.mm.fits(addr, end) {
+				return Err(ENOMEM);
+			}
+			if flags & MAP_FIXED == 0 && !self.mm.is_free(addr, end) {
+				return Err(EEXIST);
+			}
+			addr
+		}
+		//the address is only a hint otherwise
+		else if addr != 0 && addr.is_multiple_of(PAGE_SIZE) && self.mm.is_free(addr, end) {
+			addr
+		}
+		else {
+			self.mm.find_free(len, self.mmapTop).ok_or(ENOMEM)?
+		};
+		self.mm.map(cpu, start, start + len, prot);
+		let _ = cpu.bus.write_bytes(start, &buf);
+		Ok(start)
+	}
+
+	fn munmap(&mut self, cpu: &mut Cpu, addr: u64, len: u64) -> Result<u64, i64> {
+		let end = page_up(len).and_then(|len| addr.checked_add(len)).ok_or(EINVAL)?;
+		if len == 0 || !addr.is_multiple_of(PAGE_SIZE) {
+			return Err(EINVAL);
+		}
+		//unmapping something thats not there is fine, as long as it could have been
+		if self.mm.fits(addr, end) {
+			self.mm.unmap(cpu, addr, end);
+		}
+		Ok(0)
+	}
+
+	fn mprotect(&mut self, cpu: &mut Cpu, addr: u64, len: u64, prot: u64) -> Result<u64, i64> {
+		let end = page_up(len).and_then(|len| addr.checked_add(len)).ok_or(ENOMEM)?;
+		if !addr.is_multiple_of(PAGE_SIZE) || prot & !(PROT_READ | PROT_WRITE | PROT_EXEC) != 0 {
+			return Err(EINVAL);
+		}
+		if len == 0 {
+			return Ok(0);
+		}
+		if !self.mm.protect(cpu, addr, end, prot) {
+			return Err(ENOMEM);
+		}
+		Ok(0)
+	}
+
+	//seconds and nanoseconds. Wall clock time for the realtime clocks, and time since
+	//we started for the rest
+	fn clock(&self, clock: u64) -> (u64, u64) {
+		let time = match clock {
+			CLOCK_REALTIME | CLOCK_REALTIME_COARSE => SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default(),
+			_ => self.start.elapsed(),
+		};
+		(time.as_secs(), time.subsec_nanos() as u64)
+	}
+}
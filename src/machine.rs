@@ -0,0 +1,138 @@
+//a bunch of harts sharing one bus
+//each hart is a whole Cpu with its own registers, csrs, privilege level and tlb.
+//There's only one real bus though, and it lives in whichever hart is running.
+//The rest hold an empty bus until it's their turn
+//
+//harts take turns round robin, each running quantum instructions before the next one
+//goes. Every hart starts at the same place, and software tells them apart with mhartid
+//(same as real boards, where the boot code picks one hart and parks the others on a wfi
+//until it sends them an ipi through the clint)
+//
+//run_threaded puts every hart on a host thread of its own instead, so they really do run
+//at the same time. The bus goes behind a lock for that (see Bus::attach), and the quantum
+//becomes how often each hart catches the devices up
+#![allow(non_snake_case)]
+
+use std::mem;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::thread;
+
+use crate::bus::Bus;
+use crate::cpu::Cpu;
+use crate::regs::MHARTID;
+
+//short enough that spinlocks see each other quickly, long enough to not switch constantly
+pub const DEFAULT_QUANTUM: u64 = 100;
+
+pub struct Machine {
+	harts: Vec<Cpu>,
+	pub quantum: u64, //instructions each hart gets before switching
+	current: usize, //hart that has the bus
+	ran: u64, //instructions the current hart has run this turn
+}
+
+impl Machine {
+	//hart 0 is the one given, already loaded with whatever its going to run
+	//the others start out the same as it did
+	pub fn New(mut boot: Cpu, harts: usize, quantum: u64) -> Machine {
+		let harts = harts.max(1);
+		boot.bus.set_harts(harts);
+		let mut all = Vec::with_capacity(harts);
+		for id in 1..harts {
+			let mut hart = Cpu::with_bus(Bus::empty());
+			hart.pc = boot.pc;
+			hart.regs = boot.regs;
+			hart.csrs[MHARTID] = id as u64;
+			all.push(hart);
+		}
+		all.insert(0, boot);
+		Machine {
+			harts: all,
+			quantum: quantum.max(1),
+			current: 0,
+			ran: 0,
+		}
+	}
+
+	pub fn harts(&self) -> usize {
+		self.harts.len()
+	}
+
+	//the hart that runs next
+	pub fn current(&self) -> usize {
+		self.current
+	}
+
+	pub fn hart(&self, id: usize) -> &Cpu {
+		&self.harts[id]
+	}
+
+	pub fn hart_mut(&mut self, id: usize) -> &mut Cpu {
+		&mut self.harts[id]
+	}
+
+	//the shared bus, wherever it is right now
+	pub fn bus(&mut self) -> &mut Bus {
+		&mut self.harts[self.current].bus
+	}
+
+	//runs one instruction on the current hart, then moves on if its turn is up
+	pub fn step(&mut self) {
+		self.harts[self.current].step();
+		self.ran += 1;
+		if self.ran >= self.quantum {
+			self.switch((self.current + 1) % self.harts.len());
+		}
+	}
+
+	//hands the bus to another hart
+	fn switch(&mut self, next: usize) {
+		self.ran = 0;
+		if next == self.current {
+			return;
+		}
+		let bus = mem::replace(&mut self.harts[self.current].bus, Bus::empty());
+		self.harts[next].bus = bus;
+		self.current = next;
+	}
+
+	//runs every hart on its own thread. After each instruction keep_going is asked whether
+	//that hart should carry on, and this returns once they've all stopped.
+	//The bus is back in hart 0 afterwards
+	pub fn run_threaded<F>(&mut self, keep_going: F)
+	where
+		F: Fn(usize, &Cpu) -> bool + Sync,
+	{
+		let home = mem::replace(&mut self.harts[self.current].bus, Bus::empty());
+		let home = Arc::new(Mutex::new(home));
+		for hart in self.harts.iter_mut() {
+			hart.bus = Bus::attach(&home, self.quantum);
+		}
+		let keep_going = &keep_going;
+		thread::scope(|scope| {
+			for (id, hart) in self.harts.iter_mut().enumerate() {
+				scope.spawn(move || {
+					loop {
+						hart.step();
+						if !keep_going(id, hart) {
+							break;
+						}
+						//nothing to do until an interrupt shows up, let someone else have the core
+						if hart.wfi {
+							thread::yield_now();
+						}
+					}
+					//whatever ticks are left over
+					hart.bus.sync();
+				});
+			}
+		});
+		for hart in self.harts.iter_mut() {
+			hart.bus = Bus::empty();
+		}
+		let home = Arc::try_unwrap(home).ok().expect("a hart thread kept hold of the bus");
+		self.harts[0].bus = home.into_inner().unwrap_or_else(PoisonError::into_inner);
+		self.current = 0;
+		self.ran = 0;
+	}
+}
@@ -9,58 +9,340 @@ use std::env;
 use std::fs::File;
 use std::io;
 use std::io::Read;
+use std::process;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 //module defining all the register structs
 mod regs;
 mod cpu;
 mod bus;
 mod dram;
+mod elf;
+mod trap;
+mod clint;
+mod plic;
+mod uart;
+mod console;
+mod device;
+mod machine;
+mod linux;
+mod mm;
+mod newlib;
+mod semihost;
 
 use regs::Instruction;
 use cpu::Cpu;
+use bus::Bus;
+use dram::{Dram, DRAM_BASE, DRAM_SIZE};
+use elf::Elf;
+use console::Console;
+use uart::Uart;
+use machine::{Machine, DEFAULT_QUANTUM};
+use linux::{Linux, USER_BASE};
+use newlib::Newlib;
+use semihost::Semihost;
+
+//everything that can be set from the command line
+struct Options {
+	file: String,
+	args: Vec<String>, //for the guest, in user mode or with semihosting
+	memBase: u64,
+	memSize: u64,
+	tlbStats: bool,
+	trace: bool,
+	harts: usize,
+	quantum: u64,
+	threads: bool,
+	user: bool,
+	newlib: bool,
+	semihosting: bool,
+}
+
+const USAGE: &str = "Correct argument usage: riscvEmu [--mem-base <addr>] [--mem-size <bytes>] [--harts <n>] [--quantum <insts>] [--threads] [--newlib] [--semihosting] [--tlb-stats] [--trace] <file> [args...]\n\
+                     or: riscvEmu --user [--mem-base <addr>] [--mem-size <bytes>] [--tlb-stats] [--trace] <file> [args...]";
+
+//accepts plain decimal, 0x hex, and K/M/G suffixes for sizes
+fn parse_num(arg: &str) -> Option<u64> {
+	let (digits, scale) = match arg.chars().last()? {
+		'K' | 'k' => (&arg[..arg.len() - 1], 1024),
+		'M' | 'm' => (&arg[..arg.len() - 1], 1024*1024),
+		'G' | 'g' => (&arg[..arg.len() - 1], 1024*1024*1024),
+		_ => (arg, 1),
+	};
+	let num = match digits.strip_prefix("0x") {
+		Some(hex) => u64::from_str_radix(&hex.replace('_', ""), 16).ok()?,
+		None => digits.replace('_', "").parse::<u64>().ok()?,
+	};
+	num.checked_mul(scale)
+}
+
+fn usage_error(msg: &str) -> ! {
+	eprintln!("riscvEmu: {}", msg);
+	eprintln!("{}", USAGE);
+	process::exit(1);
+}
+
+fn parse_args(args: &[String]) -> Options {
+	let mut opts = Options {
+		file: String::new(),
+		args: Vec::new(),
+		memBase: DRAM_BASE,
+		memSize: DRAM_SIZE,
+		tlbStats: false,
+		trace: false,
+		harts: 1,
+		quantum: DEFAULT_QUANTUM,
+		threads: false,
+		user: false,
+		newlib: false,
+		semihosting: false,
+	};
+	//where dram goes by default depends on the mode
+	let mut memBase = None;
+	let mut i = 1;
+	while i < args.len() {
+		match args[i].as_str() {
+			"--mem-base" | "--mem-size" => {
+				let val = match args.get(i + 1).and_then(|a| parse_num(a)) {
+					Some(val) => val,
+					None => usage_error(&format!("{} needs a number", args[i])),
+				};
+				if args[i] == "--mem-base" {
+					memBase = Some(val);
+				}
+				else {
+					opts.memSize = val;
+				}
+				i += 1;
+			},
+			"--harts" | "--quantum" => {
+				let val = match args.get(i + 1).and_then(|a| parse_num(a)).filter(|&n| n > 0) {
+					Some(val) => val,
+					None => usage_error(&format!("{} needs a number above 0", args[i])),
+				};
+				if args[i] == "--harts" {
+					opts.harts = val as usize;
+				}
+				else {
+					opts.quantum = val;
+				}
+				i += 1;
+			},
+			"--threads" => opts.threads = true,
+			"--user" => opts.user = true,
+			"--newlib" => opts.newlib = true,
+			"--semihosting" => opts.semihosting = true,
+			"--tlb-stats" => opts.tlbStats = true,
+			"--trace" => opts.trace = true,
+			arg if arg.starts_with("--") => usage_error(&format!("unknown option {}", arg)),
+			arg => {
+				if !opts.file.is_empty() {
+					usage_error("only one file can be run");
+				}
+				opts.file = arg.to_string();
+				//the rest belongs to the program
+				if opts.user || opts.semihosting {
+					opts.args = args[i + 1..].to_vec();
+					break;
+				}
+			},
+		}
+		i += 1;
+	}
+	if opts.file.is_empty() {
+		usage_error("no file given");
+	}
+	if opts.user && (opts.harts > 1 || opts.threads) {
+		usage_error("--user only runs one hart");
+	}
+	if (opts.newlib || opts.semihosting) && (opts.user || opts.harts > 1 || opts.threads) {
+		usage_error("--newlib and --semihosting only run one hart, outside of user mode");
+	}
+	opts.memBase = memBase.unwrap_or(if opts.user { USER_BASE } else { DRAM_BASE });
+	if opts.memSize == 0 || opts.memBase.checked_add(opts.memSize).is_none() {
+		usage_error("memory doesnt fit in the address space");
+	}
+	opts
+}
+
+//the old register dump, for debugging. Built up first so harts on different threads
+//dont get their lines mixed together
+fn dump(id: usize, cpu: &Cpu, harts: usize) -> String {
+	let mut out = String::new();
+	if harts > 1 {
+		out += &format!("hart {}\n", id);
+	}
+	out += &format!("pc is currently {:#x}\n", cpu.pc);
+	for i in 0..32 {
+		if cpu.regs[i] != 0 {
+			out += &format!("register {} has val {}\n", i, cpu.regs[i]);
+		}
+	}
+	out
+}
+
+//a hart with dram where the options put it. Dram cant sit on top of the devices
+fn new_cpu(opts: &Options, code: Vec<u8>) -> Cpu {
+	match Bus::with_dram(Dram::New(code, opts.memBase, opts.memSize)) {
+		Ok(bus) => Cpu::with_bus(bus),
+		Err(_) => {
+			eprintln!("riscvEmu: memory at {:#x}-{:#x} overlaps the clint, plic or uart", opts.memBase, opts.memBase + opts.memSize);
+			process::exit(1);
+		},
+	}
+}
+
+//runs a linux program as if it were a process of ours, and hands back its exit status
+fn run_user(opts: &Options, code: &[u8]) -> i32 {
+	let exe = match Elf::parse(code) {
+		Ok(exe) => exe,
+		Err(e) => {
+			eprintln!("riscvEmu: cant run {} in user mode: {}", opts.file, e);
+			return 1;
+		},
+	};
+	let mut cpu = new_cpu(opts, Vec::new());
+	let mut args = vec![opts.file.clone()];
+	args.extend(opts.args.iter().cloned());
+	let env: Vec<String> = env::vars().map(|(k, v)| format!("{}={}", k, v)).collect();
+	let mut linux = match Linux::New(&mut cpu, &exe, &args, &env) {
+		Ok(linux) => linux,
+		Err(e) => {
+			eprintln!("riscvEmu: cant load {}: {}", opts.file, e);
+			return 1;
+		},
+	};
+	let status = loop {
+		cpu.step();
+		if opts.trace {
+			eprint!("{}", dump(0, &cpu, 1));
+		}
+		match linux.service(&mut cpu) {
+			Ok(None) => {},
+			Ok(Some(status)) => break status,
+			//like a shell reports a process killed by a signal
+			Err(fault) => {
+				eprintln!("riscvEmu: {} crashed: {}", opts.file, fault);
+				break 128 + fault.signal();
+			},
+		}
+	};
+	if opts.tlbStats {
+		eprintln!("tlb: {} hits, {} misses, {} flushes, {} entries", cpu.tlb.hits, cpu.tlb.misses, cpu.tlb.flushes, cpu.tlb.len());
+	}
+	status
+}
 
 fn main() -> io::Result<()> {
 	//get arguments
-	//for now its just a file name
 	//if interaction with terminal gets more complicated
 	//should consider using something like clap
 	
 	let args: Vec<String>  = env::args().collect();
+	let opts = parse_args(&args);
 
-	if args.len() != 2 {
-		panic!("Correct argument usage: riscvEmu <file>");
-	}
-	let mut exeFile = File::open(&args[1])?;
+	let mut exeFile = File::open(&opts.file)?;
 	
 	let mut code = Vec::new();
 	exeFile.read_to_end(&mut code)?;
-	let codeLen = code.len();
-	let mut cpu = Cpu::new(code);
+	//ELF segments get checked against dram as theyre loaded, a flat binary is copied in whole
+	if !elf::is_elf(&code) && code.len() as u64 > opts.memSize {
+		eprintln!("riscvEmu: {} is too big to fit in memory", opts.file);
+		process::exit(1);
+	}
+	if opts.user {
+		process::exit(run_user(&opts, &code));
+	}
+	//ELF files get their segments loaded where they ask to be
+	//anything else is assumed to be a flat binary starting at the base of dram
+	//either way the heap for newlib starts right after the program
+	let (mut cpu, end) = if elf::is_elf(&code) {
+		let mut cpu = new_cpu(&opts, Vec::new());
+		let exe = Elf::parse(&code).and_then(|exe| exe.load(&mut cpu).map(|_| exe));
+		match exe {
+			Ok(exe) => (cpu, exe.segments.iter().map(|seg| seg.paddr + seg.memsz).max().unwrap_or(opts.memBase)),
+			Err(e) => {
+				eprintln!("riscvEmu: cant load {}: {}", opts.file, e);
+				process::exit(1);
+			},
+		}
+	}
+	else {
+		let end = opts.memBase + code.len() as u64;
+		(new_cpu(&opts, code), end)
+	};
+	let mut newlib = None;
+	if opts.newlib {
+		cpu.hostCalls = true;
+		newlib = Some(Newlib::New(end));
+	}
+	let mut semihost = None;
+	if opts.semihosting {
+		cpu.semihosting = true;
+		let mut args = vec![opts.file.clone()];
+		args.extend(opts.args.iter().cloned());
+		semihost = Some(Semihost::New(&args));
+	}
+	let mut status = 0;
+	let mut machine = Machine::New(cpu, opts.harts, opts.quantum);
+	//uart reads from our stdin from here on. Not with --newlib or --semihosting though, the
+	//program reads stdin itself and the console thread would steal its input
+	if !opts.newlib && !opts.semihosting {
+		if let Some(uart) = machine.bus().device::<Uart>() {
+			uart.attach_console(Console::New());
+		}
+	}
 	//emulation loop
 	//could just be a while(true) i think
-	
-	loop {
-		//fetch instruciton
-		let instruction = cpu.fetch();
-				
-		
-		//TO DO: decode, the start of execute
-		let instructionFormatted = Cpu::decode(instruction);
-		println!("{:?} from {:b}", instructionFormatted, instruction);
-		cpu.execute(instructionFormatted);
-		println!("pc is currently {}", cpu.pc);	
-		//update pc counter
-		cpu.pc = cpu.pc.wrapping_add(4);
-		for i in (0..32) {
-			if (cpu.regs[i as usize] != 0) {
-				println!("register {} has val {}", i, cpu.regs[i as usize]);
+	//nothing is mapped at 0, so a hart ending up there is either a jump to null or a trap with
+	//no handler. That stops everything
+	let harts = machine.harts();
+	if opts.threads {
+		let halted = AtomicBool::new(false);
+		machine.run_threaded(|id, cpu| {
+			//goes to stderr to stay out of the way of the uart
+			if opts.trace {
+				eprint!("{}", dump(id, cpu, harts));
+			}
+			if cpu.pc == 0 {
+				halted.store(true, Ordering::Relaxed);
+			}
+			!halted.load(Ordering::Relaxed)
+		});
+	}
+	else {
+		loop {
+			//fetch, decode, execute and update pc on whichever hart's turn it is
+			//anything that goes wrong turns into a trap to mtvec
+			let id = machine.current();
+			machine.step();
+			let cpu = machine.hart_mut(id);
+			if opts.trace {
+				eprint!("{}", dump(id, cpu, harts));
+			}
+			let exited = newlib.as_mut().and_then(|newlib| newlib.service(cpu))
+				.or_else(|| semihost.as_mut().and_then(|semihost| semihost.service(cpu)));
+			if let Some(exited) = exited {
+				status = exited;
+				break
+			}
+			if cpu.pc == 0 {
+				break
 			}
 		}
-		cpu.regs[0] = 0;
-		if cpu.pc == 0 {
-			break
+	}
+	if opts.tlbStats {
+		for id in 0..machine.harts() {
+			let tlb = &machine.hart(id).tlb;
+			let prefix = if machine.harts() > 1 { format!("hart {} ", id) } else { String::new() };
+			eprintln!("{}tlb: {} hits, {} misses, {} flushes, {} entries", prefix, tlb.hits, tlb.misses, tlb.flushes, tlb.len());
 		}
-	} 
+	}
+	if status != 0 {
+		//exit skips destructors, so the console has to put the terminal back first
+		drop(machine);
+		process::exit(status);
+	}
 	Ok(())
 
 }
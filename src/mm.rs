@@ -0,0 +1,229 @@
+//address space of a user mode process, what the kernel calls the mm
+//keeps a list of mapped regions with their protections, and mirrors them into real Sv39
+//page tables so the mmu enforces them: touching an unmapped page or writing a read only
+//one page faults, the same as it would under linux
+//
+//every page maps to the same physical address, so dram is the address space and the
+//manager only decides which pages are there and what they allow. The page tables sit
+//at the very top of dram where the guest cant map anything. dram is small enough that
+//they all get built up front: a root, a middle table for every GiB and a leaf table for
+//every 2 MiB, so mapping a page is just writing its leaf pte
+#![allow(non_snake_case)]
+
+use std::collections::BTreeMap;
+use std::io;
+
+use crate::cpu::{Cpu, AccessType, SATP_MODE_SHIFT, SATP_MODE_SV39, PAGE_SHIFT, PAGE_SIZE};
+use crate::cpu::{PTE_V, PTE_R, PTE_W, PTE_X, PTE_U, PTE_A, PTE_D};
+use crate::regs::SATP;
+
+pub const PROT_NONE: u64 = 0;
+pub const PROT_READ: u64 = 1;
+pub const PROT_WRITE: u64 = 2;
+pub const PROT_EXEC: u64 = 4;
+
+const PTE_PPN_SHIFT: u64 = 10;
+//Sv39 only reaches 256 GiB up from 0
+const SV39_TOP: u64 = 1 << 38;
+
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub struct Region {
+	pub start: u64,
+	pub end: u64, //exclusive, both page aligned
+	pub prot: u64,
+}
+
+pub struct AddressSpace {
+	regions: BTreeMap<u64, Region>, //by start address, never overlapping
+	base: u64, //lowest address that can be mapped
+	limit: u64, //where the page tables start. Nothing at or above this can be mapped
+	leaves: u64, //first leaf table, the rest follow one per 2 MiB
+}
+
+fn page_down(addr: u64) -> u64 {
+	addr & !(PAGE_SIZE - 1)
+}
+
+fn pte(paddr: u64, bits: u64) -> u64 {
+	((paddr >> PAGE_SHIFT) << PTE_PPN_SHIFT) | bits | PTE_V
+}
+
+//leaf pte bits for a protection. riscv has no write only pages, so write gets read too
+//(linux does the same). A and D start set since nothing here cares about them
+fn leaf_bits(prot: u64) -> u64 {
+	if prot & (PROT_READ | PROT_WRITE | PROT_EXEC) == 0 {
+		return 0;
+	}
+	let mut bits = PTE_U | PTE_A | PTE_D;
+	if prot & (PROT_READ | PROT_WRITE) != 0 {
+		bits |= PTE_R;
+	}
+	if prot & PROT_WRITE != 0 {
+		bits |= PTE_W;
+	}
+	if prot & PROT_EXEC != 0 {
+		bits |= PTE_X;
+	}
+	bits
+}
+
+impl AddressSpace {
+	//builds empty page tables for all of dram and turns translation on
+	pub fn New(cpu: &mut Cpu) -> io::Result<AddressSpace> {
+		let base = cpu.bus.dram.base;
+		let top = page_down(base + cpu.bus.dram.size());
+		if !base.is_multiple_of(PAGE_SIZE) || top > SV39_TOP || top <= base {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "user mode memory has to be page aligned and sit in the bottom 256 GiB"));
+		}
+		let middles = ((top - 1) >> 30) - (base >> 30) + 1;
+		let leaves = ((top - 1) >> 21) - (base >> 21) + 1;
+		let tableSize = (1 + middles + leaves) * PAGE_SIZE;
+		if base + tableSize > top {
+			return Err(io::Error::new(io::ErrorKind::InvalidInput, "not enough memory for the page tables"));
+		}
+		let root = top - tableSize;
+		let mm = AddressSpace {
+			regions: BTreeMap::new(),
+			base,
+			limit: root,
+			leaves: root + (1 + middles) * PAGE_SIZE,
+		};
+
+		let fail = |_| io::Error::other("couldnt write the page tables");
+		cpu.bus.write_bytes(root, &vec![0; tableSize as usize]).map_err(fail)?;
+		for gib in 0..middles {
+			let middle = root + (1 + gib) * PAGE_SIZE;
+			let vpn2 = (base >> 30) + gib;
+			cpu.bus.store(root + vpn2 * 8, pte(middle, 0), 8).map_err(fail)?;
+		}
+		for leaf in 0..leaves {
+			let va = ((base >> 21) + leaf) << 21;
+			let middle = root + (1 + (va >> 30) - (base >> 30)) * PAGE_SIZE;
+			let vpn1 = (va >> 21) & 0x1ff;
+			cpu.bus.store(middle + vpn1 * 8, pte(mm.leaves + leaf * PAGE_SIZE, 0), 8).map_err(fail)?;
+		}
+		cpu.store_csr(SATP, (SATP_MODE_SV39 << SATP_MODE_SHIFT) | (root >> PAGE_SHIFT));
+		Ok(mm)
+	}
+
+	//the range pages can be mapped in
+	pub fn base(&self) -> u64 {
+		self.base
+	}
+
+	pub fn limit(&self) -> u64 {
+		self.limit
+	}
+
+	//whether [start, end) is inside the mappable range
+	pub fn fits(&self, start: u64, end: u64) -> bool {
+		start >= self.base && start < end && end <= self.limit
+	}
+
+	//nothing mapped anywhere in [start, end)
+	pub fn is_free(&self, start: u64, end: u64) -> bool {
+		self.fits(start, end) && self.regions.range(..end).map(|(_, r)| r).next_back().is_none_or(|r| r.end <= start)
+	}
+
+	//every page of [start, end) mapped, whatever its protection
+	pub fn is_mapped(&self, start: u64, end: u64) -> bool {
+		let mut at = start;
+		while at < end {
+			match self.regions.range(..=at).map(|(_, r)| r).next_back() {
+				Some(r) if r.end > at => at = r.end,
+				_ => return false,
+			}
+		}
+		true
+	}
+
+	//highest free gap of len bytes that ends at or below top
+	pub fn find_free(&self, len: u64, top: u64) -> Option<u64> {
+		let mut end = top.min(self.limit);
+		for r in self.regions.range(..end).map(|(_, r)| r).rev() {
+			if r.end.saturating_add(len) <= end {
+				break;
+			}
+			end = end.min(r.start);
+		}
+		let start = end.checked_sub(len)?;
+		if start >= self.base && self.is_free(start, end) { Some(start) } else { None }
+	}
+
+	//maps [start, end) with prot, replacing anything already there. The pages come back zeroed
+	pub fn map(&mut self, cpu: &mut Cpu, start: u64, end: u64, prot: u64) {
+		self.cut(start, end);
+		self.insert(Region { start, end, prot });
+		let _ = cpu.bus.write_bytes(start, &vec![0; (end - start) as usize]);
+		self.set_ptes(cpu, start, end, prot);
+	}
+
+	pub fn unmap(&mut self, cpu: &mut Cpu, start: u64, end: u64) {
+		self.cut(start, end);
+		self.set_ptes(cpu, start, end, PROT_NONE);
+	}
+
+	//changes the protection on [start, end), which has to be all mapped already
+	pub fn protect(&mut self, cpu: &mut Cpu, start: u64, end: u64, prot: u64) -> bool {
+		if !self.is_mapped(start, end) {
+			return false;
+		}
+		self.cut(start, end);
+		self.insert(Region { start, end, prot });
+		self.set_ptes(cpu, start, end, prot);
+		true
+	}
+
+	//takes [start, end) out of every region it overlaps, splitting them around it
+	fn cut(&mut self, start: u64, end: u64) {
+		let hit: Vec<Region> = self.regions.range(..end).map(|(_, r)| *r).rev().take_while(|r| r.end > start).collect();
+		for r in hit {
+			self.regions.remove(&r.start);
+			if r.start < start {
+				self.regions.insert(r.start, Region { end: start, ..r });
+			}
+			if r.end > end {
+				self.regions.insert(end, Region { start: end, ..r });
+			}
+		}
+	}
+
+	//adds a region into a gap, merging it with neighbours that have the same protection
+	fn insert(&mut self, mut region: Region) {
+		if let Some(&before) = self.regions.range(..region.start).map(|(_, r)| r).next_back() {
+			if before.end == region.start && before.prot == region.prot {
+				self.regions.remove(&before.start);
+				region.start = before.start;
+			}
+		}
+		if let Some(&after) = self.regions.get(&region.end) {
+			if after.prot == region.prot {
+				self.regions.remove(&after.start);
+				region.end = after.end;
+			}
+		}
+		self.regions.insert(region.start, region);
+	}
+
+	fn set_ptes(&self, cpu: &mut Cpu, start: u64, end: u64, prot: u64) {
+		let bits = leaf_bits(prot);
+		for page in (start..end).step_by(PAGE_SIZE as usize) {
+			let leaf = self.leaves + ((page >> 21) - (self.base >> 21)) * PAGE_SIZE;
+			let entry = if bits == 0 { 0 } else { pte(page, bits) };
+			let _ = cpu.bus.store(leaf + ((page >> PAGE_SHIFT) & 0x1ff) * 8, entry, 8);
+		}
+		//same as the sfence.vma the kernel would do
+		cpu.tlb.flush(None, None);
+	}
+}
+
+//whether the guest could do access to all of [addr, addr+len) right now.
+//Goes through the mmu, so the page tables are the only thing that decides
+pub fn accessible(cpu: &mut Cpu, addr: u64, len: u64, access: AccessType) -> bool {
+	if len == 0 {
+		return true;
+	}
+	let Some(last) = addr.checked_add(len - 1) else { return false };
+	(page_down(addr)..=page_down(last)).step_by(PAGE_SIZE as usize).all(|page| cpu.translate(page, access).is_ok())
+}
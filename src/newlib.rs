@@ -0,0 +1,207 @@
+//host side of the libgloss syscalls, so bare metal programs linked against newlib can
+//printf, read files and exit without any devices
+//
+//libgloss does an ecall with the syscall number in a7 and the args in a0-a5, and takes
+//a0 back, a negative one being -errno. The numbers are the asm-generic linux ones, plus
+//the old open/unlink/... ones at 1024 and up. With --newlib the cpu doesnt trap on ecall
+//at all, it just flags it and service() does the call before the next instruction
+//
+//addresses are physical, whatever privilege the program runs at
+#![allow(non_snake_case)]
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::os::fd::AsFd;
+use std::os::unix::fs::OpenOptionsExt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::cpu::Cpu;
+use crate::linux::{push64, stat_bytes};
+
+const SYS_OPENAT: u64 = 56;
+const SYS_CLOSE: u64 = 57;
+const SYS_LSEEK: u64 = 62;
+const SYS_READ: u64 = 63;
+const SYS_WRITE: u64 = 64;
+const SYS_FSTAT: u64 = 80;
+const SYS_EXIT: u64 = 93;
+const SYS_EXIT_GROUP: u64 = 94;
+const SYS_GETTIMEOFDAY: u64 = 169;
+const SYS_BRK: u64 = 214;
+const SYS_OPEN: u64 = 1024;
+
+//newlib's errnos. The classic unix ones up to ERANGE are the same numbers as linux,
+//past that they go their own way
+const EIO: i64 = 5;
+const EBADF: i64 = 9;
+const EFAULT: i64 = 14;
+const EINVAL: i64 = 22;
+const EMFILE: i64 = 24;
+const ERANGE: i64 = 34;
+const ENOSYS: i64 = 88;
+
+//newlib's open flags, which arent linux's
+const O_ACCMODE: u64 = 0x3;
+const O_APPEND: u64 = 0x8;
+const O_CREAT: u64 = 0x200;
+const O_TRUNC: u64 = 0x400;
+const O_EXCL: u64 = 0x800;
+
+const MAX_FILES: usize = 256;
+
+pub struct Newlib {
+	files: Vec<Option<File>>, //host file behind each guest fd
+	heapStart: u64, //end of the program, where brk starts
+	brk: u64,
+}
+
+fn errno(e: io::Error) -> i64 {
+	match e.raw_os_error() {
+		Some(e) if e as i64 <= ERANGE => e as i64,
+		_ => EIO,
+	}
+}
+
+impl Newlib {
+	//heapStart is the first byte past the program
+	pub fn New(heapStart: u64) -> Newlib {
+		let stdio = |fd: io::Result<std::os::fd::OwnedFd>| fd.ok().map(File::from);
+		Newlib {
+			files: vec![
+				stdio(io::stdin().as_fd().try_clone_to_owned()),
+				stdio(io::stdout().as_fd().try_clone_to_owned()),
+				stdio(io::stderr().as_fd().try_clone_to_owned()),
+			],
+			heapStart,
+			brk: heapStart,
+		}
+	}
+
+	//call after every step. Does the syscall if the last instruction was an ecall,
+	//and gives back the exit status once the program has exited
+	pub fn service(&mut self, cpu: &mut Cpu) -> Option<i32> {
+		if !cpu.ecalled {
+			return None;
+		}
+		cpu.ecalled = false;
+		self.syscall(cpu)
+	}
+
+	pub fn syscall(&mut self, cpu: &mut Cpu) -> Option<i32> {
+		let a = [cpu.regs[10], cpu.regs[11], cpu.regs[12], cpu.regs[13]];
+		let result = match cpu.regs[17] {
+			SYS_EXIT | SYS_EXIT_GROUP => return Some(a[0] as i32),
+			SYS_READ => self.read(cpu, a[0], a[1], a[2]),
+			SYS_WRITE => self.write(cpu, a[0], a[1], a[2]),
+			SYS_OPEN => self.open(cpu, a[0], a[1], a[2]),
+			//only ever called with AT_FDCWD
+			SYS_OPENAT => self.open(cpu, a[1], a[2], a[3]),
+			SYS_CLOSE => match self.files.get_mut(a[0] as usize).and_then(|f| f.take()) {
+				Some(_) => Ok(0),
+				None => Err(EBADF),
+			},
+			SYS_LSEEK => self.lseek(a[0], a[1], a[2]),
+			SYS_FSTAT => self.file(a[0]).and_then(|f| f.metadata().map_err(errno))
+				.and_then(|m| cpu.bus.write_bytes(a[1], &stat_bytes(&m)).map_err(|_| EFAULT)).map(|_| 0),
+			SYS_GETTIMEOFDAY => {
+				let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+				let mut buf = Vec::new();
+				push64(&mut buf, now.as_secs());
+				push64(&mut buf, now.subsec_micros() as u64);
+				cpu.bus.write_bytes(a[0], &buf).map(|_| 0).map_err(|_| EFAULT)
+			},
+			SYS_BRK => Ok(self.set_brk(cpu, a[0])),
+			_ => Err(ENOSYS),
+		};
+		cpu.regs[10] = match result {
+			Ok(val) => val,
+			Err(e) => (-e) as u64,
+		};
+		None
+	}
+
+	fn file(&mut self, fd: u64) -> Result<&mut File, i64> {
+		self.files.get_mut(fd as usize).and_then(|f| f.as_mut()).ok_or(EBADF)
+	}
+
+	fn read(&mut self, cpu: &mut Cpu, fd: u64, addr: u64, len: u64) -> Result<u64, i64> {
+		if !cpu.bus.dram.contains(addr, len) {
+			return Err(EFAULT);
+		}
+		let mut buf = vec![0; len as usize];
+		let n = self.file(fd)?.read(&mut buf).map_err(errno)?;
+		cpu.bus.write_bytes(addr, &buf[..n]).map_err(|_| EFAULT)?;
+		Ok(n as u64)
+	}
+
+	fn write(&mut self, cpu: &mut Cpu, fd: u64, addr: u64, len: u64) -> Result<u64, i64> {
+		let data = cpu.bus.read_bytes(addr, len).map_err(|_| EFAULT)?;
+		let file = self.file(fd)?;
+		let n = file.write(&data).map_err(errno)?;
+		//printf without a newline still has to show up before the program exits
+		let _ = file.flush();
+		Ok(n as u64)
+	}
+
+	fn open(&mut self, cpu: &mut Cpu, addr: u64, flags: u64, mode: u64) -> Result<u64, i64> {
+		let mut path = Vec::new();
+		loop {
+			let b = cpu.bus.load(addr.wrapping_add(path.len() as u64), 1).map_err(|_| EFAULT)? as u8;
+			if b == 0 {
+				break;
+			}
+			if path.len() == 4096 {
+				return Err(ERANGE);
+			}
+			path.push(b);
+		}
+		let mut opts = OpenOptions::new();
+		match flags & O_ACCMODE {
+			0 => opts.read(true),
+			1 => opts.write(true),
+			_ => opts.read(true).write(true),
+		};
+		if flags & O_CREAT != 0 {
+			if flags & O_EXCL != 0 { opts.create_new(true) } else { opts.create(true) };
+		}
+		opts.truncate(flags & O_TRUNC != 0);
+		opts.append(flags & O_APPEND != 0);
+		opts.mode(mode as u32);
+		let file = opts.open(String::from_utf8_lossy(&path).as_ref()).map_err(errno)?;
+		//lowest free fd, same as anywhere else
+		match self.files.iter().position(|f| f.is_none()) {
+			Some(fd) => {
+				self.files[fd] = Some(file);
+				Ok(fd as u64)
+			},
+			None if self.files.len() < MAX_FILES => {
+				self.files.push(Some(file));
+				Ok(self.files.len() as u64 - 1)
+			},
+			None => Err(EMFILE),
+		}
+	}
+
+	fn lseek(&mut self, fd: u64, offset: u64, whence: u64) -> Result<u64, i64> {
+		let pos = match whence {
+			0 => SeekFrom::Start(offset),
+			1 => SeekFrom::Current(offset as i64),
+			2 => SeekFrom::End(offset as i64),
+			_ => return Err(EINVAL),
+		};
+		self.file(fd)?.seek(pos).map_err(errno)
+	}
+
+	//the heap can grow from the end of the program up to the stack pointer.
+	//Anything else (0 included) gets told where the break is now
+	fn set_brk(&mut self, cpu: &mut Cpu, addr: u64) -> u64 {
+		if addr < self.heapStart || addr >= cpu.regs[2] || !cpu.bus.dram.contains(self.heapStart, addr - self.heapStart) {
+			return self.brk;
+		}
+		if addr > self.brk {
+			let _ = cpu.bus.write_bytes(self.brk, &vec![0; (addr - self.brk) as usize]);
+		}
+		self.brk = addr;
+		self.brk
+	}
+}
@@ -0,0 +1,199 @@
+//platform level interrupt controller
+//collects interrupt lines from devices and hands them out to harts
+//each hart gets two contexts: 2*hart for machine mode (MEIP) and 2*hart+1 for
+//supervisor mode (SEIP), same as qemu's virt machine, which is what our device trees expect
+//
+//register layout (all 32 bit):
+//+0x0 + 4*source            priority
+//+0x1000                    pending bits
+//+0x2000 + 0x80*context     enable bits
+//+0x200000 + 0x1000*context threshold, then claim/complete at +4
+//
+//lines are level triggered. A source thats been claimed wont pend again
+//until its completed
+
+use crate::device::Device;
+
+pub const PLIC_BASE: u64 = 0x0c00_0000;
+pub const PLIC_SIZE: u64 = 0x0400_0000;
+//source 0 doesnt exist, so this gives 1-63
+pub const PLIC_SOURCES: usize = 64;
+
+const PRIORITY_OFFSET: u64 = 0x0;
+const PENDING_OFFSET: u64 = 0x1000;
+const ENABLE_OFFSET: u64 = 0x2000;
+const ENABLE_STRIDE: u64 = 0x80;
+const CONTEXT_OFFSET: u64 = 0x20_0000;
+const CONTEXT_STRIDE: u64 = 0x1000;
+
+#[derive(Clone)]
+pub struct Plic {
+	priority: [u32; PLIC_SOURCES],
+	pending: u64,
+	claimed: u64, //being handled by someone, waiting on a complete
+	level: u64, //what the device lines are currently doing
+	enable: Vec<u64>, //per context
+	threshold: Vec<u32>, //per context
+}
+
+impl Plic {
+	pub fn New(harts: usize) -> Plic {
+		Plic {
+			priority: [0; PLIC_SOURCES],
+			pending: 0,
+			claimed: 0,
+			level: 0,
+			enable: vec![0; harts * 2],
+			threshold: vec![0; harts * 2],
+		}
+	}
+
+	//devices call this with the state of their interrupt line
+	pub fn set_irq(&mut self, source: usize, high: bool) {
+		if source == 0 || source >= PLIC_SOURCES {
+			return;
+		}
+		let bit = 1u64 << source;
+		if high {
+			self.level |= bit;
+			if self.claimed & bit == 0 {
+				self.pending |= bit;
+			}
+		}
+		else {
+			self.level &= !bit;
+			self.pending &= !bit;
+		}
+	}
+
+	//highest priority source this context could claim. Ties go to the lowest id
+	fn best(&self, context: usize) -> Option<usize> {
+		let candidates = self.pending & self.enable[context];
+		let mut best = None;
+		let mut bestPriority = self.threshold[context];
+		for source in 1..PLIC_SOURCES {
+			if candidates & (1 << source) != 0 && self.priority[source] > bestPriority {
+				best = Some(source);
+				bestPriority = self.priority[source];
+			}
+		}
+		best
+	}
+
+	//whether a context has an interrupt waiting (what drives MEIP/SEIP)
+	pub fn interrupt(&self, context: usize) -> bool {
+		context < self.enable.len() && self.best(context).is_some()
+	}
+
+	fn claim(&mut self, context: usize) -> u32 {
+		match self.best(context) {
+			Some(source) => {
+				self.pending &= !(1 << source);
+				self.claimed |= 1 << source;
+				source as u32
+			},
+			None => 0,
+		}
+	}
+
+	fn complete(&mut self, context: usize, source: u32) {
+		let source = source as usize;
+		//completes for sources the context cant see are ignored
+		if source == 0 || source >= PLIC_SOURCES || self.enable[context] & (1 << source) == 0 {
+			return;
+		}
+		self.claimed &= !(1 << source);
+		//still being held high, so it goes straight back to pending
+		if self.level & (1 << source) != 0 {
+			self.pending |= 1 << source;
+		}
+	}
+}
+
+impl Device for Plic {
+	//offset is relative to the start of the plic
+	//everything is done a 32 bit word at a time
+	//claiming has a side effect, so this needs &mut
+	fn load(&mut self, offset: u64, size: u8) -> u64 {
+		let word = offset & !0x3;
+		let val = match word {
+			PRIORITY_OFFSET..=0xfff => {
+				self.priority.get((word / 4) as usize).copied().unwrap_or(0)
+			},
+			PENDING_OFFSET..=0x1fff => {
+				match word - PENDING_OFFSET {
+					0 => self.pending as u32,
+					4 => (self.pending >> 32) as u32,
+					_ => 0,
+				}
+			},
+			ENABLE_OFFSET..=0x1f_ffff => {
+				let context = ((word - ENABLE_OFFSET) / ENABLE_STRIDE) as usize;
+				let enable = self.enable.get(context).copied().unwrap_or(0);
+				match (word - ENABLE_OFFSET) % ENABLE_STRIDE {
+					0 => enable as u32,
+					4 => (enable >> 32) as u32,
+					_ => 0,
+				}
+			},
+			_ => {
+				let context = ((word - CONTEXT_OFFSET) / CONTEXT_STRIDE) as usize;
+				if word < CONTEXT_OFFSET || context >= self.threshold.len() {
+					0
+				}
+				else {
+					match (word - CONTEXT_OFFSET) % CONTEXT_STRIDE {
+						0 => self.threshold[context],
+						4 => self.claim(context),
+						_ => 0,
+					}
+				}
+			},
+		};
+		let val = (val as u64) >> ((offset & 0x3) * 8);
+		match size {
+			1 | 2 => val & ((1u64 << (size as u64 * 8)) - 1),
+			_ => val,
+		}
+	}
+
+	fn store(&mut self, offset: u64, data: u64, _size: u8) {
+		let word = offset & !0x3;
+		let data = data as u32;
+		match word {
+			PRIORITY_OFFSET..=0xfff => {
+				//source 0 doesnt exist
+				let source = (word / 4) as usize;
+				if source != 0 && source < PLIC_SOURCES {
+					self.priority[source] = data & 0x7;
+				}
+			},
+			//pending bits are read only
+			PENDING_OFFSET..=0x1fff => {},
+			ENABLE_OFFSET..=0x1f_ffff => {
+				let context = ((word - ENABLE_OFFSET) / ENABLE_STRIDE) as usize;
+				if let Some(enable) = self.enable.get_mut(context) {
+					match (word - ENABLE_OFFSET) % ENABLE_STRIDE {
+						0 => *enable = (*enable & !0xffff_ffff) | (data & !1) as u64,
+						4 => *enable = (*enable & 0xffff_ffff) | ((data as u64) << 32),
+						_ => {},
+					}
+				}
+			},
+			_ => {
+				let context = ((word - CONTEXT_OFFSET) / CONTEXT_STRIDE) as usize;
+				if word >= CONTEXT_OFFSET && context < self.threshold.len() {
+					match (word - CONTEXT_OFFSET) % CONTEXT_STRIDE {
+						0 => self.threshold[context] = data & 0x7,
+						4 => self.complete(context, data),
+						_ => {},
+					}
+				}
+			},
+		}
+	}
+
+	fn reset(&mut self) {
+		*self = Plic::New(self.enable.len() / 2);
+	}
+}
@@ -1,3 +1,5 @@
+use crate::trap::Exception;
+
 //apparently there are many forms of instruciton formats
 //but to start Ill just support the main 6
 //will be slightly memory inefficient (opcode has 7 bits, stored in a u8)
@@ -120,16 +122,50 @@ pub enum Instruction {
 	FMVXW,
 	FMVWX,
 	FCLASSS,
-	FMADDD, //RV64D (Not all isnt yet)
+	FMADDD, //RV64D
 	FMSUBD,
 	FNMSUBD,
 	FNMADDD,
+	FLD,
+	FSD,
+	FADDD,
+	FSUBD,
+	FMULD,
+	FDIVD,
+	FSQRTD,
+	FMIND,
+	FMAXD,
+	FEQD,
+	FLTD,
+	FLED,
+	FSGNJD,
+	FSGNJND,
+	FSGNJXD,
+	FCVTSD,
+	FCVTDS,
+	FCVTWD,
+	FCVTWUD,
+	FCVTLD,
+	FCVTLUD,
+	FCVTDW,
+	FCVTDWU,
+	FCVTDL,
+	FCVTDLU,
+	FMVXD,
+	FMVDX,
+	FCLASSD,
 	CSRRW, //RV64CSR
 	CSRRS,
 	CSRRC,
 	CSRRWI,
 	CSRRSI,
 	CSRRCI,
+	ECALL, //system instructions
+	EBREAK,
+	SRET,
+	MRET,
+	WFI,
+	SFENCEVMA,
 	AMOADDW, //RV64A
 	AMOADDD,
 	AMOANDW,
@@ -162,11 +198,12 @@ pub struct R4Inst {
 	pub rs2: u8,
 	pub rs3: u8,
 	pub rd: u8,
+	pub rm: u8, //rounding mode, funct3
 	pub instName: Instruction,
 }
 
 impl R4Inst {
-	pub fn New(code: u32) -> R4Inst {
+	pub fn New(code: u32) -> Result<R4Inst, Exception> {
 		let opcode = code & 0x7f;
 		let funct2 = (code >> 25) & 0x3;
 		let inst = R4Inst {
@@ -174,6 +211,7 @@ impl R4Inst {
 			rs2: ((code >> 20) & 0x1f) as u8,
 			rs3: ((code>> 27) & 0x1f) as u8,
 			rd: ((code >> 7) & 0x1f) as u8,
+			rm: ((code >> 12) & 0x7) as u8,
 			instName: match (funct2, opcode) {
 				(0, 67) => Instruction::FMADDS,
 				(1, 67) => Instruction::FMADDD,
@@ -183,10 +221,10 @@ impl R4Inst {
 				(1, 75) => Instruction::FNMSUBD,
 				(0, 79) => Instruction::FNMADDS,
 				(1, 79) => Instruction::FNMADDD,
-				(_,_) => panic!("Invalid R4 type instruction opcode"),
+				(_,_) => return Err(Exception::IllegalInstruction(code as u64)),
 			}
 		};
-		inst
+		Ok(inst)
 	}
 }
 
@@ -199,11 +237,12 @@ pub struct RegRegInst {
 	pub rs1: u8, //source register 1
 	pub rs2: u8, //source register 2
 	pub rd: u8,	 //destination register
+	pub rm: u8, //funct3. Only means anything to float ops that round, where its the rounding mode
 	pub instName: Instruction
 }
 
 impl RegRegInst {
-	pub fn New(code:u32) -> RegRegInst {
+	pub fn New(code: u32) -> Result<RegRegInst, Exception> {
 		let funct3 = (code >> 12) & 0x7;
 		let funct7 = (code >> 25) & 0x7f;
 		let opcode = code & 0x7f;
@@ -216,6 +255,7 @@ impl RegRegInst {
 						rd: (((code >> 7) & 0x1f) as u8),
 						rs1: (((code >> 15) & 0x1f) as u8),
 						rs2: (((code >> 20) & 0x1f) as u8),
+						rm: funct3 as u8,
 						instName: match funct3 {
 							0 => Instruction::MUL,
 							1 => Instruction::MULH,
@@ -225,10 +265,10 @@ impl RegRegInst {
 							5 => Instruction::DIVU,
 							6 => Instruction::REM,
 							7 => Instruction::REMU,
-							_=> panic!("Invalid R-type opcode 32 bit RV32M"),
+							_=> return Err(Exception::IllegalInstruction(code as u64)),
 						}
 					};
-					inst
+					Ok(inst)
 				}
 				else {
 				//RV32I inst
@@ -236,6 +276,7 @@ impl RegRegInst {
 						rd: (((code >> 7) & 0x1f) as u8),
 						rs1: (((code >> 15) & 0x1f) as u8),
 						rs2: (((code >> 20) & 0x1f) as u8),
+						rm: funct3 as u8,
 						instName: match (funct7, funct3) {
 							(0,0) => Instruction::ADD,
 							(32,0) => Instruction::SUB,
@@ -247,10 +288,10 @@ impl RegRegInst {
 							(32,5) => Instruction::SRA,
 							(0,6) => Instruction::OR,
 							(0,7) => Instruction::AND,
-							_=> panic!("Invalid R-type opcode 32 bit RV32I"),
+							_=> return Err(Exception::IllegalInstruction(code as u64)),
 						}
 					};
-					inst
+					Ok(inst)
 				}
 			},
 			59 => {
@@ -260,16 +301,17 @@ impl RegRegInst {
 						rd: (((code >> 7) & 0x1f) as u8),
 						rs1: (((code >> 15) & 0x1f) as u8),
 						rs2: (((code >> 20) & 0x1f) as u8),
+						rm: funct3 as u8,
 						instName: match funct3 {
 							0 => Instruction::MULW,
 							4 => Instruction::DIVW,
 							5 => Instruction::DIVUW,
 							6 => Instruction::REMW,
 							7 => Instruction::REMUW,
-							_=> panic!("Invalid R-type 64bit funct RV64I" ),
+							_=> return Err(Exception::IllegalInstruction(code as u64)),
 						}
 					};
-					inst
+					Ok(inst)
 				}
 				else {
 				//RV64I instructions
@@ -277,69 +319,126 @@ impl RegRegInst {
 						rd: (((code >> 7) & 0x1f) as u8),
 						rs1: (((code >> 15) & 0x1f) as u8),
 						rs2: (((code >> 20) & 0x1f) as u8),
+						rm: funct3 as u8,
 						instName: match (funct7, funct3) {
 							(0,0) => Instruction::ADDW,
 							(32,0) => Instruction::SUBW,
 							(0,1) => Instruction::SLLW,
 							(32,5) => Instruction::SRAW,
 							(0,5) => Instruction::SRLW,
-							_=> panic!("Invalid R-type 64bit funct RV64I" ),
+							_=> return Err(Exception::IllegalInstruction(code as u64)),
 						}
 					};
-					inst
+					Ok(inst)
 				}
 			},
 			47 => {
-				//adjusting for aq/rl bits, so its really more a funct5
-				let funct7 = funct7 >> 2;
-				//RV32A Instructions
-				if funct3 == 2 {
-					let inst = RegRegInst {
-						rd: (((code >> 7) & 0x1f) as u8),
-						rs1: (((code >> 15) & 0x1f) as u8),
-						rs2: (((code >> 20) & 0x1f) as u8),
-						instName: match (funct7) {
-							(0) => Instruction::AMOADDW,
-							(1) => Instruction::AMOSWAPW,
-							(2) => Instruction::LRW,
-							(3) => Instruction::SCW,
-							(4) => Instruction::AMOXORW,
-							(8) => Instruction::AMOORW,
-							(12) => Instruction::AMOANDW,
-							(16) => Instruction::AMOMINW,
-							(20) => Instruction::AMOMAXW,
-							(24) => Instruction::AMOMINUW,
-							(28) => Instruction::AMOMAXUW,
-							_=> panic!("Invalid R-type 64bit funct RV32a" ),
-						}
-					};
-					inst
-				}
-				//RV64A Instructions
-				else {
-					let inst = RegRegInst {
-						rd: (((code >> 7) & 0x1f) as u8),
-						rs1: (((code >> 15) & 0x1f) as u8),
-						rs2: (((code >> 20) & 0x1f) as u8),
-						instName: match (funct7) {
-							(0) => Instruction::AMOADDD,
-							(1) => Instruction::AMOSWAPD,
-							(2) => Instruction::LRD,
-							(3) => Instruction::SCD,
-							(4) => Instruction::AMOXORD,
-							(8) => Instruction::AMOORD,
-							(12) => Instruction::AMOANDD,
-							(16) => Instruction::AMOMIND,
-							(20) => Instruction::AMOMAXD,
-							(24) => Instruction::AMOMINUD,
-							(28) => Instruction::AMOMAXUD,
-							_=> panic!("Invalid R-type 64bit funct RV64A" ),
-						}
-					};
-					inst
-				}
+				//RV64A. The bottom two bits of funct7 are aq/rl, which ask for ordering against
+				//other harts. Every access here already happens in program order, so they
+				//decode fine but dont change anything. funct3 picks word or doubleword
+				let funct5 = funct7 >> 2;
+				let rs2 = (code >> 20) & 0x1f;
+				let inst = RegRegInst {
+					rd: (((code >> 7) & 0x1f) as u8),
+					rs1: (((code >> 15) & 0x1f) as u8),
+					rs2: rs2 as u8,
+					rm: funct3 as u8,
+					instName: match (funct5, funct3) {
+						(0, 2) => Instruction::AMOADDW,
+						(1, 2) => Instruction::AMOSWAPW,
+						//lr has no rs2, and anything else there is reserved
+						(2, 2) if rs2 == 0 => Instruction::LRW,
+						(3, 2) => Instruction::SCW,
+						(4, 2) => Instruction::AMOXORW,
+						(8, 2) => Instruction::AMOORW,
+						(12, 2) => Instruction::AMOANDW,
+						(16, 2) => Instruction::AMOMINW,
+						(20, 2) => Instruction::AMOMAXW,
+						(24, 2) => Instruction::AMOMINUW,
+						(28, 2) => Instruction::AMOMAXUW,
+						(0, 3) => Instruction::AMOADDD,
+						(1, 3) => Instruction::AMOSWAPD,
+						(2, 3) if rs2 == 0 => Instruction::LRD,
+						(3, 3) => Instruction::SCD,
+						(4, 3) => Instruction::AMOXORD,
+						(8, 3) => Instruction::AMOORD,
+						(12, 3) => Instruction::AMOANDD,
+						(16, 3) => Instruction::AMOMIND,
+						(20, 3) => Instruction::AMOMAXD,
+						(24, 3) => Instruction::AMOMINUD,
+						(28, 3) => Instruction::AMOMAXUD,
+						_=> return Err(Exception::IllegalInstruction(code as u64)),
+					}
+				};
+				Ok(inst)
+			},
+			83 => {
+				//float ops. funct7 picks the operation, and for the ones that only have one
+				//source, rs2 picks the variant. funct3 is the rounding mode for anything that rounds,
+				//otherwise it picks the variant too
+				let inst = RegRegInst {
+					rd: (((code >> 7) & 0x1f) as u8),
+					rs1: (((code >> 15) & 0x1f) as u8),
+					rs2: (((code >> 20) & 0x1f) as u8),
+					rm: funct3 as u8,
+					instName: match (funct7, (code >> 20) & 0x1f, funct3) {
+						(0x00, _, _) => Instruction::FADDS,
+						(0x04, _, _) => Instruction::FSUBS,
+						(0x08, _, _) => Instruction::FMULS,
+						(0x0c, _, _) => Instruction::FDIVS,
+						(0x2c, 0, _) => Instruction::FSQRTS,
+						(0x10, _, 0) => Instruction::FSGNJS,
+						(0x10, _, 1) => Instruction::FSGNJNS,
+						(0x10, _, 2) => Instruction::FSGNJXS,
+						(0x14, _, 0) => Instruction::FMINS,
+						(0x14, _, 1) => Instruction::FMAXS,
+						(0x60, 0, _) => Instruction::FCVTWS,
+						(0x60, 1, _) => Instruction::FCVTWUS,
+						(0x60, 2, _) => Instruction::FCVTLS,
+						(0x60, 3, _) => Instruction::FCVTLUS,
+						(0x68, 0, _) => Instruction::FCVTSW,
+						(0x68, 1, _) => Instruction::FCVTSWU,
+						(0x68, 2, _) => Instruction::FCVTSL,
+						(0x68, 3, _) => Instruction::FCVTSLU,
+						(0x70, 0, 0) => Instruction::FMVXW,
+						(0x70, 0, 1) => Instruction::FCLASSS,
+						(0x50, _, 2) => Instruction::FEQS,
+						(0x50, _, 1) => Instruction::FLTS,
+						(0x50, _, 0) => Instruction::FLES,
+						(0x78, 0, 0) => Instruction::FMVWX,
+						//doubles have bit 0 of funct7 set
+						(0x01, _, _) => Instruction::FADDD,
+						(0x05, _, _) => Instruction::FSUBD,
+						(0x09, _, _) => Instruction::FMULD,
+						(0x0d, _, _) => Instruction::FDIVD,
+						(0x2d, 0, _) => Instruction::FSQRTD,
+						(0x11, _, 0) => Instruction::FSGNJD,
+						(0x11, _, 1) => Instruction::FSGNJND,
+						(0x11, _, 2) => Instruction::FSGNJXD,
+						(0x15, _, 0) => Instruction::FMIND,
+						(0x15, _, 1) => Instruction::FMAXD,
+						(0x20, 1, _) => Instruction::FCVTSD,
+						(0x21, 0, _) => Instruction::FCVTDS,
+						(0x61, 0, _) => Instruction::FCVTWD,
+						(0x61, 1, _) => Instruction::FCVTWUD,
+						(0x61, 2, _) => Instruction::FCVTLD,
+						(0x61, 3, _) => Instruction::FCVTLUD,
+						(0x69, 0, _) => Instruction::FCVTDW,
+						(0x69, 1, _) => Instruction::FCVTDWU,
+						(0x69, 2, _) => Instruction::FCVTDL,
+						(0x69, 3, _) => Instruction::FCVTDLU,
+						(0x71, 0, 0) => Instruction::FMVXD,
+						(0x71, 0, 1) => Instruction::FCLASSD,
+						(0x51, _, 2) => Instruction::FEQD,
+						(0x51, _, 1) => Instruction::FLTD,
+						(0x51, _, 0) => Instruction::FLED,
+						(0x79, 0, 0) => Instruction::FMVDX,
+						_ => return Err(Exception::IllegalInstruction(code as u64)),
+					}
+				};
+				Ok(inst)
 			},
-			_ => {panic!("Invalid opcode for R type instruction");},
+			_ => {return Err(Exception::IllegalInstruction(code as u64));},
 		}
 	}
 }
@@ -359,7 +458,7 @@ pub struct RegImmInst {
 }
 
 impl RegImmInst {
-	pub fn New(code:u32) -> RegImmInst {
+	pub fn New(code: u32) -> Result<RegImmInst, Exception> {
 		let opcode = (code & 0x7f);
 		//immedate instructions
 		//havent implemented shift instructions yet - SHAMT will equal the imm val
@@ -378,20 +477,21 @@ impl RegImmInst {
 							match ((code >> 30)) {
 								1 =>  Instruction::SRAI,
 								0 =>  Instruction::SRLI,
-								_ => panic!("Invalid sshift instruction"),
+								_ => return Err(Exception::IllegalInstruction(code as u64)),
 							}
 						},
 						6 => Instruction::ORI,
 						7 => Instruction::ANDI,
-						_ => panic!("Invalid immediate funct val"),
+						_ => return Err(Exception::IllegalInstruction(code as u64)),
 					},
 					rs1: (((code >> 15) & 0x1f) as u8),
 					imm: (((code as i32) >> 20) as i16),
 				};
+				//shamt is 6 bits on rv64, the rest is the funct bits saying its an srai
 				if inst.instName == Instruction::SRAI {
-					inst.imm = inst.imm & 0x1f;
+					inst.imm = inst.imm & 0x3f;
 				}
-				inst
+				Ok(inst)
 			},
 			27 => {
 				let mut inst = RegImmInst {
@@ -404,10 +504,10 @@ impl RegImmInst {
 							match ((code >> 30)) {
 								1 =>  Instruction::SRAIW,
 								0 =>  Instruction::SRLIW,
-								_ => panic!("Invalid sshift instruction"),
+								_ => return Err(Exception::IllegalInstruction(code as u64)),
 							}
 						},
-						_ => panic!("Invalid immediate funct val {} for {:b}",(code >> 12) & 0x7 ,code),
+						_ => return Err(Exception::IllegalInstruction(code as u64)),
 					},
 					rs1: (((code >> 15) & 0x1f) as u8),
 					imm: (((code as i32) >> 20) as i16),
@@ -415,7 +515,7 @@ impl RegImmInst {
 				if inst.instName == Instruction::SRAIW {
 					inst.imm = inst.imm & 0x1f;
 				}
-				inst
+				Ok(inst)
 			},
 			3=> {
 		//Load instructions
@@ -429,12 +529,26 @@ impl RegImmInst {
 						4 => Instruction::LBU,
 						5 => Instruction::LHU,
 						6 => Instruction::LWU,
-						_ => panic!("Invalid immeidate funct val"),
+						_ => return Err(Exception::IllegalInstruction(code as u64)),
+					},
+					rs1: (((code >> 15) & 0x1f) as u8),
+					imm: (((code as i32) >> 20) as i16),
+				};
+				Ok(inst)
+			},
+			7 => {
+				//float loads. funct3 is the width
+				let inst = RegImmInst {
+					rd: (((code >> 7) & 0x1f) as u8),
+					instName: match ((code >> 12) & 0x7) {
+						2 => Instruction::FLW,
+						3 => Instruction::FLD,
+						_ => return Err(Exception::IllegalInstruction(code as u64)),
 					},
 					rs1: (((code >> 15) & 0x1f) as u8),
 					imm: (((code as i32) >> 20) as i16),
 				};
-				inst
+				Ok(inst)
 			},
 			103 => {
 				let inst = RegImmInst {
@@ -443,7 +557,7 @@ impl RegImmInst {
 					rs1: (((code >> 15) & 0x1f) as u8),
 					imm: (((code as i32) >> 20) as i16),
 				};
-				inst
+				Ok(inst)
 			}
 			15 => { //technically need to differentiate btn FENCE and FENCEI. But. I dont implement either, as emulator is singe threaded
 				let inst = RegImmInst {
@@ -452,26 +566,42 @@ impl RegImmInst {
 					rs1: (((code >> 15) & 0x1f) as u8),
 					imm: (((code as i32) >> 20) as i16),
 				};
-				inst
+				Ok(inst)
 			}
-			115 => { //CSR Instructions
+			115 => { //CSR + system Instructions
 				let inst = RegImmInst {
 					rd: (((code >> 7) & 0x1f) as u8),
 					instName: match ((code >> 12) & 0x7) {
+						//funct3 of 0 is the system group, told apart by the top 12 bits
+						//(or top 7 for sfence.vma, which has rs2 in there)
+						//rd has to be 0 for all of them, and rs1 for everything but sfence.vma
+						0 => {
+							let rd = (code >> 7) & 0x1f;
+							let rs1 = (code >> 15) & 0x1f;
+							match (code >> 20, rs1, rd) {
+								(0x000, 0, 0) => Instruction::ECALL,
+								(0x001, 0, 0) => Instruction::EBREAK,
+								(0x102, 0, 0) => Instruction::SRET,
+								(0x302, 0, 0) => Instruction::MRET,
+								(0x105, 0, 0) => Instruction::WFI,
+								(funct12, _, 0) if (funct12 >> 5) == 0x09 => Instruction::SFENCEVMA,
+								_ => return Err(Exception::IllegalInstruction(code as u64)),
+							}
+						},
 						1 => Instruction::CSRRW,
 						2 => Instruction::CSRRS,
 						3 => Instruction::CSRRC,
 						5 => Instruction::CSRRWI,
 						6 => Instruction::CSRRSI,
 						7 => Instruction::CSRRCI,
-						_ => panic!("Invalid immeidate funct val"),
+						_ => return Err(Exception::IllegalInstruction(code as u64)),
 					},
 					rs1: (((code >> 15) & 0x1f) as u8),
-					imm: (((code as u32) >> 20) as i16), //this is CSR offset
+					imm: (((code as u32) >> 20) as i16), //this is CSR offset (or rs2 for sfence.vma)
 				};
-				inst
+				Ok(inst)
 			}
-			_ => panic!("invalid opcode form immediate instruction {:b}", opcode),
+			_ => return Err(Exception::IllegalInstruction(code as u64)),
 		}
 
 	}
@@ -494,14 +624,16 @@ pub struct StoreInst {
 }
 
 impl StoreInst {
-	pub fn New(code: u32) -> StoreInst {
+	pub fn New(code: u32) -> Result<StoreInst, Exception> {
 		let inst = StoreInst {	
-			instName: match ((code >> 12) & 0x7) {
-				0 => Instruction::SB,
-				1 => Instruction::SH,
-				2 => Instruction::SW,
-				3 => Instruction::SD,
-				_ => panic!("Invalid funct3 for store instName"), 
+			instName: match (code & 0x7f, (code >> 12) & 0x7) {
+				(35, 0) => Instruction::SB,
+				(35, 1) => Instruction::SH,
+				(35, 2) => Instruction::SW,
+				(35, 3) => Instruction::SD,
+				(39, 2) => Instruction::FSW,
+				(39, 3) => Instruction::FSD,
+				_ => return Err(Exception::IllegalInstruction(code as u64)), 
 			},
 			rs1: (((code >> 15) & 0x1f) as u8),
 			rs2: (((code >> 20) & 0x1f) as u8),
@@ -512,7 +644,7 @@ impl StoreInst {
 		//println!("imm[0:4] = {:b}", ((code >> 7) & 0x1f) as i16);
 
 		//println!("imm[5:11] = {:b}", ((code as i32) >> 20) as i16);
-		inst
+		Ok(inst)
 	}
 }
 
@@ -534,7 +666,7 @@ pub struct BranchInst {
 
 //implementing new method for each struct
 impl BranchInst {
-	pub fn New(code: u32) -> BranchInst {
+	pub fn New(code: u32) -> Result<BranchInst, Exception> {
 		let inst = BranchInst {
 			instName: match ((code >> 12) & 0x7) {
 				0 => Instruction::BEQ,
@@ -543,7 +675,7 @@ impl BranchInst {
 				5 => Instruction::BGE,
 				6 => Instruction::BLTU,
 				7 => Instruction::BGEU,
-				_ => panic!("Invalid funct3 for branch instruction"),
+				_ => return Err(Exception::IllegalInstruction(code as u64)),
 			},
 			rs1: (((code >> 15) & 0x1f) as u8),
 			rs2: (((code >> 20) & 0x1f) as u8),
@@ -563,7 +695,7 @@ impl BranchInst {
 												//which means setting these all to 1
 			}
 		};
-		inst
+		Ok(inst)
 	}
 }
 
@@ -585,17 +717,17 @@ pub struct UpperImmInst {
 }
 
 impl UpperImmInst {
-	pub fn New(code: u32) -> UpperImmInst {
+	pub fn New(code: u32) -> Result<UpperImmInst, Exception> {
 		let inst = UpperImmInst {
 			instName: match code & 0x7f {
 				55 => Instruction::LUI,
 				23 => Instruction::AUIPC,
-				_ => panic!("Invalid Jump instruction opcode"),
+				_ => return Err(Exception::IllegalInstruction(code as u64)),
 			},
 			rd: (((code >> 7) & 0x1f) as u8),
 			imm: ((code as i32) >> 12),
 		};
-		inst
+		Ok(inst)
 	}
 }
 
@@ -616,7 +748,7 @@ pub struct JumpInst {
 
 
 impl JumpInst {
-	pub fn New(code: u32) -> JumpInst {
+	pub fn New(code: u32) -> Result<JumpInst, Exception> {
 		let inst = JumpInst {
 			instName: Instruction::JAL, 
 			rd: (((code >> 7) & 0x1f) as u8),
@@ -635,30 +767,31 @@ impl JumpInst {
 				total
 			},
 			};
-		inst
+		Ok(inst)
 	}
 }
 
 //user level csrs
-pub const USTATUS: usize = 0x0;
-pub const UIE: usize = 0x4;
-pub const UTVEC: usize = 0x5; 
-pub const USCRATCH: usize = 0x40;
-pub const UEPC: usize = 0x41;
-pub const UCAUSE: usize = 0x42;
-pub const UBADADDR: usize = 0x43; 
-pub const UIP: usize = 0x44;
+//float flags/rounding mode - views of fcsr
+pub const FFLAGS: usize = 0x1;
+pub const FRM: usize = 0x2;
+pub const FCSR: usize = 0x3;
+//read only shadows of the machine counters
 pub const CYCLE: usize = 0xc00;
 pub const TIME: usize = 0xc01;
 pub const INSTRET: usize = 0xc02;
 
 // Machine-level CSRs.
+// Vendor, architecture and implementation IDs. All 0 since this isnt a real chip
+pub const MVENDORID: usize = 0xf11;
+pub const MARCHID: usize = 0xf12;
+pub const MIMPID: usize = 0xf13;
 // Hardware thread ID.
 pub const MHARTID: usize = 0xf14;
 // Machine status register.
 pub const MSTATUS: usize = 0x300;
 //ISA and extensions
-pub const MIFA: usize = 0x301;
+pub const MISA: usize = 0x301;
 // Machine exception delefation register.
 pub const MEDELEG: usize = 0x302;
 // Machine interrupt delefation register.
@@ -676,21 +809,27 @@ pub const MEPC: usize = 0x341;
 // Machine trap cause.
 pub const MCAUSE: usize = 0x342;
 // Machine bad address or instruction.
-pub const MTBADADDR: usize = 0x343;
+pub const MTVAL: usize = 0x343;
 // Machine interrupt pending.
 pub const MIP: usize = 0x344;
+// Physical memory protection config (only the even ones exist on rv64) and addresses.
+pub const PMPCFG0: usize = 0x3a0;
+pub const PMPCFG15: usize = 0x3af;
+pub const PMPADDR0: usize = 0x3b0;
+pub const PMPADDR63: usize = 0x3ef;
+// Machine cycle and instructions retired counters.
+pub const MCYCLE: usize = 0xb00;
+pub const MINSTRET: usize = 0xb02;
 
 // Supervisor-level CSRs.
 // Supervisor status register.
 pub const SSTATUS: usize = 0x100;
-//exception delegaiton register
-pub const SEDELEG: usize = 0x102;
-//interrupt delegaiton register
-pub const SIDELEG: usize = 0x103;
 // Supervisor interrupt-enable register.
 pub const SIE: usize = 0x104;
 // Supervisor trap handler base address.
 pub const STVEC: usize = 0x105;
+// Supervisor counter enable.
+pub const SCOUNTEREN: usize = 0x106;
 // Scratch register for supervisor trap handlers.
 pub const SSCRATCH: usize = 0x140;
 // Supervisor exception program counter.
@@ -698,9 +837,46 @@ pub const SEPC: usize = 0x141;
 // Supervisor trap cause.
 pub const SCAUSE: usize = 0x142;
 // Supervisor bad address or instruction.
-pub const SBADADDR: usize = 0x143;
+pub const STVAL: usize = 0x143;
 // Supervisor interrupt pending.
 pub const SIP: usize = 0x144;
 // Supervisor address translation and protection.
 pub const SATP: usize = 0x180;
 
+// mstatus fields
+// Machine interrupt enable, and what it was before the last trap
+pub const MSTATUS_MIE: u64 = 1 << 3;
+pub const MSTATUS_MPIE: u64 = 1 << 7;
+// Privilege level before the last trap into machine mode. 2 bits wide
+pub const MSTATUS_MPP_SHIFT: u64 = 11;
+pub const MSTATUS_MPP: u64 = 0x3 << MSTATUS_MPP_SHIFT;
+// Supervisor versions. SPP is only 1 bit since it can only be U or S
+pub const MSTATUS_SIE: u64 = 1 << 1;
+pub const MSTATUS_SPIE: u64 = 1 << 5;
+pub const MSTATUS_SPP: u64 = 1 << 8;
+// Loads/stores use the privilege in MPP
+pub const MSTATUS_MPRV: u64 = 1 << 17;
+// Trap sfence.vma/satp accesses, wfi, and sret from supervisor mode
+pub const MSTATUS_TVM: u64 = 1 << 20;
+pub const MSTATUS_TW: u64 = 1 << 21;
+pub const MSTATUS_TSR: u64 = 1 << 22;
+// Float unit state (off, initial, clean, dirty). 2 bits wide
+pub const MSTATUS_FS: u64 = 0x3 << 13;
+pub const MSTATUS_FS_INITIAL: u64 = 0x1 << 13;
+// Supervisor can touch user pages, and loads from execute only pages work
+pub const MSTATUS_SUM: u64 = 1 << 18;
+pub const MSTATUS_MXR: u64 = 1 << 19;
+// XLEN of user and supervisor mode. Always 64 (2)
+pub const MSTATUS_UXL: u64 = 0x3 << 32;
+pub const MSTATUS_SXL: u64 = 0x3 << 34;
+// Set when FS is dirty
+pub const MSTATUS_SD: u64 = 1 << 63;
+
+// interrupt bits of mip/mie (and sip/sie)
+// software, timer and external interrupts for supervisor and machine mode
+pub const MIP_SSIP: u64 = 1 << 1;
+pub const MIP_MSIP: u64 = 1 << 3;
+pub const MIP_STIP: u64 = 1 << 5;
+pub const MIP_MTIP: u64 = 1 << 7;
+pub const MIP_SEIP: u64 = 1 << 9;
+pub const MIP_MEIP: u64 = 1 << 11;
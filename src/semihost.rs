@@ -0,0 +1,326 @@
+//riscv semihosting: the arm semihosting calls, reached with slli/ebreak/srai instead of
+//a bkpt. What openocd does for a real board, done against our own filesystem
+//
+//the operation goes in a0 and a1 is its argument, usually a pointer to a block of
+//xlen (so 8 byte) fields. The result comes back in a0, with most calls giving -1 on
+//failure and leaving the reason for SYS_ERRNO. Handles are never 0, ":tt" is the console
+//
+//addresses are physical, same as the newlib calls
+#![allow(non_snake_case)]
+
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::os::fd::AsFd;
+use std::process;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use crate::cpu::Cpu;
+
+const SYS_OPEN: u64 = 0x01;
+const SYS_CLOSE: u64 = 0x02;
+const SYS_WRITEC: u64 = 0x03;
+const SYS_WRITE0: u64 = 0x04;
+const SYS_WRITE: u64 = 0x05;
+const SYS_READ: u64 = 0x06;
+const SYS_READC: u64 = 0x07;
+const SYS_ISERROR: u64 = 0x08;
+const SYS_ISTTY: u64 = 0x09;
+const SYS_SEEK: u64 = 0x0a;
+const SYS_FLEN: u64 = 0x0c;
+const SYS_TMPNAM: u64 = 0x0d;
+const SYS_REMOVE: u64 = 0x0e;
+const SYS_RENAME: u64 = 0x0f;
+const SYS_CLOCK: u64 = 0x10;
+const SYS_TIME: u64 = 0x11;
+const SYS_SYSTEM: u64 = 0x12;
+const SYS_ERRNO: u64 = 0x13;
+const SYS_GET_CMDLINE: u64 = 0x15;
+const SYS_HEAPINFO: u64 = 0x16;
+const SYS_EXIT: u64 = 0x18;
+const SYS_EXIT_EXTENDED: u64 = 0x20;
+const SYS_ELAPSED: u64 = 0x30;
+const SYS_TICKFREQ: u64 = 0x31;
+
+//the exit reason for a program that finished normally. Anything else is a crash
+const ADP_STOPPED_APPLICATION_EXIT: u64 = 0x20026;
+
+//host errnos, handed back by SYS_ERRNO
+const EBADF: i64 = 9;
+const EFAULT: i64 = 14;
+const EINVAL: i64 = 22;
+const EMFILE: i64 = 24;
+const ENAMETOOLONG: i64 = 36;
+const ENOSYS: i64 = 38;
+
+const MAX_FILES: usize = 256;
+const MAX_PATH: u64 = 4096;
+
+pub struct Semihost {
+	files: Vec<Option<File>>, //handle n is files[n - 1]
+	cmdline: String, //for SYS_GET_CMDLINE, program name first
+	start: Instant, //for SYS_CLOCK and SYS_ELAPSED
+	errno: i64, //from the last call that failed
+}
+
+fn errno(e: io::Error) -> i64 {
+	e.raw_os_error().map_or(EINVAL, |e| e as i64)
+}
+
+//the block fields are xlen wide
+fn field(cpu: &mut Cpu, block: u64, i: u64) -> Result<u64, i64> {
+	cpu.bus.load(block.wrapping_add(i * 8), 8).map_err(|_| EFAULT)
+}
+
+fn read_mem(cpu: &mut Cpu, addr: u64, len: u64) -> Result<Vec<u8>, i64> {
+	cpu.bus.read_bytes(addr, len).map_err(|_| EFAULT)
+}
+
+//names come as a pointer field then a length field, the length not counting the nul on the end
+fn read_name(cpu: &mut Cpu, block: u64, i: u64) -> Result<String, i64> {
+	let (addr, len) = (field(cpu, block, i)?, field(cpu, block, i + 1)?);
+	if len > MAX_PATH {
+		return Err(ENAMETOOLONG);
+	}
+	Ok(String::from_utf8_lossy(&read_mem(cpu, addr, len)?).into_owned())
+}
+
+//what WRITEC and WRITE0 print goes to stdout, straight away
+fn console_write(text: &[u8]) -> u64 {
+	let mut out = io::stdout();
+	let _ = out.write_all(text);
+	let _ = out.flush();
+	0
+}
+
+fn console(fd: io::Result<std::os::fd::OwnedFd>) -> Result<File, i64> {
+	fd.map(File::from).map_err(errno)
+}
+
+impl Semihost {
+	pub fn New(args: &[String]) -> Semihost {
+		Semihost {
+			files: Vec::new(),
+			cmdline: args.join(" "),
+			start: Instant::now(),
+			errno: 0,
+		}
+	}
+
+	//call after every step. Does the call if the last instruction was a semihosting
+	//ebreak, and gives back the exit status once the program has exited
+	pub fn service(&mut self, cpu: &mut Cpu) -> Option<i32> {
+		if !cpu.semihostCalled {
+			return None;
+		}
+		cpu.semihostCalled = false;
+		self.call(cpu)
+	}
+
+	pub fn call(&mut self, cpu: &mut Cpu) -> Option<i32> {
+		let (op, arg) = (cpu.regs[10], cpu.regs[11]);
+		let result = match op {
+			SYS_OPEN => self.open(cpu, arg),
+			SYS_CLOSE => field(cpu, arg, 0).and_then(|h| self.close(h)),
+			SYS_WRITEC => read_mem(cpu, arg, 1).map(|c| console_write(&c)),
+			SYS_WRITE0 => {
+				let mut text = Vec::new();
+				loop {
+					match cpu.bus.load(arg.wrapping_add(text.len() as u64), 1) {
+						Ok(0) | Err(_) => break,
+						Ok(c) => text.push(c as u8),
+					}
+				}
+				Ok(console_write(&text))
+			},
+			SYS_WRITE => self.write(cpu, arg),
+			SYS_READ => self.read(cpu, arg),
+			SYS_READC => {
+				let mut c = [0];
+				io::stdin().read_exact(&mut c).map(|_| c[0] as u64).map_err(errno)
+			},
+			SYS_ISERROR => field(cpu, arg, 0).map(|status| ((status as i64) < 0) as u64),
+			SYS_ISTTY => field(cpu, arg, 0).and_then(|h| self.file(h)).map(|f| f.is_terminal() as u64),
+			SYS_SEEK => self.seek(cpu, arg),
+			SYS_FLEN => field(cpu, arg, 0).and_then(|h| self.file(h)?.metadata().map(|m| m.len()).map_err(errno)),
+			SYS_TMPNAM => self.tmpnam(cpu, arg),
+			SYS_REMOVE => self.remove(cpu, arg),
+			SYS_RENAME => self.rename(cpu, arg),
+			SYS_CLOCK => Ok((self.start.elapsed().as_millis() / 10) as u64),
+			SYS_TIME => Ok(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()),
+			//running host commands on behalf of the guest isnt something we want to do
+			SYS_SYSTEM => Err(ENOSYS),
+			SYS_ERRNO => Ok(self.errno as u64),
+			SYS_GET_CMDLINE => self.get_cmdline(cpu, arg),
+			//all zeroes means nobody knows, and the program goes by its linker symbols
+			SYS_HEAPINFO => field(cpu, arg, 0).and_then(|block| {
+				cpu.bus.write_bytes(block, &[0; 32]).map(|_| 0).map_err(|_| EFAULT)
+			}),
+			SYS_EXIT | SYS_EXIT_EXTENDED => return Some(self.exit_status(cpu, op, arg)),
+			SYS_ELAPSED => {
+				let ticks = self.start.elapsed().as_micros() as u64;
+				cpu.bus.write_bytes(arg, &ticks.to_le_bytes()).map(|_| 0).map_err(|_| EFAULT)
+			},
+			SYS_TICKFREQ => Ok(1_000_000),
+			_ => Err(ENOSYS),
+		};
+		match result {
+			Ok(val) => cpu.regs[10] = val,
+			Err(e) => {
+				self.errno = e;
+				cpu.regs[10] = u64::MAX;
+			},
+		}
+		None
+	}
+
+	fn file(&mut self, handle: u64) -> Result<&mut File, i64> {
+		let index = handle.checked_sub(1).ok_or(EBADF)?;
+		self.files.get_mut(index as usize).and_then(|f| f.as_mut()).ok_or(EBADF)
+	}
+
+	fn close(&mut self, handle: u64) -> Result<u64, i64> {
+		let index = handle.checked_sub(1).ok_or(EBADF)?;
+		match self.files.get_mut(index as usize).and_then(|f| f.take()) {
+			Some(_) => Ok(0),
+			None => Err(EBADF),
+		}
+	}
+
+	//block: name, mode, name length. The mode is an index into fopen's
+	//r rb r+ r+b w wb w+ w+b a ab a+ a+b
+	fn open(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		//the mode sits between the name and its length here
+		let (addr, mode, len) = (field(cpu, block, 0)?, field(cpu, block, 1)?, field(cpu, block, 2)?);
+		if len > MAX_PATH {
+			return Err(ENAMETOOLONG);
+		}
+		let name = String::from_utf8_lossy(&read_mem(cpu, addr, len)?).into_owned();
+		if mode > 11 {
+			return Err(EINVAL);
+		}
+		let file = if name == ":tt" {
+			match mode / 4 {
+				0 => console(io::stdin().as_fd().try_clone_to_owned())?,
+				1 => console(io::stdout().as_fd().try_clone_to_owned())?,
+				_ => console(io::stderr().as_fd().try_clone_to_owned())?,
+			}
+		}
+		else {
+			let mut opts = OpenOptions::new();
+			match mode / 2 {
+				0 => opts.read(true),
+				1 => opts.read(true).write(true),
+				2 => opts.write(true).create(true).truncate(true),
+				3 => opts.read(true).write(true).create(true).truncate(true),
+				4 => opts.append(true).create(true),
+				_ => opts.read(true).append(true).create(true),
+			};
+			opts.open(name).map_err(errno)?
+		};
+		match self.files.iter().position(|f| f.is_none()) {
+			Some(i) => {
+				self.files[i] = Some(file);
+				Ok(i as u64 + 1)
+			},
+			None if self.files.len() < MAX_FILES => {
+				self.files.push(Some(file));
+				Ok(self.files.len() as u64)
+			},
+			None => Err(EMFILE),
+		}
+	}
+
+	//block: handle, buffer, length. Gives back how many bytes didnt get written
+	fn write(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		let (handle, addr, len) = (field(cpu, block, 0)?, field(cpu, block, 1)?, field(cpu, block, 2)?);
+		let data = read_mem(cpu, addr, len)?;
+		let file = self.file(handle)?;
+		match file.write_all(&data).and_then(|_| file.flush()) {
+			Ok(()) => Ok(0),
+			Err(e) => {
+				self.errno = errno(e);
+				Ok(len)
+			},
+		}
+	}
+
+	//same block as write. Gives back how many bytes it was short, so len means end of file
+	fn read(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		let (handle, addr, len) = (field(cpu, block, 0)?, field(cpu, block, 1)?, field(cpu, block, 2)?);
+		if !cpu.bus.dram.contains(addr, len) {
+			return Err(EFAULT);
+		}
+		let mut buf = vec![0; len as usize];
+		let n = match self.file(handle)?.read(&mut buf) {
+			Ok(n) => n,
+			Err(e) => {
+				self.errno = errno(e);
+				return Ok(len);
+			},
+		};
+		cpu.bus.write_bytes(addr, &buf[..n]).map_err(|_| EFAULT)?;
+		Ok(len - n as u64)
+	}
+
+	//block: handle, position from the start
+	fn seek(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		let (handle, pos) = (field(cpu, block, 0)?, field(cpu, block, 1)?);
+		self.file(handle)?.seek(SeekFrom::Start(pos)).map(|_| 0).map_err(errno)
+	}
+
+	//block: buffer, id (0-255), buffer length
+	fn tmpnam(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		let (addr, id, len) = (field(cpu, block, 0)?, field(cpu, block, 1)?, field(cpu, block, 2)?);
+		if id > 255 {
+			return Err(EINVAL);
+		}
+		let path = env::temp_dir().join(format!("riscvEmu-{}-{:03}", process::id(), id));
+		let mut name = path.to_string_lossy().into_owned().into_bytes();
+		name.push(0);
+		if name.len() as u64 > len {
+			return Err(ENAMETOOLONG);
+		}
+		cpu.bus.write_bytes(addr, &name).map(|_| 0).map_err(|_| EFAULT)
+	}
+
+	//block: name, its length
+	fn remove(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		let name = read_name(cpu, block, 0)?;
+		fs::remove_file(name).map(|_| 0).map_err(errno)
+	}
+
+	//block: old name, its length, new name, its length
+	fn rename(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		let old = read_name(cpu, block, 0)?;
+		let new = read_name(cpu, block, 2)?;
+		fs::rename(old, new).map(|_| 0).map_err(errno)
+	}
+
+	//block: buffer, its length. The length gets set to how long the command line is
+	fn get_cmdline(&mut self, cpu: &mut Cpu, block: u64) -> Result<u64, i64> {
+		let (addr, len) = (field(cpu, block, 0)?, field(cpu, block, 1)?);
+		let mut line = self.cmdline.clone().into_bytes();
+		if line.len() as u64 >= len {
+			return Err(EINVAL);
+		}
+		let cmdLen = line.len() as u64;
+		line.push(0);
+		cpu.bus.write_bytes(addr, &line).map_err(|_| EFAULT)?;
+		cpu.bus.store(block + 8, cmdLen, 8).map_err(|_| EFAULT)?;
+		Ok(0)
+	}
+
+	//on rv64 SYS_EXIT takes a block of reason and exit code, same as SYS_EXIT_EXTENDED.
+	//Some code still passes the reason straight in a1 the 32 bit way, which means exit code 0.
+	//Any reason other than a normal exit is a crash, and gets 1
+	fn exit_status(&mut self, cpu: &mut Cpu, op: u64, arg: u64) -> i32 {
+		if op == SYS_EXIT && arg == ADP_STOPPED_APPLICATION_EXIT {
+			return 0;
+		}
+		match (field(cpu, arg, 0), field(cpu, arg, 1)) {
+			(Ok(ADP_STOPPED_APPLICATION_EXIT), Ok(code)) => code as i32,
+			_ => 1,
+		}
+	}
+}
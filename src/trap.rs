@@ -0,0 +1,99 @@
+//exceptions that can come out of fetching, decoding or executing an instruction
+//rather than panicking, the cpu turns these into traps - saves where it was in
+//mepc, why in mcause, extra info in mtval, then jumps to mtvec
+//
+//the u64 each variant carries is what ends up in mtval
+//(faulting address for memory stuff, the instruction bits for illegal instructions)
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum Exception {
+	InstructionAddressMisaligned(u64),
+	InstructionAccessFault(u64),
+	IllegalInstruction(u64),
+	Breakpoint(u64),
+	LoadAddressMisaligned(u64),
+	LoadAccessFault(u64),
+	StoreAddressMisaligned(u64), //includes AMOs
+	StoreAccessFault(u64),
+	EnvironmentCallFromU,
+	EnvironmentCallFromS,
+	EnvironmentCallFromM,
+	InstructionPageFault(u64),
+	LoadPageFault(u64),
+	StorePageFault(u64), //includes AMOs
+}
+
+impl Exception {
+	//exception code that goes in mcause (interrupt bit clear)
+	pub fn code(&self) -> u64 {
+		match self {
+			Exception::InstructionAddressMisaligned(_) => 0,
+			Exception::InstructionAccessFault(_) => 1,
+			Exception::IllegalInstruction(_) => 2,
+			Exception::Breakpoint(_) => 3,
+			Exception::LoadAddressMisaligned(_) => 4,
+			Exception::LoadAccessFault(_) => 5,
+			Exception::StoreAddressMisaligned(_) => 6,
+			Exception::StoreAccessFault(_) => 7,
+			Exception::EnvironmentCallFromU => 8,
+			Exception::EnvironmentCallFromS => 9,
+			Exception::EnvironmentCallFromM => 11,
+			Exception::InstructionPageFault(_) => 12,
+			Exception::LoadPageFault(_) => 13,
+			Exception::StorePageFault(_) => 15,
+		}
+	}
+
+	//value for mtval
+	pub fn tval(&self) -> u64 {
+		match *self {
+			Exception::InstructionAddressMisaligned(val)
+			| Exception::InstructionAccessFault(val)
+			| Exception::IllegalInstruction(val)
+			| Exception::Breakpoint(val)
+			| Exception::LoadAddressMisaligned(val)
+			| Exception::LoadAccessFault(val)
+			| Exception::StoreAddressMisaligned(val)
+			| Exception::StoreAccessFault(val)
+			| Exception::InstructionPageFault(val)
+			| Exception::LoadPageFault(val)
+			| Exception::StorePageFault(val) => val,
+			Exception::EnvironmentCallFromU
+			| Exception::EnvironmentCallFromS
+			| Exception::EnvironmentCallFromM => 0,
+		}
+	}
+}
+
+//interrupts, by their mcause code (with the interrupt bit set on top)
+#[derive(Debug)]
+#[derive(Copy, Clone, PartialEq)]
+pub enum Interrupt {
+	SupervisorSoftware = 1,
+	MachineSoftware = 3,
+	SupervisorTimer = 5,
+	MachineTimer = 7,
+	SupervisorExternal = 9,
+	MachineExternal = 11,
+}
+
+//order interrupts are taken in when more than one is pending
+pub const INTERRUPT_PRIORITY: [Interrupt; 6] = [
+	Interrupt::MachineExternal,
+	Interrupt::MachineSoftware,
+	Interrupt::MachineTimer,
+	Interrupt::SupervisorExternal,
+	Interrupt::SupervisorSoftware,
+	Interrupt::SupervisorTimer,
+];
+
+impl Interrupt {
+	pub fn code(&self) -> u64 {
+		*self as u64
+	}
+
+	//the mip/mie bit for this interrupt
+	pub fn bit(&self) -> u64 {
+		1 << self.code()
+	}
+}
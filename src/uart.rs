@@ -0,0 +1,197 @@
+//ns16550a uart
+//transmit goes straight to stdout (so the transmitter is always empty), receive comes
+//from the host console if one is attached
+//
+//registers, one byte each:
+//0 RBR (read) / THR (write) / DLL when DLAB is set
+//1 IER / DLM when DLAB is set
+//2 IIR (read) / FCR (write)
+//3 LCR, 4 MCR, 5 LSR, 6 MSR, 7 SCR
+
+use std::collections::VecDeque;
+use std::io::{self, Write};
+
+use crate::console::Console;
+use crate::device::Device;
+
+pub const UART_BASE: u64 = 0x1000_0000;
+pub const UART_SIZE: u64 = 0x100;
+//plic source the uart is wired to
+pub const UART_IRQ: usize = 10;
+
+const RBR: u64 = 0;
+const IER: u64 = 1;
+const IIR: u64 = 2;
+const LCR: u64 = 3;
+const MCR: u64 = 4;
+const LSR: u64 = 5;
+const MSR: u64 = 6;
+const SCR: u64 = 7;
+
+const IER_RDA: u8 = 1 << 0; //received data available
+const IER_THRE: u8 = 1 << 1; //transmitter empty
+const IIR_NONE: u8 = 0x1;
+const IIR_THRE: u8 = 0x2;
+const IIR_RDA: u8 = 0x4;
+const IIR_FIFO: u8 = 0xc0; //fifos enabled
+const FCR_FIFO: u8 = 1 << 0;
+const FCR_CLEAR_RX: u8 = 1 << 1;
+const LCR_DLAB: u8 = 1 << 7;
+const MCR_LOOP: u8 = 1 << 4;
+const LSR_DR: u8 = 1 << 0; //data ready
+const LSR_THRE: u8 = 1 << 5;
+const LSR_TEMT: u8 = 1 << 6;
+
+const FIFO_SIZE: usize = 16;
+
+pub struct Uart {
+	rx: VecDeque<u8>,
+	ier: u8,
+	fcr: u8,
+	lcr: u8,
+	mcr: u8,
+	scr: u8,
+	dll: u8,
+	dlm: u8,
+	threPending: bool, //the transmitter empty interrupt, cleared by reading iir
+	console: Option<Console>,
+}
+
+impl Uart {
+	pub fn New() -> Uart {
+		Uart {
+			rx: VecDeque::new(),
+			ier: 0,
+			fcr: 0,
+			lcr: 0,
+			mcr: 0,
+			scr: 0,
+			dll: 0,
+			dlm: 0,
+			threPending: false,
+			console: None,
+		}
+	}
+
+	//hook up host stdin as the receive side
+	pub fn attach_console(&mut self, console: Console) {
+		self.console = Some(console);
+	}
+
+	//data arriving on the line
+	pub fn receive(&mut self, byte: u8) {
+		if self.rx.len() < FIFO_SIZE {
+			self.rx.push_back(byte);
+		}
+	}
+
+	//highest priority interrupt going. Received data beats transmitter empty
+	fn interrupt_id(&self) -> u8 {
+		if self.ier & IER_RDA != 0 && !self.rx.is_empty() {
+			return IIR_RDA;
+		}
+		if self.ier & IER_THRE != 0 && self.threPending {
+			return IIR_THRE;
+		}
+		IIR_NONE
+	}
+
+	fn transmit(&mut self, byte: u8) {
+		//loopback mode sends it straight back to ourselves
+		if self.mcr & MCR_LOOP != 0 {
+			self.receive(byte);
+		}
+		else {
+			let mut out = io::stdout();
+			//nothing sensible to do if stdout has gone away
+			let _ = out.write_all(&[byte]);
+			let _ = out.flush();
+		}
+		//sent immediately so its empty again straight away
+		self.threPending = true;
+	}
+}
+
+impl Device for Uart {
+	//reading rbr and iir have side effects, so this needs &mut
+	fn load(&mut self, offset: u64, _size: u8) -> u64 {
+		let dlab = self.lcr & LCR_DLAB != 0;
+		let val = match offset {
+			RBR if dlab => self.dll,
+			RBR => self.rx.pop_front().unwrap_or(0),
+			IER if dlab => self.dlm,
+			IER => self.ier,
+			IIR => {
+				let id = self.interrupt_id();
+				if id == IIR_THRE {
+					self.threPending = false;
+				}
+				let fifo = if self.fcr & FCR_FIFO != 0 { IIR_FIFO } else { 0 };
+				id | fifo
+			},
+			LCR => self.lcr,
+			MCR => self.mcr,
+			LSR => {
+				let ready = if self.rx.is_empty() { 0 } else { LSR_DR };
+				ready | LSR_THRE | LSR_TEMT
+			},
+			//carrier detect, data set ready and clear to send are always on
+			MSR => 0xb0,
+			SCR => self.scr,
+			_ => 0,
+		};
+		val as u64
+	}
+
+	fn store(&mut self, offset: u64, data: u64, _size: u8) {
+		let data = data as u8;
+		let dlab = self.lcr & LCR_DLAB != 0;
+		match offset {
+			RBR if dlab => self.dll = data,
+			RBR => self.transmit(data),
+			IER if dlab => self.dlm = data,
+			IER => {
+				//turning on the transmit interrupt fires it straight away since were always empty
+				if data & IER_THRE != 0 && self.ier & IER_THRE == 0 {
+					self.threPending = true;
+				}
+				self.ier = data & 0xf;
+			},
+			IIR => {
+				self.fcr = data;
+				if data & FCR_CLEAR_RX != 0 {
+					self.rx.clear();
+				}
+			},
+			LCR => self.lcr = data,
+			MCR => self.mcr = data & 0x1f,
+			SCR => self.scr = data,
+			_ => {},
+		}
+	}
+
+	//the console stays plugged in
+	fn reset(&mut self) {
+		let console = self.console.take();
+		*self = Uart::New();
+		self.console = console;
+	}
+
+	//pulls in whatever has been typed, as long as theres room for it
+	fn tick(&mut self) {
+		while self.rx.len() < FIFO_SIZE {
+			match self.console.as_mut().and_then(|c| c.read()) {
+				Some(byte) => self.rx.push_back(byte),
+				None => break,
+			}
+		}
+	}
+
+	fn irq(&self) -> Option<usize> {
+		Some(UART_IRQ)
+	}
+
+	fn irq_pending(&self) -> bool {
+		self.interrupt_id() != IIR_NONE
+	}
+}